@@ -0,0 +1,873 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use reqwest::{header::CONTENT_TYPE, Client, Response};
+use serde::Deserialize;
+use sha3::{Digest, Sha3_256};
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{info, warn};
+
+use crate::{
+    config::AptosConfig,
+    models::{AccountAddress, MintRequest, PendingTransfer},
+    queue::{AptosClient, TransferJournal, TransferState},
+};
+
+const SIGNED_TXN_CONTENT_TYPE: &str = "application/x.aptos.signed_transaction+bcs";
+const RAW_TXN_SALT: &[u8] = b"APTOS::RawTransaction";
+const TXN_SALT: &[u8] = b"APTOS::Transaction";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+/// 节点因 sequence number 不匹配拒绝交易时，重新读取并再签名的次数
+const MAX_SEQUENCE_RETRIES: usize = 3;
+const COMMIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 通过全节点 REST 接口提交 `0x1::aptos_account::transfer` 交易的客户端。
+///
+/// 出资账户的 sequence number 在本地缓存并串行递增。web 与两个机器人共用同一个出资账户，
+/// 其他进程提交后本地缓存会落后，节点以 sequence number 不匹配拒绝时在同一次调用中
+/// 重新读取并再次提交；其余失败同样丢弃缓存，下次提交前重新读取。
+///
+/// 交易哈希在本地计算，每次广播前先交给 [`TransferJournal`] 记录，
+/// 调用方在超时或崩溃后据此查询结果，而不是换一个 sequence number 再转一次。
+#[derive(Clone)]
+pub struct RestAptosClient {
+    http: Client,
+    base_url: String,
+    signing_key: Arc<SigningKey>,
+    sender: AccountAddress,
+    chain_id: Arc<OnceCell<u8>>,
+    sequence: Arc<Mutex<Option<u64>>>,
+    max_gas_amount: u64,
+    gas_unit_price: Option<u64>,
+    txn_expiry_secs: u64,
+}
+
+impl RestAptosClient {
+    pub fn new(config: &AptosConfig) -> Result<Self> {
+        let signing_key = SigningKey::from_bytes(&parse_private_key(&config.private_key)?);
+        let sender = match &config.funder_address {
+            Some(address) => address.parse()?,
+            None => derive_address(signing_key.verifying_key().as_bytes()),
+        };
+
+        let chain_id = OnceCell::new();
+        if let Some(id) = config.chain_id {
+            chain_id.set(id).ok();
+        }
+
+        let http = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        let client = Self {
+            http,
+            base_url: config.node_url.trim_end_matches('/').to_string(),
+            signing_key: Arc::new(signing_key),
+            sender,
+            chain_id: Arc::new(chain_id),
+            sequence: Arc::new(Mutex::new(None)),
+            max_gas_amount: config.max_gas_amount,
+            gas_unit_price: config.gas_unit_price,
+            txn_expiry_secs: config.txn_expiry_secs,
+        };
+        info!(funder = %client.sender, node = %client.base_url, "aptos_client_ready");
+        Ok(client)
+    }
+
+    pub fn sender(&self) -> AccountAddress {
+        self.sender
+    }
+
+    /// 从出资账户向 `recipient` 转账 `amount` octas，交易上链并执行成功后返回交易哈希。
+    pub async fn transfer(
+        &self,
+        recipient: &AccountAddress,
+        amount: u64,
+        journal: &dyn TransferJournal,
+    ) -> Result<String> {
+        let transfer = self.submit(recipient, amount, journal).await?;
+        let state = self.confirm(&transfer).await?;
+        if state != TransferState::Committed {
+            journal.discard(&transfer);
+        }
+        match state {
+            TransferState::Committed => Ok(transfer.hash),
+            TransferState::Aborted(vm_status) => anyhow::bail!(
+                "aptos transaction {} failed on chain: {vm_status}",
+                transfer.hash
+            ),
+            TransferState::Expired => anyhow::bail!(
+                "aptos transaction {} was not committed before it expired",
+                transfer.hash
+            ),
+        }
+    }
+
+    /// 查询交易结果；交易仍在 mempool 或尚未被节点看到时等待，直到上链或确定已过期。
+    ///
+    /// 网络错误与节点的 5xx、429 响应只记录日志并继续查询；到期时最后一次查询仍失败则
+    /// 返回错误而不是 `Expired`，因为交易可能已经上链。
+    pub async fn confirm(&self, transfer: &PendingTransfer) -> Result<TransferState> {
+        // 过期时间之后交易不会再被执行，多等一个请求超时覆盖节点与本地的时钟差
+        let deadline = transfer.expires_at + chrono::Duration::from_std(REQUEST_TIMEOUT)?;
+        loop {
+            let lookup = self.lookup(&transfer.hash).await;
+            let failed = match lookup {
+                Ok(Some(state)) => return Ok(state),
+                Ok(None) => None,
+                Err(err) if is_transient(&err) => {
+                    warn!(hash = %transfer.hash, error = %err, "aptos_lookup_failed");
+                    Some(err)
+                }
+                Err(err) => return Err(err),
+            };
+            if Utc::now() >= deadline {
+                return match failed {
+                    Some(err) => Err(err),
+                    None => Ok(TransferState::Expired),
+                };
+            }
+            tokio::time::sleep(COMMIT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// 查询一次交易；节点尚未看到或交易仍在 mempool 中时返回 `None`
+    async fn lookup(&self, hash: &str) -> Result<Option<TransferState>> {
+        let response = self
+            .http
+            .get(self.url(&format!("transactions/by_hash/{hash}")))
+            .send()
+            .await
+            .context("failed to look up aptos transaction")?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let transaction: TransactionInfo = decode(response, "transaction").await?;
+        Ok(match transaction.success {
+            Some(true) => Some(TransferState::Committed),
+            Some(false) => Some(TransferState::Aborted(
+                transaction.vm_status.unwrap_or_default(),
+            )),
+            None => None,
+        })
+    }
+
+    /// `confirm` 最坏情况下的耗时：等到过期加时钟余量，再加最后一次查询与一次轮询间隔
+    fn max_confirm_duration(&self) -> Duration {
+        Duration::from_secs(self.txn_expiry_secs) + REQUEST_TIMEOUT * 2 + COMMIT_POLL_INTERVAL
+    }
+
+    async fn submit(
+        &self,
+        recipient: &AccountAddress,
+        amount: u64,
+        journal: &dyn TransferJournal,
+    ) -> Result<PendingTransfer> {
+        let chain_id = self.chain_id().await?;
+        let gas_unit_price = match self.gas_unit_price {
+            Some(price) => price,
+            None => self.estimate_gas_price().await?,
+        };
+
+        let mut sequence = self.sequence.lock().await;
+        let mut retries = 0;
+        loop {
+            let sequence_number = match *sequence {
+                Some(value) => value,
+                None => self.fetch_sequence_number().await?,
+            };
+            let expires_at = Utc::now() + chrono::Duration::seconds(self.txn_expiry_secs as i64);
+            let signed = self.sign(
+                sequence_number,
+                recipient,
+                amount,
+                gas_unit_price,
+                chain_id,
+                expires_at,
+            );
+            let transfer = PendingTransfer {
+                hash: transaction_hash(&signed),
+                sequence_number,
+                expires_at,
+            };
+            // 记录失败时交易没有发出，本地缓存的 sequence number 仍然有效
+            journal.record(&transfer).await?;
+
+            match self.broadcast(signed).await {
+                Ok(hash) => {
+                    *sequence = Some(sequence_number + 1);
+                    if hash == transfer.hash {
+                        return Ok(transfer);
+                    }
+                    // 以节点返回的哈希为准，否则之后的查询会误判交易已过期
+                    warn!(expected = %transfer.hash, %hash, "aptos_hash_mismatch");
+                    let transfer = PendingTransfer { hash, ..transfer };
+                    journal.record(&transfer).await?;
+                    return Ok(transfer);
+                }
+                Err(err) => {
+                    // 链上状态未知，之后重新读取 sequence number
+                    *sequence = None;
+                    if is_rejected(&err) {
+                        journal.discard(&transfer);
+                    }
+                    // 被拒绝的交易不会上链，换一个 sequence number 重签不会重复转账
+                    if retries < MAX_SEQUENCE_RETRIES && is_sequence_mismatch(&err) {
+                        retries += 1;
+                        warn!(sequence_number, retries, error = %err, "aptos_sequence_mismatch");
+                        continue;
+                    }
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// `submit` 最坏情况下的耗时：读取 chain id 与 gas 价格，每次签名前读取 sequence number 并广播
+    fn max_submit_duration(&self) -> Duration {
+        let requests = 2 + 2 * (MAX_SEQUENCE_RETRIES as u32 + 1);
+        REQUEST_TIMEOUT * requests
+    }
+
+    fn sign(
+        &self,
+        sequence_number: u64,
+        recipient: &AccountAddress,
+        amount: u64,
+        gas_unit_price: u64,
+        chain_id: u8,
+        expires_at: DateTime<Utc>,
+    ) -> Vec<u8> {
+        let raw = RawTransfer {
+            sender: *self.sender.as_bytes(),
+            sequence_number,
+            recipient: *recipient.as_bytes(),
+            amount,
+            max_gas_amount: self.max_gas_amount,
+            gas_unit_price,
+            // 向下取整，链上的过期时间不晚于记录的 `expires_at`
+            expiration_timestamp_secs: expires_at.timestamp() as u64,
+            chain_id,
+        }
+        .to_bcs();
+        sign_transaction(&self.signing_key, raw)
+    }
+
+    async fn broadcast(&self, signed: Vec<u8>) -> Result<String> {
+        let response = self
+            .http
+            .post(self.url("transactions"))
+            .header(CONTENT_TYPE, SIGNED_TXN_CONTENT_TYPE)
+            .body(signed)
+            .send()
+            .await
+            .context("failed to submit aptos transaction")?;
+        let pending: PendingTransaction = decode(response, "submit transaction").await?;
+        Ok(pending.hash)
+    }
+
+    async fn chain_id(&self) -> Result<u8> {
+        self.chain_id
+            .get_or_try_init(|| async {
+                let response = self
+                    .http
+                    .get(&self.base_url)
+                    .send()
+                    .await
+                    .context("failed to fetch aptos ledger info")?;
+                let info: LedgerInfo = decode(response, "ledger info").await?;
+                Ok(info.chain_id)
+            })
+            .await
+            .copied()
+    }
+
+    async fn fetch_sequence_number(&self) -> Result<u64> {
+        let response = self
+            .http
+            .get(self.url(&format!("accounts/{}", self.sender)))
+            .send()
+            .await
+            .context("failed to fetch funder account")?;
+        let account: AccountData = decode(response, "funder account").await?;
+        account
+            .sequence_number
+            .parse()
+            .context("invalid sequence number in account response")
+    }
+
+    async fn estimate_gas_price(&self) -> Result<u64> {
+        let response = self
+            .http
+            .get(self.url("estimate_gas_price"))
+            .send()
+            .await
+            .context("failed to estimate gas price")?;
+        let estimate: GasEstimate = decode(response, "gas estimate").await?;
+        Ok(estimate.gas_estimate)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+}
+
+#[async_trait]
+impl AptosClient for RestAptosClient {
+    async fn submit_transfer(
+        &self,
+        request: &MintRequest,
+        journal: &dyn TransferJournal,
+    ) -> Result<String> {
+        let recipient = request
+            .recipient
+            .with_context(|| format!("mint request {} has no recipient address", request.id))?;
+        let hash = self.transfer(&recipient, request.amount, journal).await?;
+        info!(request_id = %request.id, %recipient, %hash, "aptos_transfer_submitted");
+        Ok(hash)
+    }
+
+    async fn confirm_transfer(&self, transfer: &PendingTransfer) -> Result<TransferState> {
+        self.confirm(transfer).await
+    }
+
+    fn max_attempt_duration(&self) -> Duration {
+        // 先确认上一次尝试记录的交易，再提交并确认新交易
+        self.max_confirm_duration() + self.max_submit_duration() + self.max_confirm_duration()
+    }
+}
+
+async fn decode<T: for<'de> Deserialize<'de>>(response: Response, what: &str) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<NodeError>(&body)
+            .map(|err| err.message)
+            .unwrap_or(body);
+        return Err(NodeRejection {
+            what: what.to_string(),
+            status,
+            message,
+        }
+        .into());
+    }
+    response
+        .json()
+        .await
+        .with_context(|| format!("failed to decode aptos {what} response"))
+}
+
+#[derive(Debug, Deserialize)]
+struct LedgerInfo {
+    chain_id: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountData {
+    sequence_number: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GasEstimate {
+    gas_estimate: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct PendingTransaction {
+    hash: String,
+}
+
+/// 已上链的交易带有执行结果，仍在 mempool 中的交易没有 `success`
+#[derive(Debug, Deserialize)]
+struct TransactionInfo {
+    #[serde(default)]
+    success: Option<bool>,
+    #[serde(default)]
+    vm_status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeError {
+    message: String,
+}
+
+/// 节点返回的非 2xx 响应
+#[derive(Debug)]
+struct NodeRejection {
+    what: String,
+    status: reqwest::StatusCode,
+    message: String,
+}
+
+impl std::fmt::Display for NodeRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "aptos node rejected {}: status {}: {}",
+            self.what, self.status, self.message
+        )
+    }
+}
+
+impl std::error::Error for NodeRejection {}
+
+/// 可以重试的查询失败：请求未得到响应，或节点暂时不可用
+fn is_transient(err: &anyhow::Error) -> bool {
+    if let Some(rejection) = err.downcast_ref::<NodeRejection>() {
+        return rejection.status.is_server_error()
+            || rejection.status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+    }
+    err.downcast_ref::<reqwest::Error>()
+        .is_some_and(|err| err.is_timeout() || err.is_connect() || err.is_request())
+}
+
+/// 节点以 4xx 拒绝了交易，交易不会进入 mempool；超时与 5xx 时交易仍可能上链
+fn is_rejected(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NodeRejection>()
+        .is_some_and(|rejection| rejection.status.is_client_error())
+}
+
+/// 节点以出资账户的 sequence number 过旧或过新为由拒绝了交易
+fn is_sequence_mismatch(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<NodeRejection>()
+        .is_some_and(|rejection| {
+            rejection.message.contains("SEQUENCE_NUMBER_TOO_OLD")
+                || rejection.message.contains("SEQUENCE_NUMBER_TOO_NEW")
+        })
+}
+
+/// 仅覆盖 `0x1::aptos_account::transfer(address, u64)` 的 RawTransaction。
+struct RawTransfer {
+    sender: [u8; 32],
+    sequence_number: u64,
+    recipient: [u8; 32],
+    amount: u64,
+    max_gas_amount: u64,
+    gas_unit_price: u64,
+    expiration_timestamp_secs: u64,
+    chain_id: u8,
+}
+
+impl RawTransfer {
+    fn to_bcs(&self) -> Vec<u8> {
+        let mut framework = [0u8; 32];
+        framework[31] = 1;
+
+        let mut buf = Vec::with_capacity(192);
+        buf.extend_from_slice(&self.sender);
+        buf.extend_from_slice(&self.sequence_number.to_le_bytes());
+        // TransactionPayload::EntryFunction
+        write_uleb128(&mut buf, 2);
+        buf.extend_from_slice(&framework);
+        write_bytes(&mut buf, b"aptos_account");
+        write_bytes(&mut buf, b"transfer");
+        // 无类型参数，两个 BCS 编码后的实参
+        write_uleb128(&mut buf, 0);
+        write_uleb128(&mut buf, 2);
+        write_bytes(&mut buf, &self.recipient);
+        write_bytes(&mut buf, &self.amount.to_le_bytes());
+        buf.extend_from_slice(&self.max_gas_amount.to_le_bytes());
+        buf.extend_from_slice(&self.gas_unit_price.to_le_bytes());
+        buf.extend_from_slice(&self.expiration_timestamp_secs.to_le_bytes());
+        buf.push(self.chain_id);
+        buf
+    }
+}
+
+fn signing_message(raw: &[u8]) -> Vec<u8> {
+    let mut message = Sha3_256::digest(RAW_TXN_SALT).to_vec();
+    message.extend_from_slice(raw);
+    message
+}
+
+/// 与节点计算方式一致的交易哈希，`0x00` 是 `Transaction::UserTransaction` 的 BCS 变体序号
+fn transaction_hash(signed: &[u8]) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(Sha3_256::digest(TXN_SALT));
+    hasher.update([0u8]);
+    hasher.update(signed);
+    format!("0x{}", hex::encode(hasher.finalize()))
+}
+
+fn sign_transaction(key: &SigningKey, raw: Vec<u8>) -> Vec<u8> {
+    let signature = key.sign(&signing_message(&raw));
+    let mut signed = raw;
+    // TransactionAuthenticator::Ed25519
+    write_uleb128(&mut signed, 0);
+    write_bytes(&mut signed, key.verifying_key().as_bytes());
+    write_bytes(&mut signed, &signature.to_bytes());
+    signed
+}
+
+fn write_uleb128(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_uleb128(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// 用单签 Ed25519 公钥验证消息签名，成功时返回该公钥对应的账户地址。
+///
+/// 地址按未轮换过密钥的账户推导，轮换过认证密钥的账户地址与之不同。
+pub fn verify_signed_message(
+    public_key: &[u8; 32],
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<AccountAddress> {
+    let key = VerifyingKey::from_bytes(public_key).context("invalid ed25519 public key")?;
+    key.verify_strict(message, &Signature::from_bytes(signature))
+        .context("ed25519 signature does not match")?;
+    Ok(derive_address(public_key))
+}
+
+fn derive_address(public_key: &[u8; 32]) -> AccountAddress {
+    // 单签 Ed25519 的认证密钥为 sha3-256(public_key | 0x00)
+    let mut hasher = Sha3_256::new();
+    hasher.update(public_key);
+    hasher.update([0u8]);
+    AccountAddress::new(hasher.finalize().into())
+}
+
+fn parse_private_key(value: &str) -> Result<[u8; 32]> {
+    let trimmed = value.trim();
+    let trimmed = trimmed.strip_prefix("ed25519-priv-").unwrap_or(trimmed);
+    let trimmed = trimmed.strip_prefix("0x").unwrap_or(trimmed);
+    let bytes = hex::decode(trimmed).context("aptos private key is not valid hex")?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("aptos private key must be 32 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+    use axum::{
+        body::Bytes,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::{get, post},
+        Json, Router,
+    };
+    use ed25519_dalek::Verifier;
+    use serde_json::json;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Node {
+        account_hits: Arc<AtomicUsize>,
+        /// 链上的出资账户 sequence number 比本地缓存多出的交易数（其他进程提交）
+        external_txns: Arc<AtomicU64>,
+        submitted: Arc<Mutex<Vec<Vec<u8>>>>,
+        /// 下一次提交被拒绝时返回的错误
+        reject: Arc<std::sync::Mutex<Option<&'static str>>>,
+        abort_on_chain: Arc<AtomicBool>,
+        /// 之后的这么多次交易查询返回 502
+        lookup_failures: Arc<AtomicUsize>,
+    }
+
+    async fn ledger() -> Json<serde_json::Value> {
+        Json(json!({"chain_id": 4, "ledger_version": "1"}))
+    }
+
+    async fn account(State(node): State<Node>) -> Json<serde_json::Value> {
+        node.account_hits.fetch_add(1, Ordering::SeqCst);
+        let sequence = 7 + node.external_txns.load(Ordering::SeqCst);
+        Json(json!({"sequence_number": sequence.to_string(), "authentication_key": "0x0"}))
+    }
+
+    async fn gas() -> Json<serde_json::Value> {
+        Json(json!({"gas_estimate": 100}))
+    }
+
+    async fn submit(
+        State(node): State<Node>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> impl IntoResponse {
+        assert_eq!(
+            headers[axum::http::header::CONTENT_TYPE],
+            SIGNED_TXN_CONTENT_TYPE
+        );
+        if let Some(message) = node.reject.lock().unwrap().take() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"message": message, "error_code": "vm_error"})),
+            );
+        }
+        node.submitted.lock().await.push(body.to_vec());
+        (
+            StatusCode::ACCEPTED,
+            Json(json!({"hash": transaction_hash(&body)})),
+        )
+    }
+
+    async fn by_hash(State(node): State<Node>, Path(hash): Path<String>) -> impl IntoResponse {
+        let failing = node
+            .lookup_failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"message": "upstream unavailable"})),
+            );
+        }
+        let submitted = node.submitted.lock().await;
+        if !submitted.iter().any(|body| transaction_hash(body) == hash) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(
+                    json!({"message": "Transaction not found", "error_code": "transaction_not_found"}),
+                ),
+            );
+        }
+        let success = !node.abort_on_chain.load(Ordering::SeqCst);
+        let vm_status = if success {
+            "Executed successfully"
+        } else {
+            "Move abort: EINSUFFICIENT_BALANCE"
+        };
+        (
+            StatusCode::OK,
+            Json(json!({
+                "type": "user_transaction",
+                "hash": hash,
+                "success": success,
+                "vm_status": vm_status,
+            })),
+        )
+    }
+
+    /// 记录每笔交易及记录时节点已收到的交易数
+    #[derive(Default)]
+    struct Journal {
+        node: Node,
+        recorded: Mutex<Vec<(PendingTransfer, usize)>>,
+        refuse: bool,
+    }
+
+    #[async_trait]
+    impl TransferJournal for Journal {
+        async fn record(&self, transfer: &PendingTransfer) -> Result<()> {
+            if self.refuse {
+                anyhow::bail!("lease lost");
+            }
+            let broadcast = self.node.submitted.lock().await.len();
+            self.recorded
+                .lock()
+                .await
+                .push((transfer.clone(), broadcast));
+            Ok(())
+        }
+
+        fn discard(&self, _transfer: &PendingTransfer) {}
+    }
+
+    fn journal(node: &Node) -> Journal {
+        Journal {
+            node: node.clone(),
+            ..Journal::default()
+        }
+    }
+
+    async fn spawn_node() -> (Node, String) {
+        let node = Node::default();
+        let app = Router::new()
+            .route("/v1", get(ledger))
+            .route("/v1/accounts/:address", get(account))
+            .route("/v1/estimate_gas_price", get(gas))
+            .route("/v1/transactions", post(submit))
+            .route("/v1/transactions/by_hash/:hash", get(by_hash))
+            .with_state(node.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (node, format!("http://{addr}/v1"))
+    }
+
+    fn config(node_url: String) -> AptosConfig {
+        AptosConfig {
+            node_url,
+            private_key: format!("0x{}", hex::encode([7u8; 32])),
+            funder_address: None,
+            chain_id: None,
+            max_gas_amount: 2_000,
+            gas_unit_price: None,
+            txn_expiry_secs: 30,
+        }
+    }
+
+    #[tokio::test]
+    async fn submits_signed_transfer_and_tracks_sequence() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        let recipient: AccountAddress = "0xabc".parse().unwrap();
+        let journal = journal(&node);
+
+        let first = client.transfer(&recipient, 150, &journal).await.unwrap();
+        let second = client.transfer(&recipient, 150, &journal).await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(node.account_hits.load(Ordering::SeqCst), 1);
+
+        // 每笔交易在广播前记录，记录的哈希与节点一致
+        let recorded = journal.recorded.lock().await;
+        assert_eq!(recorded[0].0.hash, first);
+        assert_eq!(recorded[1].0.hash, second);
+        assert_eq!((recorded[0].1, recorded[1].1), (0, 1));
+        assert_eq!(
+            (recorded[0].0.sequence_number, recorded[1].0.sequence_number),
+            (7, 8)
+        );
+
+        let submitted = node.submitted.lock().await;
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        for (index, body) in submitted.iter().enumerate() {
+            // 签名交易 = raw | 0x00 | len(32) | pubkey | len(64) | signature
+            let (raw, auth) = body.split_at(body.len() - 99);
+            assert_eq!(auth[0], 0);
+            assert_eq!(&auth[2..34], key.verifying_key().as_bytes());
+            let signature = Signature::from_slice(&auth[35..]).unwrap();
+            key.verifying_key()
+                .verify(&signing_message(raw), &signature)
+                .unwrap();
+
+            assert_eq!(&raw[..32], client.sender().as_bytes());
+            // recipient 之后依次是 amount 参数与 gas、过期时间、chain id 共 34 字节
+            assert_eq!(&raw[raw.len() - 66..raw.len() - 34], recipient.as_bytes());
+            let sequence = u64::from_le_bytes(raw[32..40].try_into().unwrap());
+            assert_eq!(sequence, 7 + index as u64);
+            assert_eq!(*raw.last().unwrap(), 4);
+        }
+    }
+
+    #[tokio::test]
+    async fn resubmits_after_sequence_mismatch() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        let recipient: AccountAddress = "0x1".parse().unwrap();
+        let journal = journal(&node);
+        client.transfer(&recipient, 1, &journal).await.unwrap();
+
+        // 另一个进程用同一出资账户提交了两笔交易，本地缓存的 8 已被占用
+        node.external_txns.store(2, Ordering::SeqCst);
+        *node.reject.lock().unwrap() =
+            Some("Invalid transaction: Type: Validation Code: SEQUENCE_NUMBER_TOO_OLD");
+        client.transfer(&recipient, 1, &journal).await.unwrap();
+        assert_eq!(node.account_hits.load(Ordering::SeqCst), 2);
+
+        let submitted = node.submitted.lock().await;
+        let sequence = u64::from_le_bytes(submitted[1][32..40].try_into().unwrap());
+        assert_eq!(sequence, 9);
+    }
+
+    #[tokio::test]
+    async fn rejected_submission_surfaces_node_message() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        let recipient: AccountAddress = "0x1".parse().unwrap();
+        let journal = journal(&node);
+
+        *node.reject.lock().unwrap() = Some("INSUFFICIENT_BALANCE_FOR_TRANSACTION_FEE");
+        let err = client.transfer(&recipient, 1, &journal).await.unwrap_err();
+        assert!(err.to_string().contains("INSUFFICIENT_BALANCE"));
+        assert!(node.submitted.lock().await.is_empty());
+
+        client.transfer(&recipient, 1, &journal).await.unwrap();
+        assert_eq!(node.account_hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_execution_is_an_error() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+
+        node.abort_on_chain.store(true, Ordering::SeqCst);
+        let err = client
+            .transfer(&"0x1".parse().unwrap(), 1, &journal(&node))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("failed on chain"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn does_not_broadcast_unrecorded_transfer() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        let journal = Journal {
+            node: node.clone(),
+            refuse: true,
+            ..Journal::default()
+        };
+
+        client
+            .transfer(&"0x1".parse().unwrap(), 1, &journal)
+            .await
+            .unwrap_err();
+        assert!(node.submitted.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_transfer_expires() {
+        let (_node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+
+        let transfer = PendingTransfer {
+            hash: format!("0x{}", "ab".repeat(32)),
+            sequence_number: 7,
+            expires_at: Utc::now() - chrono::Duration::minutes(5),
+        };
+        assert_eq!(
+            client.confirm(&transfer).await.unwrap(),
+            TransferState::Expired
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_polling_through_node_errors() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        node.lookup_failures.store(2, Ordering::SeqCst);
+
+        let recipient = "0x1".parse().unwrap();
+        client
+            .transfer(&recipient, 1, &journal(&node))
+            .await
+            .unwrap();
+        assert_eq!(node.lookup_failures.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_node_is_not_reported_as_expired() {
+        let (node, url) = spawn_node().await;
+        let client = RestAptosClient::new(&config(url)).unwrap();
+        node.lookup_failures.store(usize::MAX, Ordering::SeqCst);
+
+        // 结果未知，不能当作没有上链
+        let transfer = PendingTransfer {
+            hash: format!("0x{}", "ab".repeat(32)),
+            sequence_number: 7,
+            expires_at: Utc::now() - chrono::Duration::minutes(5),
+        };
+        let err = client.confirm(&transfer).await.unwrap_err();
+        assert!(err.to_string().contains("502"), "{err}");
+    }
+
+    #[test]
+    fn parses_private_keys() {
+        let hex_key = hex::encode([7u8; 32]);
+        assert_eq!(
+            parse_private_key(&format!("ed25519-priv-0x{hex_key}")).unwrap(),
+            [7u8; 32]
+        );
+        assert!(parse_private_key("0x1234").is_err());
+    }
+}
@@ -1,7 +1,9 @@
-use std::time::Duration;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 use serde::Deserialize;
 
+use crate::models::Role;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub server: ServerConfig,
@@ -10,6 +12,12 @@ pub struct AppConfig {
     pub queue: QueueConfig,
     pub database: DatabaseConfig,
     pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub aptos: Option<AptosConfig>,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
 }
 
 impl AppConfig {
@@ -46,10 +54,29 @@ impl AppConfig {
                         "MongoDB URL 不能为空，请设置 FAUCET__DATABASE__URL 环境变量".to_string()
                     ));
                 }
+                DatabaseConfig::Sqlite { path } if path.is_empty() => {
+                    return Err(config::ConfigError::Message(
+                        "SQLite 文件路径不能为空，请设置 FAUCET__DATABASE__PATH 环境变量".to_string()
+                    ));
+                }
                 _ => {}
             }
         }
         
+        // 冷却时间参与 chrono 的时间计算，超出其范围的值无法生效
+        for (role, policies) in [
+            ("user", &self.limits.policies.user),
+            ("privileged", &self.limits.policies.privileged),
+        ] {
+            if let Some(cooldown) = policies.cooldown {
+                if chrono::Duration::from_std(cooldown).is_err() {
+                    return Err(config::ConfigError::Message(format!(
+                        "limits.policies.{role}.cooldown 超出可表示的范围: {cooldown:?}"
+                    )));
+                }
+            }
+        }
+
         // 验证 OAuth 配置
         if self.auth.google_client_id.is_empty() {
             return Err(config::ConfigError::Message(
@@ -81,28 +108,304 @@ pub struct LimitConfig {
     pub default_daily_cap: u64,
     pub privileged_amount: u64,
     pub privileged_daily_cap: Option<u64>,
+    /// 单个收款地址每日累计上限，不区分角色与账号
+    #[serde(default)]
+    pub address_daily_cap: Option<u64>,
+    /// 数据库中的限额覆盖项在本进程内的缓存时间
+    #[serde(with = "humantime_serde", default = "default_limits_refresh")]
+    pub refresh_interval: Duration,
+    #[serde(default)]
+    pub policies: RolePolicies,
+}
+
+impl LimitConfig {
+    pub fn max_amount(&self, role: &Role) -> u64 {
+        match role {
+            Role::Admin | Role::Privileged => self.privileged_amount,
+            Role::User => self.default_amount,
+        }
+    }
+
+    pub fn daily_cap(&self, role: &Role) -> Option<u64> {
+        match role {
+            Role::Admin | Role::Privileged => self.privileged_daily_cap,
+            Role::User => Some(self.default_daily_cap),
+        }
+    }
+}
+
+fn default_limits_refresh() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// 在每日上限之外按角色叠加的限流策略，管理员沿用 `privileged` 的配置
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RolePolicies {
+    #[serde(default)]
+    pub user: RatePolicyConfig,
+    #[serde(default)]
+    pub privileged: RatePolicyConfig,
+}
+
+impl RolePolicies {
+    pub fn for_role(&self, role: &Role) -> &RatePolicyConfig {
+        match role {
+            Role::Admin | Role::Privileged => &self.privileged,
+            Role::User => &self.user,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct RatePolicyConfig {
+    /// 任意连续 24 小时内的累计上限
+    pub rolling_cap: Option<u64>,
+    /// 两次领取之间的最短间隔
+    #[serde(default, with = "humantime_serde")]
+    pub cooldown: Option<Duration>,
+    /// 每分钟最多发起的请求数
+    pub per_minute: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AuthConfig {
     pub google_client_id: String,
     pub google_client_secret: String,
+    /// Google 账号（`hd`）获得 privileged 角色的域名；OIDC 提供方使用各自的 `privileged_domains`
     pub privileged_domains: Vec<String>,
+    /// 会话（刷新令牌）的有效期，期间可以不重新登录换取新的访问令牌
     #[serde(default = "default_jwt_expiry_hours")]
     pub jwt_expiry_hours: i64,
+    /// 访问令牌（JWT）的有效期，登出与角色变化最迟在这段时间后对旧令牌生效
+    #[serde(with = "humantime_serde", default = "default_access_token_ttl")]
+    pub access_token_ttl: Duration,
+    #[serde(default)]
+    pub jwt: JwtConfig,
+    #[serde(default)]
+    pub google: GoogleConfig,
+    #[serde(default)]
+    pub github: Option<GithubConfig>,
+    /// 其他 OIDC 提供方，按 `name` 区分
+    #[serde(default)]
+    pub oidc: Vec<OidcProviderConfig>,
+}
+
+/// 会话 token 的签名密钥。`keys` 中的密钥都可用于验证，`active_kid` 指定签发新 token 的密钥，
+/// 轮换时把旧密钥留在列表中直到其签发的 token 全部过期
+#[derive(Deserialize, Clone, Default)]
+pub struct JwtConfig {
+    /// 单个 HS256 密钥的简写，等价于 kid 为 `default` 的一项
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub active_kid: Option<String>,
+    #[serde(default)]
+    pub keys: Vec<JwtKeyConfig>,
+    /// 本地开发：未配置密钥时使用每次启动随机生成的密钥，弱密钥只告警不拒绝
+    #[serde(default)]
+    pub dev_mode: bool,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: JwtAlgorithm,
+    /// HS256 的共享密钥
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// EdDSA / RS256 的 PKCS#8 PEM 私钥，只用于验证的旧密钥可以省略
+    #[serde(default)]
+    pub private_key_path: Option<PathBuf>,
+    /// EdDSA / RS256 的 PEM 公钥，可分发给其他服务验证 token
+    #[serde(default)]
+    pub public_key_path: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for JwtConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtConfig")
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("active_kid", &self.active_kid)
+            .field("keys", &self.keys)
+            .field("dev_mode", &self.dev_mode)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for JwtKeyConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JwtKeyConfig")
+            .field("kid", &self.kid)
+            .field("algorithm", &self.algorithm)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("private_key_path", &self.private_key_path)
+            .field("public_key_path", &self.public_key_path)
+            .finish()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JwtAlgorithm {
+    Hs256,
+    EdDsa,
+    Rs256,
+}
+
+/// Google ID token 的本地验证：签名密钥从 `jwks_url` 获取并按响应的 Cache-Control 缓存
+#[derive(Debug, Deserialize, Clone)]
+pub struct GoogleConfig {
+    #[serde(default = "default_google_jwks_url")]
+    pub jwks_url: String,
+    /// 允许的 `iss`
+    #[serde(default = "default_google_issuers")]
+    pub issuers: Vec<String>,
+    /// 非空时只接受 `hd` 属于这些 Google Workspace 域的账号
+    #[serde(default)]
+    pub hosted_domains: Vec<String>,
+}
+
+impl Default for GoogleConfig {
+    fn default() -> Self {
+        Self {
+            jwks_url: default_google_jwks_url(),
+            issuers: default_google_issuers(),
+            hosted_domains: Vec::new(),
+        }
+    }
+}
+
+/// GitHub OAuth 登录：用授权码换取 access token 后读取 `/user`
+#[derive(Deserialize, Clone)]
+pub struct GithubConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    #[serde(default = "default_github_oauth_url")]
+    pub oauth_url: String,
+    #[serde(default = "default_github_api_url")]
+    pub api_url: String,
+}
+
+/// 通过 `{issuer}/.well-known/openid-configuration` 发现端点的通用 OIDC 提供方
+#[derive(Deserialize, Clone)]
+pub struct OidcProviderConfig {
+    /// 登录请求中的 `provider`，同时作为 handle 的前缀
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    /// 授权码登录时换取 token 使用；只接受前端直接提交 ID token 时可以省略
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// 表示组织域名的 claim（如 `hd`），只应配置提供方保证真实的 claim
+    #[serde(default)]
+    pub domain_claim: Option<String>,
+    /// 该提供方的用户中获得 privileged 角色的域名；不使用 `auth.privileged_domains`，
+    /// 其他提供方声明的同名域名不会因此提升角色
+    #[serde(default)]
+    pub privileged_domains: Vec<String>,
+}
+
+impl std::fmt::Debug for GithubConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GithubConfig")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<redacted>")
+            .field("oauth_url", &self.oauth_url)
+            .field("api_url", &self.api_url)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for OidcProviderConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OidcProviderConfig")
+            .field("name", &self.name)
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &self.client_secret.as_ref().map(|_| "<redacted>"))
+            .field("domain_claim", &self.domain_claim)
+            .field("privileged_domains", &self.privileged_domains)
+            .finish()
+    }
+}
+
+fn default_github_oauth_url() -> String {
+    "https://github.com".to_string()
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".to_string()
+}
+
+fn default_google_jwks_url() -> String {
+    "https://www.googleapis.com/oauth2/v3/certs".to_string()
+}
+
+fn default_google_issuers() -> Vec<String> {
+    vec![
+        "https://accounts.google.com".to_string(),
+        "accounts.google.com".to_string(),
+    ]
 }
 
 fn default_jwt_expiry_hours() -> i64 {
     168 // 7天 = 7 * 24 = 168小时
 }
 
+fn default_access_token_ttl() -> Duration {
+    Duration::from_secs(15 * 60)
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct QueueConfig {
+    #[serde(default)]
+    pub mode: MintMode,
     #[serde(with = "humantime_serde")]
     pub visibility_timeout: Duration,
     #[serde(with = "humantime_serde")]
     pub retry_backoff: Duration,
     pub max_retries: u16,
+    #[serde(with = "humantime_serde", default = "default_poll_interval")]
+    pub poll_interval: Duration,
+}
+
+/// 铸币请求的处理方式
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MintMode {
+    /// 在请求内同步提交交易
+    #[default]
+    Inline,
+    /// 仅写入队列，由后台 worker 提交
+    Async,
+}
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// `--no-db` 模式下内存存储的快照
+#[derive(Debug, Deserialize, Clone)]
+pub struct MemoryConfig {
+    /// 快照文件（JSON）。启动时从中恢复，运行中定期写回，正常退出时再写一次；
+    /// 不设置时数据只保存在内存中
+    #[serde(default)]
+    pub snapshot_path: Option<PathBuf>,
+    #[serde(with = "humantime_serde", default = "default_snapshot_interval")]
+    pub flush_interval: Duration,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_path: None,
+            flush_interval: default_snapshot_interval(),
+        }
+    }
+}
+
+fn default_snapshot_interval() -> Duration {
+    Duration::from_secs(60)
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -110,6 +413,60 @@ pub struct QueueConfig {
 pub enum DatabaseConfig {
     Postgres { url: String },
     Mongodb { url: String, database: String },
+    /// 单文件数据库，文件不存在时自动创建
+    Sqlite { path: String },
+}
+
+#[derive(Deserialize, Clone)]
+pub struct AptosConfig {
+    /// 全节点 REST 地址，例如 `https://fullnode.testnet.aptoslabs.com/v1`
+    pub node_url: String,
+    /// 出资账户的 Ed25519 私钥（十六进制，可带 `0x` 前缀）
+    pub private_key: String,
+    /// 出资账户地址；密钥轮换过的账户需要显式指定，否则由公钥推导
+    #[serde(default)]
+    pub funder_address: Option<String>,
+    /// 不设置时从节点的 ledger info 读取
+    #[serde(default)]
+    pub chain_id: Option<u8>,
+    #[serde(default = "default_max_gas_amount")]
+    pub max_gas_amount: u64,
+    /// 不设置时调用节点的 gas 估算接口
+    #[serde(default)]
+    pub gas_unit_price: Option<u64>,
+    #[serde(default = "default_txn_expiry_secs")]
+    pub txn_expiry_secs: u64,
+}
+
+fn default_max_gas_amount() -> u64 {
+    2_000
+}
+
+fn default_txn_expiry_secs() -> u64 {
+    30
+}
+
+impl std::fmt::Debug for AptosConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 避免私钥随配置一起出现在日志中
+        f.debug_struct("AptosConfig")
+            .field("node_url", &self.node_url)
+            .field("private_key", &"<redacted>")
+            .field("funder_address", &self.funder_address)
+            .field("chain_id", &self.chain_id)
+            .field("max_gas_amount", &self.max_gas_amount)
+            .field("gas_unit_price", &self.gas_unit_price)
+            .field("txn_expiry_secs", &self.txn_expiry_secs)
+            .finish()
+    }
+}
+
+/// Prometheus 指标。web 始终在 `/metrics` 暴露；机器人没有 HTTP 服务，
+/// 设置 `listen_addr` 后才单独监听
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub listen_addr: Option<SocketAddr>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
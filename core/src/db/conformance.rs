@@ -0,0 +1,820 @@
+//! 各存储后端共用的仓储一致性测试。
+//!
+//! 内存后端总会运行，启用 `sqlite` feature 时在临时文件上运行。Postgres 与 MongoDB
+//! 的用例默认忽略，设置 `FAUCET_TEST_POSTGRES_URL` 或 `FAUCET_TEST_MONGODB_URL` 后用
+//! `cargo test -- --ignored` 在对应的本地实例上运行，未设置时直接失败而不是跳过。
+//!
+//! 队列用例会领取库中最早的待处理请求，因此每次运行都使用新的存储：Postgres 新建一个
+//! schema，MongoDB 新建一个数据库，结束后删除，不会改动库中已有的数据。
+
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::TryStreamExt;
+use uuid::Uuid;
+
+use crate::models::{
+    AccountAddress, AddressQuota, Channel, LimitConfigUpdate, LinkCode, MintFailure, MintOutcome,
+    LoginNonce, MintStatus, PendingTransfer, Quota, RefreshToken, Role, SystemConfig, User,
+};
+use crate::queue::new_request;
+use crate::repository::{
+    ConfigRepository, DailyReportRow, MintCursor, MintRepository, QuotaRepository,
+    ReportingRepository, SessionRepository, TransferRepository, UserRepository,
+};
+
+/// 依次运行全部一致性用例
+pub async fn run<S>(store: &S)
+where
+    S: UserRepository
+        + MintRepository
+        + QuotaRepository
+        + ReportingRepository
+        + ConfigRepository
+        + SessionRepository
+        + TransferRepository,
+{
+    user_repository(store).await;
+    mint_repository(store).await;
+    quota_repository(store).await;
+    reporting_repository(store).await;
+    config_repository(store).await;
+    session_repository(store).await;
+    transfer_repository(store).await;
+}
+
+fn unique_user(channel: Channel) -> User {
+    User::new(channel, &format!("conformance-{}", Uuid::new_v4().simple()))
+}
+
+/// 已写入存储的用户，数据库后端的请求表引用用户表
+async fn stored_user<S: UserRepository>(store: &S, channel: Channel) -> User {
+    let user = unique_user(channel);
+    store.upsert_user(&user).await.unwrap();
+    user
+}
+
+fn unique_address() -> AccountAddress {
+    format!("0x{}", Uuid::new_v4().simple()).parse().unwrap()
+}
+
+/// 远离当前时间的随机日期，避免与真实数据或其他用例落在同一天
+fn unique_day() -> NaiveDate {
+    let offset = Uuid::new_v4().as_u128() % 30_000;
+    NaiveDate::from_ymd_opt(1900, 1, 1).unwrap() + chrono::Duration::days(offset as i64)
+}
+
+fn at(day: NaiveDate, second: u32) -> DateTime<Utc> {
+    day.and_hms_opt(12, 0, second).unwrap().and_utc()
+}
+
+pub async fn user_repository<S: UserRepository>(store: &S) {
+    let web = unique_user(Channel::Web);
+    assert!(store
+        .find_user(web.channel.as_str(), &web.handle)
+        .await
+        .unwrap()
+        .is_none());
+
+    store.upsert_user(&web).await.unwrap();
+    let found = store
+        .find_user(web.channel.as_str(), &web.handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, web.id);
+    assert_eq!(found.account_id, web.account_id);
+    assert_eq!(found.role, Role::User);
+    assert_eq!(found.domain, None);
+
+    let mut updated = found.clone();
+    updated.domain = Some("example.com".to_string());
+    store.upsert_user(&updated).await.unwrap();
+    let found = store
+        .find_user(web.channel.as_str(), &web.handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, web.id);
+    assert_eq!(found.domain.as_deref(), Some("example.com"));
+
+    // 心跳等后续写入携带的旧角色不会覆盖 set_role
+    store.set_role(web.account_id, Role::Admin).await.unwrap();
+    store.upsert_user(&updated).await.unwrap();
+    let found = store
+        .find_user(web.channel.as_str(), &web.handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.role, Role::Admin);
+
+    // 关联码只能使用一次，过期的码视为不存在
+    let now = Utc::now();
+    let code = LinkCode {
+        code: Uuid::new_v4().simple().to_string(),
+        account_id: web.account_id,
+        expires_at: now + chrono::Duration::minutes(10),
+    };
+    store.create_link_code(&code).await.unwrap();
+    let consumed = store
+        .consume_link_code(&code.code, now)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(consumed.account_id, web.account_id);
+    assert!(store
+        .consume_link_code(&code.code, now)
+        .await
+        .unwrap()
+        .is_none());
+
+    let expired = LinkCode {
+        code: Uuid::new_v4().simple().to_string(),
+        account_id: web.account_id,
+        expires_at: now - chrono::Duration::minutes(1),
+    };
+    store.create_link_code(&expired).await.unwrap();
+    assert!(store
+        .consume_link_code(&expired.code, now)
+        .await
+        .unwrap()
+        .is_none());
+
+    // 关联后的身份共享账户角色
+    let bot = unique_user(Channel::Telegram);
+    store.upsert_user(&bot).await.unwrap();
+    store.link_user(bot.id, web.account_id).await.unwrap();
+    store
+        .set_role(web.account_id, Role::Privileged)
+        .await
+        .unwrap();
+    for user in [&web, &bot] {
+        let found = store
+            .find_user(user.channel.as_str(), &user.handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.account_id, web.account_id);
+        assert_eq!(found.role, Role::Privileged);
+    }
+
+    // 改名后只能以新的 handle 找到，账户不变
+    let handle = format!("conformance-{}", Uuid::new_v4().simple());
+    store.rename_user(bot.id, &handle).await.unwrap();
+    assert!(store
+        .find_user(bot.channel.as_str(), &bot.handle)
+        .await
+        .unwrap()
+        .is_none());
+    let renamed = store
+        .find_user(bot.channel.as_str(), &handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(renamed.id, bot.id);
+    assert_eq!(renamed.account_id, web.account_id);
+}
+
+pub async fn mint_repository<S: UserRepository + MintRepository>(store: &S) {
+    let user = stored_user(store, Channel::Web).await;
+    // 第二条请求由关联到同一账户的机器人身份发起
+    let mut linked = stored_user(store, Channel::Telegram).await;
+    store.link_user(linked.id, user.account_id).await.unwrap();
+    linked.account_id = user.account_id;
+    let day = unique_day();
+    let pending = store.pending_count().await.unwrap();
+    let mut requests = Vec::new();
+    for second in 0..3 {
+        let issuer = if second == 1 { &linked } else { &user };
+        let mut request = new_request(issuer, unique_address(), 10 + second as u64);
+        request.requested_at = at(day, second);
+        // 入队时无论传入什么状态都以 pending 保存
+        request.status = MintStatus::Completed;
+        request.traceparent = Some(format!("00-{:032x}-{:016x}-01", second + 1, second + 1));
+        request.http_request_id = Some(Uuid::new_v4().to_string());
+        store.enqueue(&request).await.unwrap();
+        requests.push(request);
+    }
+    assert_eq!(store.pending_count().await.unwrap(), pending + 3);
+
+    let stored = store.find_request(requests[0].id).await.unwrap().unwrap();
+    assert_eq!(stored.user_id, user.id);
+    assert_eq!(stored.account_id, user.account_id);
+    assert_eq!(stored.channel, Channel::Web);
+    assert_eq!(stored.recipient, requests[0].recipient);
+    assert_eq!(stored.amount, 10);
+    assert_eq!(stored.status, MintStatus::Pending);
+    assert_eq!(stored.attempt, 0);
+    assert_eq!(stored.traceparent, requests[0].traceparent);
+    assert_eq!(stored.http_request_id, requests[0].http_request_id);
+    assert!(store.find_request(Uuid::new_v4()).await.unwrap().is_none());
+
+    // 历史按账户汇总所有关联身份的请求，按时间倒序分页
+    let page = store
+        .list_requests_for_account(user.account_id, None, 2)
+        .await
+        .unwrap();
+    let ids: Vec<_> = page.iter().map(|request| request.id).collect();
+    assert_eq!(ids, vec![requests[2].id, requests[1].id]);
+    let cursor = MintCursor::from_request(&page[1]);
+    let page = store
+        .list_requests_for_account(user.account_id, Some(cursor), 2)
+        .await
+        .unwrap();
+    let ids: Vec<_> = page.iter().map(|request| request.id).collect();
+    assert_eq!(ids, vec![requests[0].id]);
+
+    // 领取最早的请求
+    let claimed = store
+        .next_pending(Duration::from_secs(60))
+        .await
+        .unwrap()
+        .expect("request was not claimed");
+    assert_eq!(claimed.id, requests[0].id);
+    assert_eq!(claimed.status, MintStatus::Processing);
+    assert_eq!(claimed.attempt, 1);
+    let stored = store.find_request(claimed.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Processing);
+    assert_eq!(stored.attempt, 1);
+
+    // 只有持有租约的尝试能记录交易
+    let transfer = PendingTransfer {
+        hash: "0xfeed".to_string(),
+        sequence_number: 42,
+        expires_at: at(day, 30),
+    };
+    assert!(!store
+        .record_submission(claimed.id, 0, &transfer)
+        .await
+        .unwrap());
+    assert!(store
+        .record_submission(claimed.id, 1, &transfer)
+        .await
+        .unwrap());
+    let stored = store.find_request(claimed.id).await.unwrap().unwrap();
+    assert_eq!(stored.pending_transfer(), Some(transfer.clone()));
+
+    // 退避期内不会被再次领取，已记录的交易保留
+    let retry_at = Utc::now() + chrono::Duration::hours(1);
+    assert!(!store
+        .schedule_retry(claimed.id, 0, "stale worker", retry_at)
+        .await
+        .unwrap());
+    assert!(store
+        .schedule_retry(claimed.id, 1, "node unavailable", retry_at)
+        .await
+        .unwrap());
+    let stored = store.find_request(claimed.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Pending);
+    assert_eq!(stored.error.as_deref(), Some("node unavailable"));
+    assert_eq!(stored.pending_transfer(), Some(transfer));
+    // 不在 processing 状态时同样拒绝记录
+    let replacement = PendingTransfer {
+        hash: "0xbeef".to_string(),
+        sequence_number: 43,
+        expires_at: at(day, 30),
+    };
+    assert!(!store
+        .record_submission(claimed.id, 1, &replacement)
+        .await
+        .unwrap());
+
+    let mut failed = stored.clone();
+    failed.status = MintStatus::Failed;
+    failed.processed_at = Some(Utc::now());
+    assert!(store
+        .record_outcome(&MintOutcome {
+            request: failed.clone(),
+            tx_hash: None,
+        })
+        .await
+        .unwrap());
+    // 过期的尝试不能覆盖结果
+    failed.status = MintStatus::Completed;
+    failed.attempt = 0;
+    assert!(!store
+        .record_outcome(&MintOutcome {
+            request: failed,
+            tx_hash: Some("0xstale".to_string()),
+        })
+        .await
+        .unwrap());
+    let stored = store.find_request(claimed.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Failed);
+
+    let mut completed = requests[1].clone();
+    completed.status = MintStatus::Completed;
+    completed.error = None;
+    completed.processed_at = Some(Utc::now());
+    assert!(store
+        .record_outcome(&MintOutcome {
+            request: completed.clone(),
+            tx_hash: Some("0xabc".to_string()),
+        })
+        .await
+        .unwrap());
+    let stored = store.find_request(completed.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Completed);
+    assert_eq!(stored.tx_hash.as_deref(), Some("0xabc"));
+    assert_eq!(stored.attempt, 0);
+
+    store
+        .update_status(requests[2].id, MintStatus::Processing)
+        .await
+        .unwrap();
+    let stored = store.find_request(requests[2].id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Processing);
+
+    // 滑动窗口历史按时间升序且不含失败请求
+    let recent = store
+        .recent_mints(user.account_id, at(day, 1))
+        .await
+        .unwrap();
+    let ids: Vec<_> = recent.iter().map(|request| request.id).collect();
+    assert_eq!(ids, vec![requests[1].id, requests[2].id]);
+    let recent = store
+        .recent_mints(user.account_id, at(day, 0))
+        .await
+        .unwrap();
+    assert_eq!(recent.len(), 2);
+}
+
+pub async fn quota_repository<S: UserRepository + MintRepository + QuotaRepository>(store: &S) {
+    let account_id = Uuid::new_v4();
+    let day = unique_day();
+    assert!(store.fetch_quota(account_id, day).await.unwrap().is_none());
+
+    // 超出上限的首次写入不留下记录
+    assert!(!store
+        .record_mint(account_id, day, 150, Some(100))
+        .await
+        .unwrap());
+    assert!(store.fetch_quota(account_id, day).await.unwrap().is_none());
+
+    assert!(store
+        .record_mint(account_id, day, 60, Some(100))
+        .await
+        .unwrap());
+    assert!(store
+        .record_mint(account_id, day, 40, Some(100))
+        .await
+        .unwrap());
+    assert!(!store
+        .record_mint(account_id, day, 1, Some(100))
+        .await
+        .unwrap());
+    assert!(store.record_mint(account_id, day, 1, None).await.unwrap());
+    let quota = store.fetch_quota(account_id, day).await.unwrap().unwrap();
+    assert_eq!(quota.user_id, account_id);
+    assert_eq!(quota.day, day);
+    assert_eq!(quota.minted_total, 101);
+    assert_eq!(quota.success_count, 0);
+
+    store.release_mint(account_id, day, 51).await.unwrap();
+    assert_eq!(
+        store
+            .fetch_quota(account_id, day)
+            .await
+            .unwrap()
+            .unwrap()
+            .minted_total,
+        50
+    );
+    store.release_mint(account_id, day, 500).await.unwrap();
+    assert_eq!(
+        store
+            .fetch_quota(account_id, day)
+            .await
+            .unwrap()
+            .unwrap()
+            .minted_total,
+        0
+    );
+
+    // 成功的请求计入当日成功次数，即使当天还没有额度记录
+    let user = stored_user(store, Channel::Web).await;
+    let mut request = new_request(&user, unique_address(), 1);
+    request.requested_at = at(day, 0);
+    store.enqueue(&request).await.unwrap();
+    request.status = MintStatus::Completed;
+    store
+        .record_outcome(&MintOutcome {
+            request,
+            tx_hash: Some("0x1".to_string()),
+        })
+        .await
+        .unwrap();
+    let quota = store
+        .fetch_quota(user.account_id, day)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(quota.minted_total, 0);
+    assert_eq!(quota.success_count, 1);
+
+    // 每个账户只保留最近一次重置
+    assert_eq!(store.limits_reset_at(account_id).await.unwrap(), None);
+    let (first, second) = (at(day, 0), at(day, 1));
+    store.set_limits_reset(account_id, first).await.unwrap();
+    store.set_limits_reset(account_id, second).await.unwrap();
+    assert_eq!(store.limits_reset_at(account_id).await.unwrap(), Some(second));
+
+    let address = unique_address();
+    assert!(!store
+        .record_address_mint(&address, day, 150, Some(100))
+        .await
+        .unwrap());
+    assert!(store
+        .record_address_mint(&address, day, 100, Some(100))
+        .await
+        .unwrap());
+    assert!(!store
+        .record_address_mint(&address, day, 1, Some(100))
+        .await
+        .unwrap());
+    store
+        .release_address_mint(&address, day, 500)
+        .await
+        .unwrap();
+    assert!(store
+        .record_address_mint(&address, day, 100, Some(100))
+        .await
+        .unwrap());
+    assert!(store
+        .record_address_mint(&address, day, 1, None)
+        .await
+        .unwrap());
+}
+
+pub async fn reporting_repository<S>(store: &S)
+where
+    S: UserRepository + MintRepository + ReportingRepository,
+{
+    let day = unique_day();
+    let before = store.daily_summary(day).await.unwrap();
+
+    let user = stored_user(store, Channel::Telegram).await;
+    let mut leftovers = Vec::new();
+    let outcomes = [
+        (Channel::Web, MintStatus::Completed, 10),
+        (Channel::Web, MintStatus::Failed, 20),
+        (Channel::Telegram, MintStatus::Completed, 30),
+        (Channel::Telegram, MintStatus::Pending, 40),
+    ];
+    for (second, (channel, status, amount)) in outcomes.into_iter().enumerate() {
+        let mut request = new_request(&user, unique_address(), amount);
+        request.channel = channel;
+        request.requested_at = at(day, second as u32);
+        store.enqueue(&request).await.unwrap();
+        if status == MintStatus::Pending {
+            leftovers.push(request.id);
+            continue;
+        }
+        request.status = status.clone();
+        request.processed_at = Some(Utc::now());
+        store
+            .record_outcome(&MintOutcome {
+                request: request.clone(),
+                tx_hash: None,
+            })
+            .await
+            .unwrap();
+        if status == MintStatus::Failed {
+            store
+                .log_failure(request.id, Utc::now(), "conformance")
+                .await
+                .unwrap();
+        }
+    }
+    // 前一天与后一天的请求不计入
+    for requested_at in [
+        at(day, 0) - chrono::Duration::days(1),
+        at(day, 0) + chrono::Duration::days(1),
+    ] {
+        let mut request = new_request(&user, unique_address(), 1000);
+        request.requested_at = requested_at;
+        store.enqueue(&request).await.unwrap();
+        leftovers.push(request.id);
+    }
+
+    let after = store.daily_summary(day).await.unwrap();
+    let channels: Vec<_> = after.iter().map(|row| row.channel.clone()).collect();
+    let mut sorted = channels.clone();
+    sorted.sort();
+    assert_eq!(channels, sorted);
+
+    let delta = |channel: &str| {
+        let find = |rows: &[DailyReportRow]| {
+            rows.iter()
+                .find(|row| row.channel == channel)
+                .cloned()
+                .unwrap_or(DailyReportRow {
+                    channel: channel.to_string(),
+                    total_amount: 0,
+                    success_count: 0,
+                    failure_count: 0,
+                })
+        };
+        let (before, after) = (find(&before), find(&after));
+        (
+            after.total_amount - before.total_amount,
+            after.success_count - before.success_count,
+            after.failure_count - before.failure_count,
+        )
+    };
+    assert_eq!(delta("web"), (30, 1, 1));
+    assert_eq!(delta("telegram"), (70, 1, 0));
+    assert_eq!(delta("discord"), (0, 0, 0));
+
+    // 不给后续运行的领取用例留下待处理请求
+    for id in leftovers {
+        store
+            .update_status(id, MintStatus::Completed)
+            .await
+            .unwrap();
+    }
+}
+
+pub async fn config_repository<S: ConfigRepository>(store: &S) {
+    let prefix = format!("conformance.{}", Uuid::new_v4().simple());
+    let key = format!("{prefix}.b");
+
+    assert!(store.get_config(&key).await.unwrap().is_none());
+
+    store.set_config(&key, "1", Some("first")).await.unwrap();
+    let first = store.get_config(&key).await.unwrap().unwrap();
+    assert_eq!(first.key, key);
+    assert_eq!(first.value, "1");
+    assert_eq!(first.description.as_deref(), Some("first"));
+
+    // 覆盖写入只更新值与描述，保留 id 与创建时间
+    store.set_config(&key, "2", None).await.unwrap();
+    let second = store.get_config(&key).await.unwrap().unwrap();
+    assert_eq!(second.id, first.id);
+    assert_eq!(second.value, "2");
+    assert_eq!(second.description, None);
+    assert_eq!(
+        second.created_at.timestamp_millis(),
+        first.created_at.timestamp_millis()
+    );
+    assert!(second.updated_at >= second.created_at);
+
+    store
+        .set_config(&format!("{prefix}.a"), "0", None)
+        .await
+        .unwrap();
+    let keys: Vec<_> = store
+        .get_all_configs()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|config| config.key)
+        .filter(|k| k.starts_with(&prefix))
+        .collect();
+    assert_eq!(keys, vec![format!("{prefix}.a"), key]);
+
+    store
+        .update_limit_config(&LimitConfigUpdate {
+            default_amount: Some(42),
+            address_daily_cap: Some(500),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let limits = store.get_limit_config().await.unwrap().unwrap();
+    assert_eq!(limits.default_amount, Some(42));
+    assert_eq!(limits.address_daily_cap, Some(500));
+}
+
+pub async fn session_repository<S: SessionRepository>(store: &S) {
+    let now = Utc::now();
+    let session_id = Uuid::new_v4();
+    let token = |expires_at| RefreshToken {
+        token_hash: Uuid::new_v4().simple().to_string(),
+        session_id,
+        channel: Channel::Web,
+        handle: format!("conformance-{}", Uuid::new_v4().simple()),
+        expires_at,
+    };
+
+    // 刷新令牌只能使用一次
+    let current = token(now + chrono::Duration::hours(1));
+    store.create_refresh_token(&current).await.unwrap();
+    let consumed = store
+        .consume_refresh_token(&current.token_hash, now)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(consumed.session_id, session_id);
+    assert_eq!(consumed.handle, current.handle);
+    assert_eq!(consumed.channel, Channel::Web);
+    assert!(store
+        .consume_refresh_token(&current.token_hash, now)
+        .await
+        .unwrap()
+        .is_none());
+
+    let expired = token(now - chrono::Duration::minutes(1));
+    store.create_refresh_token(&expired).await.unwrap();
+    assert!(store
+        .consume_refresh_token(&expired.token_hash, now)
+        .await
+        .unwrap()
+        .is_none());
+
+    // 结束会话后其刷新令牌全部失效
+    let next = token(now + chrono::Duration::hours(1));
+    store.create_refresh_token(&next).await.unwrap();
+    store.revoke_session(session_id).await.unwrap();
+    assert!(store
+        .consume_refresh_token(&next.token_hash, now)
+        .await
+        .unwrap()
+        .is_none());
+
+    let jti = Uuid::new_v4().to_string();
+    assert!(!store.is_token_revoked(&jti, now).await.unwrap());
+    store
+        .revoke_token(&jti, now + chrono::Duration::minutes(15))
+        .await
+        .unwrap();
+    assert!(store.is_token_revoked(&jti, now).await.unwrap());
+    // 访问令牌过期后不再需要记录
+    assert!(!store
+        .is_token_revoked(&jti, now + chrono::Duration::minutes(16))
+        .await
+        .unwrap());
+
+    // 登录挑战同样只能使用一次；过期后记录被清理（过期的挑战在 web 验签前就会被拒绝）
+    let nonce = LoginNonce {
+        nonce: Uuid::new_v4().simple().to_string(),
+        expires_at: now + chrono::Duration::minutes(5),
+    };
+    assert!(store.use_login_nonce(&nonce, now).await.unwrap());
+    assert!(!store.use_login_nonce(&nonce, now).await.unwrap());
+    assert!(store
+        .use_login_nonce(&nonce, now + chrono::Duration::minutes(6))
+        .await
+        .unwrap());
+}
+
+pub async fn transfer_repository<S>(store: &S)
+where
+    S: UserRepository + MintRepository + QuotaRepository + ConfigRepository + TransferRepository,
+{
+    // 导入的身份沿用原有 id 与账户角色
+    let mut user = unique_user(Channel::Discord);
+    user.role = Role::Admin;
+    assert!(store.import_user(&user).await.unwrap());
+    assert!(!store.import_user(&user).await.unwrap());
+    let found = store
+        .find_user(user.channel.as_str(), &user.handle)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(found.id, user.id);
+    assert_eq!(found.role, Role::Admin);
+    let users: Vec<User> = store.export_users().try_collect().await.unwrap();
+    let exported = users.iter().find(|u| u.id == user.id).unwrap();
+    assert_eq!(exported.account_id, user.account_id);
+    assert_eq!(exported.role, Role::Admin);
+
+    // 请求按原状态保存
+    let day = unique_day();
+    let mut request = new_request(&user, unique_address(), 7);
+    request.requested_at = at(day, 0);
+    request.status = MintStatus::Failed;
+    request.attempt = 3;
+    request.error = Some("node unavailable".to_string());
+    request.processed_at = Some(at(day, 1));
+    assert!(store.import_request(&request).await.unwrap());
+    assert!(!store.import_request(&request).await.unwrap());
+    let stored = store.find_request(request.id).await.unwrap().unwrap();
+    assert_eq!(stored.status, MintStatus::Failed);
+    assert_eq!(stored.attempt, 3);
+    assert_eq!(stored.processed_at, request.processed_at);
+    let requests: Vec<_> = store.export_requests().try_collect().await.unwrap();
+    assert!(requests.iter().any(|r| r.id == request.id));
+
+    let quota = Quota {
+        id: Uuid::new_v4(),
+        user_id: user.account_id,
+        day,
+        minted_total: 7,
+        success_count: 2,
+    };
+    assert!(store.import_quota(&quota).await.unwrap());
+    assert!(!store.import_quota(&quota).await.unwrap());
+    let stored = store.fetch_quota(user.account_id, day).await.unwrap().unwrap();
+    assert_eq!(stored.id, quota.id);
+    assert_eq!(stored.minted_total, 7);
+    assert_eq!(stored.success_count, 2);
+    let quotas: Vec<_> = store.export_quotas().try_collect().await.unwrap();
+    assert!(quotas.iter().any(|q| q.id == quota.id));
+
+    let address_quota = AddressQuota {
+        address: unique_address(),
+        day,
+        minted_total: 7,
+    };
+    assert!(store.import_address_quota(&address_quota).await.unwrap());
+    assert!(!store.import_address_quota(&address_quota).await.unwrap());
+    let address_quotas: Vec<_> = store.export_address_quotas().try_collect().await.unwrap();
+    assert!(address_quotas
+        .iter()
+        .any(|q| q.address == address_quota.address && q.minted_total == 7));
+
+    let failure = MintFailure {
+        id: Uuid::new_v4(),
+        request_id: request.id,
+        failed_at: at(day, 1),
+        reason: "node unavailable".to_string(),
+    };
+    assert!(store.import_failure(&failure).await.unwrap());
+    assert!(!store.import_failure(&failure).await.unwrap());
+    let failures: Vec<_> = store.export_failures().try_collect().await.unwrap();
+    let exported = failures.iter().find(|f| f.id == failure.id).unwrap();
+    assert_eq!(exported.request_id, request.id);
+    assert_eq!(exported.failed_at, failure.failed_at);
+
+    let config = SystemConfig {
+        id: Uuid::new_v4(),
+        key: format!("conformance.{}", Uuid::new_v4().simple()),
+        value: "1".to_string(),
+        description: None,
+        created_at: at(day, 0),
+        updated_at: at(day, 1),
+    };
+    assert!(store.import_config(&config).await.unwrap());
+    assert!(!store.import_config(&config).await.unwrap());
+    let stored = store.get_config(&config.key).await.unwrap().unwrap();
+    assert_eq!(stored.id, config.id);
+    assert_eq!(stored.created_at, config.created_at);
+    let configs: Vec<_> = store.export_configs().try_collect().await.unwrap();
+    assert!(configs.iter().any(|c| c.key == config.key));
+}
+
+#[tokio::test]
+async fn memory_store_conforms() {
+    let store = crate::db::memory::MemoryStore::new();
+    run(&store).await;
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+#[ignore = "requires FAUCET_TEST_POSTGRES_URL"]
+async fn postgres_store_conforms() {
+    let url = std::env::var("FAUCET_TEST_POSTGRES_URL")
+        .expect("FAUCET_TEST_POSTGRES_URL must point at a postgres database");
+    let schema = format!("conformance_{}", Uuid::new_v4().simple());
+    let admin = sqlx::PgPool::connect(&url).await.unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {schema}"))
+        .execute(&admin)
+        .await
+        .unwrap();
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let store = crate::db::postgres::PostgresStore::connect(&format!(
+        "{url}{separator}options[search_path]={schema}"
+    ))
+    .await
+    .unwrap();
+    run(&store).await;
+    sqlx::query(&format!("DROP SCHEMA {schema} CASCADE"))
+        .execute(&admin)
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "mongodb")]
+#[tokio::test]
+#[ignore = "requires FAUCET_TEST_MONGODB_URL"]
+async fn mongo_store_conforms() {
+    let url = std::env::var("FAUCET_TEST_MONGODB_URL")
+        .expect("FAUCET_TEST_MONGODB_URL must point at a mongodb deployment");
+    let name = format!("faucet_conformance_{}", Uuid::new_v4().simple());
+    let store = crate::db::mongodb::MongoStore::connect(&url, &name)
+        .await
+        .unwrap();
+    run(&store).await;
+    ::mongodb::Client::with_uri_str(&url)
+        .await
+        .unwrap()
+        .database(&name)
+        .drop(None)
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn sqlite_store_conforms() {
+    let path = std::env::temp_dir().join(format!("faucet-conformance-{}.db", Uuid::new_v4()));
+    let store = crate::db::sqlite::SqliteStore::connect(path.to_str().unwrap())
+        .await
+        .unwrap();
+    run(&store).await;
+    // 重新打开时不会重复执行迁移
+    assert!(store.pending_migrations().await.unwrap().is_empty());
+    drop(store);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,42 @@
+//! 为任意 [`Store`] 记录每次仓储调用的耗时与 span。
+//!
+//! 各仓储方法的转发实现由 [`super::forward_repository`] 生成。
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tracing::Instrument;
+
+use super::Store;
+use crate::metrics;
+
+/// 按方法名记录 `faucet_db_call_duration_seconds` 并创建 `db.<方法名>` span；导出用的流不计时
+pub struct InstrumentedStore {
+    inner: Arc<dyn Store>,
+}
+
+impl InstrumentedStore {
+    pub fn new(inner: Arc<dyn Store>) -> Self {
+        Self { inner }
+    }
+
+    pub(crate) fn inner(&self) -> &dyn Store {
+        self.inner.as_ref()
+    }
+}
+
+pub(crate) async fn call<T>(
+    op: &'static str,
+    call: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    let span = tracing::info_span!("db", otel.name = format!("db.{op}"), db.operation = op);
+    metrics::time_db(op, call).instrument(span).await
+}
+
+#[async_trait]
+impl Store for InstrumentedStore {
+    async fn flush(&self) -> Result<()> {
+        call("flush", self.inner.flush()).await
+    }
+}
@@ -1,25 +1,71 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::{DateTime, NaiveDate, Utc};
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
+use tracing::info;
 use uuid::Uuid;
 
-use crate::models::{MintOutcome, MintRequest, MintStatus, Quota, Role, User, SystemConfig, LimitConfigUpdate};
+use crate::models::{
+    AccountAddress, AddressQuota, LinkCode, LoginNonce, MintFailure, MintOutcome, MintRequest,
+    MintStatus, PendingTransfer, Quota, RefreshToken, Role, User, SystemConfig, LimitConfigUpdate,
+};
 use crate::repository::{
-    DailyReportRow, MintRepository, QuotaRepository, ReportingRepository, UserRepository, ConfigRepository,
+    DailyReportRow, MintCursor, MintRepository, QuotaRepository, ReportingRepository, UserRepository,
+    ConfigRepository, SessionRepository, TransferRepository,
 };
 
+type FailureLog = Vec<MintFailure>;
+
 #[derive(Clone, Default)]
 pub struct MemoryStore {
     users: Arc<DashMap<(String, String), User>>, // (channel, handle)
+    accounts: Arc<DashMap<Uuid, Role>>,
+    link_codes: Arc<DashMap<String, LinkCode>>,
+    refresh_tokens: Arc<DashMap<String, RefreshToken>>,
+    /// jti -> 访问令牌的过期时间
+    revoked_tokens: Arc<DashMap<String, DateTime<Utc>>>,
+    login_nonces: Arc<DashMap<String, LoginNonce>>,
     mints: Arc<DashMap<Uuid, MintRequest>>,
-    queue: Arc<Mutex<VecDeque<Uuid>>>,
+    claim_lock: Arc<Mutex<()>>,
     quotas: Arc<DashMap<(Uuid, NaiveDate), Quota>>,
+    address_quotas: Arc<DashMap<(AccountAddress, NaiveDate), u64>>,
+    limit_resets: Arc<DashMap<Uuid, DateTime<Utc>>>,
     configs: Arc<DashMap<String, SystemConfig>>,
-    failures: Arc<Mutex<Vec<(Uuid, DateTime<Utc>, String)>>>,
+    failures: Arc<Mutex<FailureLog>>,
+    /// 设置后由 [`MemoryStore::flush`] 写入快照
+    snapshot_path: Option<Arc<PathBuf>>,
+    /// 定时写入与退出时的写入共用同一个临时文件，必须依次进行
+    flush_lock: Arc<Mutex<()>>,
+}
+
+/// 内存存储的完整内容，以 JSON 保存到快照文件
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MemorySnapshot {
+    pub users: Vec<User>,
+    pub accounts: Vec<(Uuid, Role)>,
+    pub link_codes: Vec<LinkCode>,
+    #[serde(default)]
+    pub refresh_tokens: Vec<RefreshToken>,
+    #[serde(default)]
+    pub revoked_tokens: Vec<(String, DateTime<Utc>)>,
+    #[serde(default)]
+    pub login_nonces: Vec<LoginNonce>,
+    pub mints: Vec<MintRequest>,
+    pub quotas: Vec<Quota>,
+    pub address_quotas: Vec<(AccountAddress, NaiveDate, u64)>,
+    #[serde(default)]
+    pub limit_resets: Vec<(Uuid, DateTime<Utc>)>,
+    pub configs: Vec<SystemConfig>,
+    pub failures: FailureLog,
 }
 
 impl MemoryStore {
@@ -27,34 +73,275 @@ impl MemoryStore {
         Self::default()
     }
 
+    /// 从快照文件恢复，文件不存在时返回空存储；之后的 `flush` 写回同一文件。
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let mut store = match std::fs::read(&path) {
+            Ok(bytes) => {
+                let snapshot: MemorySnapshot = serde_json::from_slice(&bytes)
+                    .with_context(|| format!("无法解析内存快照 {}", path.display()))?;
+                info!(path = %path.display(), users = snapshot.users.len(), mints = snapshot.mints.len(), "已从快照恢复内存存储");
+                Self::restore(snapshot)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::new(),
+            Err(err) => {
+                return Err(err).with_context(|| format!("无法读取内存快照 {}", path.display()))
+            }
+        };
+        store.snapshot_path = Some(Arc::new(path));
+        Ok(store)
+    }
+
+    pub fn restore(snapshot: MemorySnapshot) -> Self {
+        Self {
+            users: Arc::new(
+                snapshot
+                    .users
+                    .into_iter()
+                    .map(|user| (Self::key(user.channel.as_str(), &user.handle), user))
+                    .collect(),
+            ),
+            accounts: Arc::new(snapshot.accounts.into_iter().collect()),
+            link_codes: Arc::new(
+                snapshot
+                    .link_codes
+                    .into_iter()
+                    .map(|link| (link.code.clone(), link))
+                    .collect(),
+            ),
+            refresh_tokens: Arc::new(
+                snapshot
+                    .refresh_tokens
+                    .into_iter()
+                    .map(|token| (token.token_hash.clone(), token))
+                    .collect(),
+            ),
+            revoked_tokens: Arc::new(snapshot.revoked_tokens.into_iter().collect()),
+            login_nonces: Arc::new(
+                snapshot
+                    .login_nonces
+                    .into_iter()
+                    .map(|nonce| (nonce.nonce.clone(), nonce))
+                    .collect(),
+            ),
+            mints: Arc::new(snapshot.mints.into_iter().map(|mint| (mint.id, mint)).collect()),
+            claim_lock: Arc::default(),
+            quotas: Arc::new(
+                snapshot
+                    .quotas
+                    .into_iter()
+                    .map(|quota| ((quota.user_id, quota.day), quota))
+                    .collect(),
+            ),
+            address_quotas: Arc::new(
+                snapshot
+                    .address_quotas
+                    .into_iter()
+                    .map(|(address, day, minted)| ((address, day), minted))
+                    .collect(),
+            ),
+            limit_resets: Arc::new(snapshot.limit_resets.into_iter().collect()),
+            configs: Arc::new(
+                snapshot
+                    .configs
+                    .into_iter()
+                    .map(|config| (config.key.clone(), config))
+                    .collect(),
+            ),
+            failures: Arc::new(Mutex::new(snapshot.failures)),
+            snapshot_path: None,
+            flush_lock: Arc::default(),
+        }
+    }
+
+    pub async fn snapshot(&self) -> MemorySnapshot {
+        MemorySnapshot {
+            users: self.users.iter().map(|entry| entry.value().clone()).collect(),
+            accounts: self
+                .accounts
+                .iter()
+                .map(|entry| (*entry.key(), entry.value().clone()))
+                .collect(),
+            link_codes: self.link_codes.iter().map(|entry| entry.value().clone()).collect(),
+            refresh_tokens: self
+                .refresh_tokens
+                .iter()
+                .map(|entry| entry.value().clone())
+                .collect(),
+            revoked_tokens: self
+                .revoked_tokens
+                .iter()
+                .map(|entry| (entry.key().clone(), *entry.value()))
+                .collect(),
+            login_nonces: self
+                .login_nonces
+                .iter()
+                .map(|entry| entry.value().clone())
+                .collect(),
+            mints: self.mints.iter().map(|entry| entry.value().clone()).collect(),
+            quotas: self.quotas.iter().map(|entry| entry.value().clone()).collect(),
+            address_quotas: self
+                .address_quotas
+                .iter()
+                .map(|entry| (entry.key().0, entry.key().1, *entry.value()))
+                .collect(),
+            limit_resets: self
+                .limit_resets
+                .iter()
+                .map(|entry| (*entry.key(), *entry.value()))
+                .collect(),
+            configs: self.configs.iter().map(|entry| entry.value().clone()).collect(),
+            failures: self.failures.lock().await.clone(),
+        }
+    }
+
+    /// 把当前内容写入快照文件；未配置快照时什么都不做。
+    pub async fn flush(&self) -> Result<()> {
+        let Some(path) = &self.snapshot_path else {
+            return Ok(());
+        };
+        // 在锁内取快照，后写入的文件总是更新的内容
+        let _flush = self.flush_lock.lock().await;
+        let bytes = serde_json::to_vec(&self.snapshot().await)?;
+        write_atomically(path, &bytes).await
+    }
+
     fn key(channel: &str, handle: &str) -> (String, String) {
         (channel.to_ascii_lowercase(), handle.to_ascii_lowercase())
     }
+
+}
+
+/// 先写临时文件再重命名，进程中途退出时不会留下半个快照
+async fn write_atomically(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    tokio::fs::write(&tmp, bytes)
+        .await
+        .with_context(|| format!("无法写入内存快照 {}", tmp.display()))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .with_context(|| format!("无法替换内存快照 {}", path.display()))?;
+    Ok(())
 }
 
 #[async_trait]
 impl UserRepository for MemoryStore {
     async fn upsert_user(&self, user: &User) -> Result<()> {
         let key = Self::key(user.channel.as_str(), &user.handle);
+        self.accounts
+            .entry(user.account_id)
+            .or_insert_with(|| user.role.clone());
         self.users.insert(key, user.clone());
         Ok(())
     }
 
     async fn find_user(&self, channel: &str, handle: &str) -> Result<Option<User>> {
         let key = Self::key(channel, handle);
-        Ok(self.users.get(&key).map(|entry| entry.clone()))
+        let Some(mut user) = self.users.get(&key).map(|entry| entry.clone()) else {
+            return Ok(None);
+        };
+        if let Some(role) = self.accounts.get(&user.account_id) {
+            user.role = role.clone();
+        }
+        Ok(Some(user))
+    }
+
+    async fn set_role(&self, account_id: Uuid, role: Role) -> Result<()> {
+        self.accounts.insert(account_id, role);
+        Ok(())
     }
 
-    async fn set_role(&self, user_id: Uuid, role: Role) -> Result<()> {
+    async fn link_user(&self, user_id: Uuid, account_id: Uuid) -> Result<()> {
         for mut entry in self.users.iter_mut() {
             let user = entry.value_mut();
             if user.id == user_id {
-                user.role = role.clone();
+                user.account_id = account_id;
                 break;
             }
         }
         Ok(())
     }
+
+    async fn rename_user(&self, user_id: Uuid, handle: &str) -> Result<()> {
+        let key = self
+            .users
+            .iter()
+            .find(|entry| entry.value().id == user_id)
+            .map(|entry| entry.key().clone());
+        if let Some((_, mut user)) = key.and_then(|key| self.users.remove(&key)) {
+            user.handle = handle.to_string();
+            self.users
+                .insert(Self::key(user.channel.as_str(), handle), user);
+        }
+        Ok(())
+    }
+
+    async fn create_link_code(&self, code: &LinkCode) -> Result<()> {
+        self.link_codes.insert(code.code.clone(), code.clone());
+        Ok(())
+    }
+
+    async fn consume_link_code(&self, code: &str, now: DateTime<Utc>) -> Result<Option<LinkCode>> {
+        Ok(self
+            .link_codes
+            .remove(code)
+            .map(|(_, link)| link)
+            .filter(|link| link.expires_at > now))
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MemoryStore {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<()> {
+        let now = Utc::now();
+        self.refresh_tokens.retain(|_, token| token.expires_at > now);
+        self.refresh_tokens.insert(token.token_hash.clone(), token.clone());
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>> {
+        Ok(self
+            .refresh_tokens
+            .remove(token_hash)
+            .map(|(_, token)| token)
+            .filter(|token| token.expires_at > now))
+    }
+
+    async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        self.refresh_tokens.retain(|_, token| token.session_id != session_id);
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        let now = Utc::now();
+        self.revoked_tokens.retain(|_, expires_at| *expires_at > now);
+        self.revoked_tokens.insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str, now: DateTime<Utc>) -> Result<bool> {
+        Ok(self
+            .revoked_tokens
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at > now))
+    }
+
+    async fn use_login_nonce(&self, nonce: &LoginNonce, now: DateTime<Utc>) -> Result<bool> {
+        self.login_nonces.retain(|_, nonce| nonce.expires_at > now);
+        Ok(match self.login_nonces.entry(nonce.nonce.clone()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(nonce.clone());
+                true
+            }
+        })
+    }
 }
 
 #[async_trait]
@@ -62,25 +349,39 @@ impl MintRepository for MemoryStore {
     async fn enqueue(&self, request: &MintRequest) -> Result<()> {
         let mut cloned = request.clone();
         cloned.status = MintStatus::Pending;
-        self.mints.insert(cloned.id, cloned.clone());
-        let mut queue = self.queue.lock().await;
-        queue.push_back(cloned.id);
+        self.mints.insert(cloned.id, cloned);
         Ok(())
     }
 
-    async fn next_pending(&self) -> Result<Option<MintRequest>> {
-        let mut queue = self.queue.lock().await;
-        while let Some(id) = queue.pop_front() {
-            if let Some(mut entry) = self.mints.get_mut(&id) {
-                if matches!(entry.status, MintStatus::Pending | MintStatus::Processing) {
-                    entry.status = MintStatus::Processing;
-                    entry.processed_at = Some(Utc::now());
-                    entry.attempt = entry.attempt.saturating_add(1);
-                    return Ok(Some(entry.clone()));
+    async fn next_pending(&self, lease: Duration) -> Result<Option<MintRequest>> {
+        // 串行化领取，避免两个 worker 拿到同一条请求
+        let _claim = self.claim_lock.lock().await;
+        let now = Utc::now();
+        let next = self
+            .mints
+            .iter()
+            .filter(|entry| {
+                let mint = entry.value();
+                match mint.status {
+                    MintStatus::Pending => !matches!(mint.available_at, Some(at) if at > now),
+                    MintStatus::Processing => mint.available_at.is_some_and(|at| at <= now),
+                    _ => false,
                 }
-            }
-        }
-        Ok(None)
+            })
+            .min_by_key(|entry| entry.value().requested_at)
+            .map(|entry| *entry.key());
+
+        let Some(id) = next else {
+            return Ok(None);
+        };
+        let Some(mut entry) = self.mints.get_mut(&id) else {
+            return Ok(None);
+        };
+        entry.status = MintStatus::Processing;
+        entry.processed_at = Some(now);
+        entry.attempt = entry.attempt.saturating_add(1);
+        entry.available_at = Some(now + chrono::Duration::from_std(lease)?);
+        Ok(Some(entry.clone()))
     }
 
     async fn update_status(&self, request_id: Uuid, status: MintStatus) -> Result<()> {
@@ -93,17 +394,40 @@ impl MintRepository for MemoryStore {
         Ok(())
     }
 
-    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<()> {
-        if let Some(mut entry) = self.mints.get_mut(&outcome.request.id) {
-            *entry = outcome.request.clone();
+    async fn record_submission(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        transfer: &PendingTransfer,
+    ) -> Result<bool> {
+        let Some(mut entry) = self.mints.get_mut(&request_id) else {
+            return Ok(false);
+        };
+        if entry.status != MintStatus::Processing || entry.attempt != attempt {
+            return Ok(false);
+        }
+        entry.tx_hash = Some(transfer.hash.clone());
+        entry.sequence_number = Some(transfer.sequence_number);
+        entry.tx_expires_at = Some(transfer.expires_at);
+        Ok(true)
+    }
+
+    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<bool> {
+        {
+            let Some(mut entry) = self.mints.get_mut(&outcome.request.id) else {
+                return Ok(false);
+            };
+            if entry.attempt != outcome.request.attempt {
+                return Ok(false);
+            }
+            entry.status = outcome.request.status.clone();
             entry.tx_hash = outcome.tx_hash.clone();
+            entry.error = outcome.request.error.clone();
+            entry.processed_at = outcome.request.processed_at;
         }
 
         if outcome.request.status == MintStatus::Completed {
-            let key = (
-                outcome.request.user_id,
-                outcome.request.requested_at.date_naive(),
-            );
+            let key = (outcome.request.account_id, outcome.request.charged_day());
             self.quotas
                 .entry(key)
                 .and_modify(|quota| {
@@ -111,37 +435,161 @@ impl MintRepository for MemoryStore {
                 })
                 .or_insert_with(|| Quota {
                     id: Uuid::new_v4(),
-                    user_id: outcome.request.user_id,
-                    day: outcome.request.requested_at.date_naive(),
+                    user_id: outcome.request.account_id,
+                    day: outcome.request.charged_day(),
                     minted_total: 0,
                     success_count: 1,
                 });
         }
 
-        Ok(())
+        Ok(true)
+    }
+
+    async fn schedule_retry(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        error: &str,
+        available_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let Some(mut entry) = self.mints.get_mut(&request_id) else {
+            return Ok(false);
+        };
+        if entry.attempt != attempt {
+            return Ok(false);
+        }
+        entry.status = MintStatus::Pending;
+        entry.error = Some(error.to_string());
+        entry.available_at = Some(available_at);
+        Ok(true)
+    }
+
+    async fn find_request(&self, request_id: Uuid) -> Result<Option<MintRequest>> {
+        Ok(self.mints.get(&request_id).map(|entry| entry.clone()))
+    }
+
+    async fn list_requests_for_account(
+        &self,
+        account_id: Uuid,
+        before: Option<MintCursor>,
+        limit: usize,
+    ) -> Result<Vec<MintRequest>> {
+        let mut requests: Vec<MintRequest> = self
+            .mints
+            .iter()
+            .filter(|entry| entry.account_id == account_id)
+            .filter(|entry| match before {
+                Some(cursor) => cursor.is_after(entry.value()),
+                None => true,
+            })
+            .map(|entry| entry.clone())
+            .collect();
+        requests.sort_by_key(|request| std::cmp::Reverse((request.requested_at, request.id)));
+        requests.truncate(limit);
+        Ok(requests)
+    }
+
+    async fn recent_mints(
+        &self,
+        account_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<MintRequest>> {
+        let mut requests: Vec<MintRequest> = self
+            .mints
+            .iter()
+            .filter(|entry| entry.account_id == account_id && entry.requested_at >= since)
+            .filter(|entry| entry.status != MintStatus::Failed)
+            .map(|entry| entry.clone())
+            .collect();
+        requests.sort_by_key(|request| request.requested_at);
+        Ok(requests)
+    }
+
+    async fn pending_count(&self) -> Result<u64> {
+        Ok(self
+            .mints
+            .iter()
+            .filter(|entry| entry.status == MintStatus::Pending)
+            .count() as u64)
     }
 }
 
 #[async_trait]
 impl QuotaRepository for MemoryStore {
-    async fn record_mint(&self, user_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
-        self.quotas
-            .entry((user_id, day))
-            .and_modify(|quota| {
-                quota.minted_total += amount;
-            })
-            .or_insert_with(|| Quota {
-                id: Uuid::new_v4(),
-                user_id,
-                day,
-                minted_total: amount,
-                success_count: 0,
-            });
+    async fn record_mint(
+        &self,
+        account_id: Uuid,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        // 单笔即超出上限时不创建当日记录，与数据库后端保持一致
+        if matches!(cap, Some(cap) if amount > cap) {
+            return Ok(false);
+        }
+        // entry 持有分片写锁，检查与累加在同一临界区内完成
+        let mut quota = self.quotas.entry((account_id, day)).or_insert_with(|| Quota {
+            id: Uuid::new_v4(),
+            user_id: account_id,
+            day,
+            minted_total: 0,
+            success_count: 0,
+        });
+        if matches!(cap, Some(cap) if quota.minted_total + amount > cap) {
+            return Ok(false);
+        }
+        quota.minted_total += amount;
+        Ok(true)
+    }
+
+    async fn release_mint(&self, account_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
+        if let Some(mut quota) = self.quotas.get_mut(&(account_id, day)) {
+            quota.minted_total = quota.minted_total.saturating_sub(amount);
+        }
+        Ok(())
+    }
+
+    async fn fetch_quota(&self, account_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
+        Ok(self.quotas.get(&(account_id, day)).map(|quota| quota.clone()))
+    }
+
+    async fn record_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        if matches!(cap, Some(cap) if amount > cap) {
+            return Ok(false);
+        }
+        let mut minted = self.address_quotas.entry((*address, day)).or_insert(0);
+        if matches!(cap, Some(cap) if *minted + amount > cap) {
+            return Ok(false);
+        }
+        *minted += amount;
+        Ok(true)
+    }
+
+    async fn release_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        if let Some(mut minted) = self.address_quotas.get_mut(&(*address, day)) {
+            *minted = minted.saturating_sub(amount);
+        }
+        Ok(())
+    }
+
+    async fn set_limits_reset(&self, account_id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        self.limit_resets.insert(account_id, at);
         Ok(())
     }
 
-    async fn fetch_quota(&self, user_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
-        Ok(self.quotas.get(&(user_id, day)).map(|quota| quota.clone()))
+    async fn limits_reset_at(&self, account_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        Ok(self.limit_resets.get(&account_id).map(|entry| *entry))
     }
 }
 
@@ -166,7 +614,7 @@ impl ReportingRepository for MemoryStore {
             }
         }
 
-        Ok(totals
+        let mut rows: Vec<DailyReportRow> = totals
             .into_iter()
             .map(|kv| DailyReportRow {
                 channel: kv.0,
@@ -174,12 +622,19 @@ impl ReportingRepository for MemoryStore {
                 success_count: kv.1 .1,
                 failure_count: kv.1 .2,
             })
-            .collect())
+            .collect();
+        rows.sort_by_key(|row| row.channel.clone());
+        Ok(rows)
     }
 
     async fn log_failure(&self, request_id: Uuid, when: DateTime<Utc>, reason: &str) -> Result<()> {
         let mut failures = self.failures.lock().await;
-        failures.push((request_id, when, reason.to_string()));
+        failures.push(MintFailure {
+            id: Uuid::new_v4(),
+            request_id,
+            failed_at: when,
+            reason: reason.to_string(),
+        });
         Ok(())
     }
 }
@@ -192,20 +647,28 @@ impl ConfigRepository for MemoryStore {
 
     async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> Result<()> {
         let now = Utc::now();
-        let config = SystemConfig {
-            id: Uuid::new_v4(),
-            key: key.to_string(),
-            value: value.to_string(),
-            description: description.map(|s| s.to_string()),
-            created_at: now,
-            updated_at: now,
-        };
-        self.configs.insert(key.to_string(), config);
+        self.configs
+            .entry(key.to_string())
+            .and_modify(|config| {
+                config.value = value.to_string();
+                config.description = description.map(|s| s.to_string());
+                config.updated_at = now;
+            })
+            .or_insert_with(|| SystemConfig {
+                id: Uuid::new_v4(),
+                key: key.to_string(),
+                value: value.to_string(),
+                description: description.map(|s| s.to_string()),
+                created_at: now,
+                updated_at: now,
+            });
         Ok(())
     }
 
     async fn get_all_configs(&self) -> Result<Vec<SystemConfig>> {
-        Ok(self.configs.iter().map(|entry| entry.value().clone()).collect())
+        let mut configs: Vec<_> = self.configs.iter().map(|entry| entry.value().clone()).collect();
+        configs.sort_by_key(|config| config.key.clone());
+        Ok(configs)
     }
 
     async fn update_limit_config(&self, config: &LimitConfigUpdate) -> Result<()> {
@@ -221,24 +684,199 @@ impl ConfigRepository for MemoryStore {
         if let Some(cap) = config.privileged_daily_cap {
             self.set_config("limits.privileged_daily_cap", &cap.to_string(), Some("Privileged user daily cap")).await?;
         }
+        if let Some(cap) = config.address_daily_cap {
+            self.set_config("limits.address_daily_cap", &cap.to_string(), Some("Per-address daily cap")).await?;
+        }
         Ok(())
     }
 
     async fn get_limit_config(&self) -> Result<Option<LimitConfigUpdate>> {
-        let default_amount = self.get_config("limits.default_amount").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let privileged_amount = self.get_config("limits.privileged_amount").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
+        let default_amount = self.get_config("limits.default_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_amount = self.get_config("limits.privileged_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let address_daily_cap = self.get_config("limits.address_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
 
-        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() {
+        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() || address_daily_cap.is_some() {
             Ok(Some(LimitConfigUpdate {
                 default_amount,
                 default_daily_cap,
                 privileged_amount,
                 privileged_daily_cap,
+                address_daily_cap,
             }))
         } else {
             Ok(None)
         }
     }
 }
+
+/// 导出当前内容的副本，避免在流被消费期间持有分片锁
+fn snapshot_stream<'a, T: Send + 'a>(items: Vec<T>) -> BoxStream<'a, Result<T>> {
+    stream::iter(items.into_iter().map(Ok)).boxed()
+}
+
+#[async_trait]
+impl TransferRepository for MemoryStore {
+    fn export_users(&self) -> BoxStream<'_, Result<User>> {
+        let users = self
+            .users
+            .iter()
+            .map(|entry| {
+                let mut user = entry.value().clone();
+                if let Some(role) = self.accounts.get(&user.account_id) {
+                    user.role = role.clone();
+                }
+                user
+            })
+            .collect();
+        snapshot_stream(users)
+    }
+
+    fn export_requests(&self) -> BoxStream<'_, Result<MintRequest>> {
+        snapshot_stream(self.mints.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    fn export_quotas(&self) -> BoxStream<'_, Result<Quota>> {
+        snapshot_stream(self.quotas.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    fn export_address_quotas(&self) -> BoxStream<'_, Result<AddressQuota>> {
+        let quotas = self
+            .address_quotas
+            .iter()
+            .map(|entry| AddressQuota {
+                address: entry.key().0,
+                day: entry.key().1,
+                minted_total: *entry.value(),
+            })
+            .collect();
+        snapshot_stream(quotas)
+    }
+
+    fn export_failures(&self) -> BoxStream<'_, Result<MintFailure>> {
+        stream::once(async move { self.failures.lock().await.clone() })
+            .flat_map(snapshot_stream)
+            .boxed()
+    }
+
+    fn export_configs(&self) -> BoxStream<'_, Result<SystemConfig>> {
+        snapshot_stream(self.configs.iter().map(|entry| entry.value().clone()).collect())
+    }
+
+    async fn import_user(&self, user: &User) -> Result<bool> {
+        if self.users.iter().any(|entry| entry.id == user.id) {
+            return Ok(false);
+        }
+        match self.users.entry(Self::key(user.channel.as_str(), &user.handle)) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                self.accounts
+                    .entry(user.account_id)
+                    .or_insert_with(|| user.role.clone());
+                entry.insert(user.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    async fn import_request(&self, request: &MintRequest) -> Result<bool> {
+        match self.mints.entry(request.id) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(request.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    async fn import_quota(&self, quota: &Quota) -> Result<bool> {
+        match self.quotas.entry((quota.user_id, quota.day)) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(quota.clone());
+                Ok(true)
+            }
+        }
+    }
+
+    async fn import_address_quota(&self, quota: &AddressQuota) -> Result<bool> {
+        match self.address_quotas.entry((quota.address, quota.day)) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(quota.minted_total);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn import_failure(&self, failure: &MintFailure) -> Result<bool> {
+        let mut failures = self.failures.lock().await;
+        if failures.iter().any(|existing| existing.id == failure.id) {
+            return Ok(false);
+        }
+        failures.push(failure.clone());
+        Ok(true)
+    }
+
+    async fn import_config(&self, config: &SystemConfig) -> Result<bool> {
+        match self.configs.entry(config.key.clone()) {
+            Entry::Occupied(_) => Ok(false),
+            Entry::Vacant(entry) => {
+                entry.insert(config.clone());
+                Ok(true)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Channel;
+    use crate::queue::new_request;
+
+    #[tokio::test]
+    async fn snapshot_survives_restart() {
+        let path = std::env::temp_dir().join(format!("faucet-snapshot-{}.json", Uuid::new_v4()));
+        let store = MemoryStore::open(&path).unwrap();
+        let user = User::new(Channel::Telegram, "Alice");
+        store.upsert_user(&user).await.unwrap();
+        store.set_role(user.account_id, Role::Privileged).await.unwrap();
+        let day = Utc::now().date_naive();
+        store.record_mint(user.account_id, day, 40, None).await.unwrap();
+        let request = new_request(&user, "0x1".parse().unwrap(), 40);
+        store.enqueue(&request).await.unwrap();
+        store.set_config("limits.default_amount", "5", None).await.unwrap();
+        store.flush().await.unwrap();
+
+        let restored = MemoryStore::open(&path).unwrap();
+        let found = restored.find_user("telegram", "alice").await.unwrap().unwrap();
+        assert_eq!(found.id, user.id);
+        assert_eq!(found.role, Role::Privileged);
+        let quota = restored.fetch_quota(user.account_id, day).await.unwrap().unwrap();
+        assert_eq!(quota.minted_total, 40);
+        let pending = restored.next_pending(Duration::from_secs(30)).await.unwrap().unwrap();
+        assert_eq!(pending.id, request.id);
+        let config = restored.get_config("limits.default_amount").await.unwrap().unwrap();
+        assert_eq!(config.value, "5");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn concurrent_flushes_do_not_clobber_each_other() {
+        let path = std::env::temp_dir().join(format!("faucet-snapshot-{}.json", Uuid::new_v4()));
+        let store = MemoryStore::open(&path).unwrap();
+        store.upsert_user(&User::new(Channel::Web, "alice")).await.unwrap();
+
+        let flushes = (0..8).map(|_| store.flush());
+        for result in futures::future::join_all(flushes).await {
+            result.unwrap();
+        }
+
+        let restored = MemoryStore::open(&path).unwrap();
+        assert!(restored.find_user("web", "alice").await.unwrap().is_some());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,335 +1,250 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 use async_trait::async_trait;
+use tokio::task::JoinHandle;
+use tracing::error;
 
 use crate::{
-    config::DatabaseConfig,
-    models::{MintOutcome, MintRequest, MintStatus, Quota, Role, User},
+    config::{DatabaseConfig, MemoryConfig},
     repository::{
-        DailyReportRow, MintRepository, QuotaRepository, ReportingRepository, UserRepository, ConfigRepository,
+        ConfigRepository, MintRepository, QuotaRepository, ReportingRepository,
+        SessionRepository, TransferRepository, UserRepository,
     },
+    Service,
 };
 
-#[derive(Clone)]
-pub enum DatabaseStore {
-    #[cfg(feature = "postgres")]
-    Postgres(crate::db::postgres::PostgresStore),
-    #[cfg(feature = "mongodb")]
-    Mongo(crate::db::mongodb::MongoStore),
-    Memory(crate::db::memory::MemoryStore),
+/// 服务所需的全部仓储接口。
+///
+/// 内置后端与第三方存储都以 `Arc<dyn Store>` 交给 [`crate::FaucetService`]；
+/// 实现各仓储 trait 后再加一行 `impl Store for MyStore {}` 即可接入。
+#[async_trait]
+pub trait Store:
+    UserRepository
+    + MintRepository
+    + QuotaRepository
+    + ReportingRepository
+    + ConfigRepository
+    + SessionRepository
+    + TransferRepository
+    + Send
+    + Sync
+    + 'static
+{
+    /// 把只保存在进程内的数据写入持久化介质，数据库后端无需处理
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
-impl DatabaseStore {
-    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
-        match config {
-            #[cfg(feature = "postgres")]
-            DatabaseConfig::Postgres { url } => {
-                let store = crate::db::postgres::PostgresStore::connect(url).await?;
-                Ok(Self::Postgres(store))
-            }
-            #[cfg(feature = "mongodb")]
-            DatabaseConfig::Mongodb { url, database } => {
-                let store = crate::db::mongodb::MongoStore::connect(url, database).await?;
-                Ok(Self::Mongo(store))
-            }
-            #[cfg(not(feature = "postgres"))]
-            DatabaseConfig::Postgres { .. } => {
-                anyhow::bail!("Postgres feature is disabled");
-            }
-            #[cfg(not(feature = "mongodb"))]
-            DatabaseConfig::Mongodb { .. } => {
-                anyhow::bail!("MongoDB feature is disabled");
-            }
-        }
-    }
+#[cfg(feature = "postgres")]
+impl Store for postgres::PostgresStore {}
 
-    pub fn memory() -> Self {
-        Self::Memory(crate::db::memory::MemoryStore::new())
-    }
-}
+#[cfg(feature = "mongodb")]
+impl Store for mongodb::MongoStore {}
+
+#[cfg(feature = "sqlite")]
+impl Store for sqlite::SqliteStore {}
 
 #[async_trait]
-impl UserRepository for DatabaseStore {
-    async fn upsert_user(&self, user: &User) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.upsert_user(user).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.upsert_user(user).await,
-            DatabaseStore::Memory(store) => store.upsert_user(user).await,
-        }
+impl Store for memory::MemoryStore {
+    async fn flush(&self) -> Result<()> {
+        memory::MemoryStore::flush(self).await
     }
+}
 
-    async fn find_user(&self, channel: &str, handle: &str) -> anyhow::Result<Option<User>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.find_user(channel, handle).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.find_user(channel, handle).await,
-            DatabaseStore::Memory(store) => store.find_user(channel, handle).await,
-        }
-    }
+/// 按配置连接数据库并执行未应用的迁移，返回的存储会记录每次调用的耗时
+pub async fn connect(config: &DatabaseConfig) -> Result<Arc<dyn Store>> {
+    let store: Arc<dyn Store> = match config {
+        #[cfg(feature = "postgres")]
+        DatabaseConfig::Postgres { url } => Arc::new(postgres::PostgresStore::connect(url).await?),
+        #[cfg(feature = "mongodb")]
+        DatabaseConfig::Mongodb { url, database } => {
+            Arc::new(mongodb::MongoStore::connect(url, database).await?)
+        }
+        #[cfg(feature = "sqlite")]
+        DatabaseConfig::Sqlite { path } => Arc::new(sqlite::SqliteStore::connect(path).await?),
+        #[cfg(not(feature = "postgres"))]
+        DatabaseConfig::Postgres { .. } => {
+            anyhow::bail!("Postgres feature is disabled");
+        }
+        #[cfg(not(feature = "mongodb"))]
+        DatabaseConfig::Mongodb { .. } => {
+            anyhow::bail!("MongoDB feature is disabled");
+        }
+        #[cfg(not(feature = "sqlite"))]
+        DatabaseConfig::Sqlite { .. } => {
+            anyhow::bail!("SQLite feature is disabled");
+        }
+    };
+    Ok(Arc::new(instrumented::InstrumentedStore::new(store)))
+}
 
-    async fn set_role(&self, user_id: uuid::Uuid, role: Role) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.set_role(user_id, role).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.set_role(user_id, role).await,
-            DatabaseStore::Memory(store) => store.set_role(user_id, role).await,
-        }
-    }
+/// 内存存储；配置了快照文件时从中恢复
+pub fn open_memory(config: &MemoryConfig) -> Result<Arc<dyn Store>> {
+    let store: Arc<dyn Store> = match &config.snapshot_path {
+        Some(path) => Arc::new(memory::MemoryStore::open(path)?),
+        None => Arc::new(memory::MemoryStore::new()),
+    };
+    Ok(Arc::new(instrumented::InstrumentedStore::new(store)))
 }
 
-#[async_trait]
-impl MintRepository for DatabaseStore {
-    async fn enqueue(&self, request: &MintRequest) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.enqueue(request).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.enqueue(request).await,
-            DatabaseStore::Memory(store) => store.enqueue(request).await,
+/// 执行配置的数据库中尚未应用的迁移；`dry_run` 时只返回待执行的步骤。
+pub async fn migrate(config: &DatabaseConfig, dry_run: bool) -> Result<Vec<MigrationStep>> {
+    match config {
+        #[cfg(feature = "postgres")]
+        DatabaseConfig::Postgres { url } => {
+            let store = postgres::PostgresStore::open(url).await?;
+            if dry_run {
+                store.pending_migrations().await
+            } else {
+                store.migrate().await
+            }
         }
-    }
-
-    async fn next_pending(&self) -> anyhow::Result<Option<MintRequest>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.next_pending().await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.next_pending().await,
-            DatabaseStore::Memory(store) => store.next_pending().await,
+        #[cfg(feature = "mongodb")]
+        DatabaseConfig::Mongodb { url, database } => {
+            let store = mongodb::MongoStore::open(url, database).await?;
+            if dry_run {
+                store.pending_migrations().await
+            } else {
+                store.migrate().await
+            }
         }
-    }
-
-    async fn update_status(
-        &self,
-        request_id: uuid::Uuid,
-        status: MintStatus,
-    ) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.update_status(request_id, status).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.update_status(request_id, status).await,
-            DatabaseStore::Memory(store) => store.update_status(request_id, status).await,
+        #[cfg(feature = "sqlite")]
+        DatabaseConfig::Sqlite { path } => {
+            let store = sqlite::SqliteStore::open(path).await?;
+            if dry_run {
+                store.pending_migrations().await
+            } else {
+                store.migrate().await
+            }
         }
-    }
-
-    async fn record_outcome(&self, outcome: &MintOutcome) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.record_outcome(outcome).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.record_outcome(outcome).await,
-            DatabaseStore::Memory(store) => store.record_outcome(outcome).await,
+        #[cfg(not(feature = "postgres"))]
+        DatabaseConfig::Postgres { .. } => {
+            anyhow::bail!("Postgres feature is disabled");
         }
-    }
-}
-
-#[async_trait]
-impl QuotaRepository for DatabaseStore {
-    async fn record_mint(
-        &self,
-        user_id: uuid::Uuid,
-        day: chrono::NaiveDate,
-        amount: u64,
-    ) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.record_mint(user_id, day, amount).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.record_mint(user_id, day, amount).await,
-            DatabaseStore::Memory(store) => store.record_mint(user_id, day, amount).await,
+        #[cfg(not(feature = "mongodb"))]
+        DatabaseConfig::Mongodb { .. } => {
+            anyhow::bail!("MongoDB feature is disabled");
         }
-    }
-
-    async fn fetch_quota(
-        &self,
-        user_id: uuid::Uuid,
-        day: chrono::NaiveDate,
-    ) -> anyhow::Result<Option<Quota>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.fetch_quota(user_id, day).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.fetch_quota(user_id, day).await,
-            DatabaseStore::Memory(store) => store.fetch_quota(user_id, day).await,
+        #[cfg(not(feature = "sqlite"))]
+        DatabaseConfig::Sqlite { .. } => {
+            anyhow::bail!("SQLite feature is disabled");
         }
     }
 }
 
-#[async_trait]
-impl ReportingRepository for DatabaseStore {
-    async fn daily_summary(&self, day: chrono::NaiveDate) -> anyhow::Result<Vec<DailyReportRow>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.daily_summary(day).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.daily_summary(day).await,
-            DatabaseStore::Memory(store) => store.daily_summary(day).await,
-        }
-    }
-
-    async fn log_failure(
-        &self,
-        request_id: uuid::Uuid,
-        when: chrono::DateTime<chrono::Utc>,
-        reason: &str,
-    ) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.log_failure(request_id, when, reason).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.log_failure(request_id, when, reason).await,
-            DatabaseStore::Memory(store) => store.log_failure(request_id, when, reason).await,
-        }
-    }
+/// 一个已编号的 schema 变更，`statements` 为可读的变更内容（SQL 或索引定义）。
+#[derive(Debug, Clone)]
+pub struct MigrationStep {
+    pub version: i64,
+    pub name: &'static str,
+    pub statements: Vec<String>,
 }
 
-#[async_trait]
-impl<T> UserRepository for Arc<T>
-where
-    T: UserRepository + ?Sized,
-{
-    async fn upsert_user(&self, user: &User) -> anyhow::Result<()> {
-        (**self).upsert_user(user).await
-    }
-
-    async fn find_user(&self, channel: &str, handle: &str) -> anyhow::Result<Option<User>> {
-        (**self).find_user(channel, handle).await
-    }
-
-    async fn set_role(&self, user_id: uuid::Uuid, role: Role) -> anyhow::Result<()> {
-        (**self).set_role(user_id, role).await
-    }
+/// 按固定间隔调用 [`Store::flush`] 的后台任务
+pub struct PeriodicFlush {
+    store: Arc<dyn Store>,
+    interval: Duration,
 }
 
-#[async_trait]
-impl<T> MintRepository for Arc<T>
-where
-    T: MintRepository + ?Sized,
-{
-    async fn enqueue(&self, request: &MintRequest) -> anyhow::Result<()> {
-        (**self).enqueue(request).await
-    }
-
-    async fn next_pending(&self) -> anyhow::Result<Option<MintRequest>> {
-        (**self).next_pending().await
-    }
-
-    async fn update_status(
-        &self,
-        request_id: uuid::Uuid,
-        status: MintStatus,
-    ) -> anyhow::Result<()> {
-        (**self).update_status(request_id, status).await
-    }
-
-    async fn record_outcome(&self, outcome: &MintOutcome) -> anyhow::Result<()> {
-        (**self).record_outcome(outcome).await
+impl PeriodicFlush {
+    pub fn new(store: Arc<dyn Store>, interval: Duration) -> Self {
+        Self { store, interval }
     }
 }
 
-#[async_trait]
-impl<T> QuotaRepository for Arc<T>
-where
-    T: QuotaRepository + ?Sized,
-{
-    async fn record_mint(
-        &self,
-        user_id: uuid::Uuid,
-        day: chrono::NaiveDate,
-        amount: u64,
-    ) -> anyhow::Result<()> {
-        (**self).record_mint(user_id, day, amount).await
-    }
-
-    async fn fetch_quota(
-        &self,
-        user_id: uuid::Uuid,
-        day: chrono::NaiveDate,
-    ) -> anyhow::Result<Option<Quota>> {
-        (**self).fetch_quota(user_id, day).await
+impl Service for PeriodicFlush {
+    fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            // 第一次 tick 立即返回，刚恢复的内容无需重写
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.store.flush().await {
+                    error!(error = %err, "store_flush_failed");
+                }
+            }
+        })
     }
 }
 
-#[async_trait]
-impl<T> ReportingRepository for Arc<T>
-where
-    T: ReportingRepository + ?Sized,
-{
-    async fn daily_summary(&self, day: chrono::NaiveDate) -> anyhow::Result<Vec<DailyReportRow>> {
-        (**self).daily_summary(day).await
-    }
-
-    async fn log_failure(
-        &self,
-        request_id: uuid::Uuid,
-        when: chrono::DateTime<chrono::Utc>,
-        reason: &str,
-    ) -> anyhow::Result<()> {
-        (**self).log_failure(request_id, when, reason).await
-    }
+/// 为仓储 trait 逐个方法生成 `Arc<T>` 与 [`instrumented::InstrumentedStore`] 的转发实现。
+///
+/// 由 [`crate::repository`] 中定义 trait 的 `repository!` 调用，异步方法经过
+/// [`instrumented::call`] 计时，返回流的导出方法直接转发。
+macro_rules! forward_repository {
+    ($trait:ident { $($body:tt)* }) => {
+        $crate::db::forward_repository!(@methods $trait [] [] $($body)*);
+    };
+    (@methods $trait:ident [$($arc:tt)*] [$($instrumented:tt)*]
+        $(#[$meta:meta])*
+        async fn $method:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        $crate::db::forward_repository!(@methods $trait
+            [$($arc)*
+                async fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    (**self).$method($($arg),*).await
+                }
+            ]
+            [$($instrumented)*
+                async fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    $crate::db::instrumented::call(
+                        stringify!($method),
+                        self.inner().$method($($arg),*),
+                    )
+                    .await
+                }
+            ]
+            $($rest)*
+        );
+    };
+    (@methods $trait:ident [$($arc:tt)*] [$($instrumented:tt)*]
+        $(#[$meta:meta])*
+        fn $method:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        $crate::db::forward_repository!(@methods $trait
+            [$($arc)*
+                fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    (**self).$method($($arg),*)
+                }
+            ]
+            [$($instrumented)*
+                fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    self.inner().$method($($arg),*)
+                }
+            ]
+            $($rest)*
+        );
+    };
+    (@methods $trait:ident [$($arc:tt)*] [$($instrumented:tt)*]) => {
+        #[async_trait::async_trait]
+        impl<T> $trait for std::sync::Arc<T>
+        where
+            T: $trait + ?Sized,
+        {
+            $($arc)*
+        }
+
+        #[async_trait::async_trait]
+        impl $trait for $crate::db::instrumented::InstrumentedStore {
+            $($instrumented)*
+        }
+    };
 }
 
-#[async_trait]
-impl ConfigRepository for DatabaseStore {
-    async fn get_config(&self, key: &str) -> anyhow::Result<Option<crate::models::SystemConfig>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.get_config(key).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.get_config(key).await,
-            DatabaseStore::Memory(store) => store.get_config(key).await,
-        }
-    }
-
-    async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.set_config(key, value, description).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.set_config(key, value, description).await,
-            DatabaseStore::Memory(store) => store.set_config(key, value, description).await,
-        }
-    }
-
-    async fn get_all_configs(&self) -> anyhow::Result<Vec<crate::models::SystemConfig>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.get_all_configs().await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.get_all_configs().await,
-            DatabaseStore::Memory(store) => store.get_all_configs().await,
-        }
-    }
-
-    async fn update_limit_config(&self, config: &crate::models::LimitConfigUpdate) -> anyhow::Result<()> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.update_limit_config(config).await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.update_limit_config(config).await,
-            DatabaseStore::Memory(store) => store.update_limit_config(config).await,
-        }
-    }
-
-    async fn get_limit_config(&self) -> anyhow::Result<Option<crate::models::LimitConfigUpdate>> {
-        match self {
-            #[cfg(feature = "postgres")]
-            DatabaseStore::Postgres(store) => store.get_limit_config().await,
-            #[cfg(feature = "mongodb")]
-            DatabaseStore::Mongo(store) => store.get_limit_config().await,
-            DatabaseStore::Memory(store) => store.get_limit_config().await,
-        }
-    }
-}
+pub(crate) use forward_repository;
 
+#[cfg(test)]
+mod conformance;
+pub mod instrumented;
 pub mod memory;
 #[cfg(feature = "mongodb")]
 pub mod mongodb;
 #[cfg(feature = "postgres")]
 pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -1,44 +1,184 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use chrono::{DateTime, Duration, NaiveDate, Utc};
-use futures::TryStreamExt;
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
 use mongodb::{
     bson::{doc, Bson, Document},
-    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument, UpdateOptions},
+    error::{ErrorKind, WriteFailure},
+    options::{
+        FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument, UpdateOptions,
+    },
     Client, Collection, IndexModel,
 };
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
+    db::MigrationStep,
     models::{
-        channel_from_db, role_from_db, status_from_db, Channel, MintOutcome, MintRequest,
-        MintStatus, Quota, Role, User,
+        address_from_db, channel_from_db, role_from_db, status_from_db, AccountAddress,
+        AddressQuota, LimitConfigUpdate, LinkCode, MintFailure, MintOutcome, MintRequest,
+        LoginNonce, MintStatus, PendingTransfer, Quota, RefreshToken, Role, SystemConfig, User,
     },
     repository::{
-        DailyReportRow, MintRepository, QuotaRepository, ReportingRepository, UserRepository,
+        ConfigRepository, DailyReportRow, MintCursor, MintRepository, QuotaRepository,
+        ReportingRepository, SessionRepository, TransferRepository, UserRepository,
     },
 };
 
+struct IndexSpec {
+    collection: &'static str,
+    keys: Document,
+    unique: bool,
+}
+
+struct IndexMigration {
+    version: i64,
+    name: &'static str,
+    indexes: Vec<IndexSpec>,
+}
+
+impl IndexMigration {
+    fn step(&self) -> MigrationStep {
+        MigrationStep {
+            version: self.version,
+            name: self.name,
+            statements: self
+                .indexes
+                .iter()
+                .map(|index| {
+                    let unique = if index.unique { " unique" } else { "" };
+                    format!("{}.createIndex({}){unique}", index.collection, index.keys)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn index(collection: &'static str, keys: Document, unique: bool) -> IndexSpec {
+    IndexSpec {
+        collection,
+        keys,
+        unique,
+    }
+}
+
+/// 按版本号排列的索引迁移；已发布的版本只能追加。
+///
+/// 版本号独立于 Postgres：早期版本与同名的 Postgres 迁移对应，只加列的版本在这里没有条目
+/// （如版本 4），之后的版本只在需要新索引时追加，不再与 Postgres 对齐。
+fn index_migrations() -> Vec<IndexMigration> {
+    vec![
+        IndexMigration {
+            version: 1,
+            name: "initial_schema",
+            indexes: vec![
+                index("users", doc! {"channel": 1, "handle": 1}, true),
+                index(
+                    "mint_requests",
+                    doc! {"status": 1, "available_at": 1, "requested_at": 1},
+                    false,
+                ),
+                index(
+                    "mint_requests",
+                    doc! {"user_id": 1, "requested_at": -1, "id": -1},
+                    false,
+                ),
+                index("quotas", doc! {"user_id": 1, "day": 1}, true),
+                index("system_configs", doc! {"key": 1}, true),
+            ],
+        },
+        IndexMigration {
+            version: 2,
+            name: "address_quotas",
+            indexes: vec![index("address_quotas", doc! {"address": 1, "day": 1}, true)],
+        },
+        IndexMigration {
+            version: 3,
+            name: "accounts",
+            indexes: vec![
+                index("accounts", doc! {"id": 1}, true),
+                index("link_codes", doc! {"code": 1}, true),
+                index("mint_requests", doc! {"account_id": 1, "requested_at": 1}, false),
+            ],
+        },
+        // 版本 4 只给 Postgres 加列，文档无需迁移
+        IndexMigration {
+            version: 5,
+            name: "sessions",
+            indexes: vec![
+                index("refresh_tokens", doc! {"token_hash": 1}, true),
+                index("refresh_tokens", doc! {"session_id": 1}, false),
+                index("revoked_tokens", doc! {"jti": 1}, true),
+            ],
+        },
+        IndexMigration {
+            version: 6,
+            name: "login_nonces",
+            indexes: vec![index("login_nonces", doc! {"nonce": 1}, true)],
+        },
+        IndexMigration {
+            version: 7,
+            name: "rate_limit_resets",
+            indexes: vec![index("rate_limit_resets", doc! {"account_id": 1}, true)],
+        },
+    ]
+}
+
 #[derive(Clone)]
 pub struct MongoStore {
-    client: Client,
     database: mongodb::Database,
 }
 
 impl MongoStore {
+    /// 连接并执行未应用的索引迁移
     pub async fn connect(url: &str, name: &str) -> Result<Self> {
-        let client = Client::with_uri_str(url).await?;
-        let database = client.database(name);
-        let store = Self { client, database };
-        store.ensure_indexes().await?;
+        let store = Self::open(url, name).await?;
+        store.migrate().await?;
         info!("mongodb schema ready");
         Ok(store)
     }
 
+    /// 只建立连接，不检查索引
+    pub async fn open(url: &str, name: &str) -> Result<Self> {
+        let client = Client::with_uri_str(url).await?;
+        Ok(Self {
+            database: client.database(name),
+        })
+    }
+
     fn users(&self) -> Collection<Document> {
         self.database.collection("users")
     }
 
+    fn accounts(&self) -> Collection<Document> {
+        self.database.collection("accounts")
+    }
+
+    fn link_codes(&self) -> Collection<Document> {
+        self.database.collection("link_codes")
+    }
+
+    fn refresh_tokens(&self) -> Collection<Document> {
+        self.database.collection("refresh_tokens")
+    }
+
+    fn revoked_tokens(&self) -> Collection<Document> {
+        self.database.collection("revoked_tokens")
+    }
+
+    fn rate_limit_resets(&self) -> Collection<Document> {
+        self.database.collection("rate_limit_resets")
+    }
+
+    fn login_nonces(&self) -> Collection<Document> {
+        self.database.collection("login_nonces")
+    }
+
     fn requests(&self) -> Collection<Document> {
         self.database.collection("mint_requests")
     }
@@ -47,48 +187,94 @@ impl MongoStore {
         self.database.collection("quotas")
     }
 
+    fn address_quotas(&self) -> Collection<Document> {
+        self.database.collection("address_quotas")
+    }
+
     fn failures(&self) -> Collection<Document> {
         self.database.collection("mint_failures")
     }
 
-    async fn ensure_indexes(&self) -> Result<()> {
-        let unique = IndexOptions::builder().unique(true).build();
-        self.users()
-            .create_index(
-                IndexModel::builder()
-                    .keys(doc! {"channel": 1, "handle": 1})
-                    .options(unique.clone())
-                    .build(),
-                None,
-            )
-            .await?;
+    fn configs(&self) -> Collection<Document> {
+        self.database.collection("system_configs")
+    }
 
-        self.requests()
-            .create_index(
-                IndexModel::builder()
-                    .keys(doc! {"status": 1, "requested_at": 1})
-                    .options(IndexOptions::builder().build())
-                    .build(),
-                None,
-            )
-            .await?;
+    fn migrations_log(&self) -> Collection<Document> {
+        self.database.collection("schema_migrations")
+    }
 
-        self.quotas()
+    /// 待执行的索引迁移，不修改数据库
+    pub async fn pending_migrations(&self) -> Result<Vec<MigrationStep>> {
+        let applied = self.applied_versions().await?;
+        Ok(index_migrations()
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(IndexMigration::step)
+            .collect())
+    }
+
+    /// 按版本号依次创建未应用的索引并记录版本。
+    ///
+    /// 创建索引本身是幂等的，多个副本同时执行只会重复创建同一索引。
+    pub async fn migrate(&self) -> Result<Vec<MigrationStep>> {
+        self.migrations_log()
             .create_index(
                 IndexModel::builder()
-                    .keys(doc! {"user_id": 1, "day": 1})
-                    .options(unique)
+                    .keys(doc! {"version": 1})
+                    .options(IndexOptions::builder().unique(true).build())
                     .build(),
                 None,
             )
             .await?;
 
-        Ok(())
+        let applied = self.applied_versions().await?;
+        let mut steps = Vec::new();
+        for migration in index_migrations() {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+            for index in &migration.indexes {
+                let options = IndexOptions::builder().unique(index.unique).build();
+                self.database
+                    .collection::<Document>(index.collection)
+                    .create_index(
+                        IndexModel::builder()
+                            .keys(index.keys.clone())
+                            .options(options)
+                            .build(),
+                        None,
+                    )
+                    .await?;
+            }
+            self.migrations_log()
+                .update_one(
+                    doc! {"version": migration.version},
+                    doc! {"$setOnInsert": {
+                        "name": migration.name,
+                        "applied_at": mongodb::bson::DateTime::from_chrono(Utc::now()),
+                    }},
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await?;
+            info!(version = migration.version, name = migration.name, "mongodb_migration_applied");
+            steps.push(migration.step());
+        }
+        Ok(steps)
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<i64>> {
+        let mut cursor = self.migrations_log().find(doc! {}, None).await?;
+        let mut versions = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            versions.push(doc.get_i64("version")?);
+        }
+        Ok(versions)
     }
 
     fn user_doc(user: &User) -> Document {
         doc! {
             "id": user.id.to_string(),
+            "account_id": user.account_id.to_string(),
             "channel": user.channel.as_str(),
             "handle": &user.handle,
             "role": user.role.as_str(),
@@ -101,7 +287,9 @@ impl MongoStore {
         doc! {
             "id": request.id.to_string(),
             "user_id": request.user_id.to_string(),
+            "account_id": request.account_id.to_string(),
             "channel": request.channel.as_str(),
+            "recipient": request.recipient.map(|address| Bson::String(address.to_string())).unwrap_or(Bson::Null),
             "amount": request.amount as i64,
             "status": request.status.as_str(),
             "tx_hash": request.tx_hash.clone().map(Bson::String).unwrap_or(Bson::Null),
@@ -112,6 +300,28 @@ impl MongoStore {
                 .map(|dt| Bson::DateTime(mongodb::bson::DateTime::from_chrono(dt)))
                 .unwrap_or(Bson::Null),
             "attempt": request.attempt as i64,
+            "available_at": request
+                .available_at
+                .map(|dt| Bson::DateTime(mongodb::bson::DateTime::from_chrono(dt)))
+                .unwrap_or(Bson::Null),
+            "traceparent": request.traceparent.clone().map(Bson::String).unwrap_or(Bson::Null),
+            "http_request_id": request
+                .http_request_id
+                .clone()
+                .map(Bson::String)
+                .unwrap_or(Bson::Null),
+            "sequence_number": request
+                .sequence_number
+                .map(|value| Bson::Int64(value as i64))
+                .unwrap_or(Bson::Null),
+            "tx_expires_at": request
+                .tx_expires_at
+                .map(|dt| Bson::DateTime(mongodb::bson::DateTime::from_chrono(dt)))
+                .unwrap_or(Bson::Null),
+            "quota_day": request
+                .quota_day
+                .map(|day| Bson::String(day.to_string()))
+                .unwrap_or(Bson::Null),
         }
     }
 
@@ -126,8 +336,14 @@ impl MongoStore {
     }
 
     fn doc_to_user(doc: Document) -> Result<User> {
+        let id = Uuid::parse_str(doc.get_str("id")?)?;
         Ok(User {
-            id: Uuid::parse_str(doc.get_str("id")?)?,
+            id,
+            // 账户体系引入前的用户以自身 id 作为账户 id
+            account_id: match doc.get_str("account_id") {
+                Ok(value) => Uuid::parse_str(value)?,
+                Err(_) => id,
+            },
             channel: channel_from_db(doc.get_str("channel")?)?,
             handle: doc.get_str("handle")?.to_string(),
             role: role_from_db(doc.get_str("role")?)?,
@@ -140,10 +356,19 @@ impl MongoStore {
     }
 
     fn doc_to_request(doc: Document) -> Result<MintRequest> {
+        let user_id = Uuid::parse_str(doc.get_str("user_id")?)?;
         Ok(MintRequest {
             id: Uuid::parse_str(doc.get_str("id")?)?,
-            user_id: Uuid::parse_str(doc.get_str("user_id")?)?,
+            user_id,
+            account_id: match doc.get_str("account_id") {
+                Ok(value) => Uuid::parse_str(value)?,
+                Err(_) => user_id,
+            },
             channel: channel_from_db(doc.get_str("channel")?)?,
+            recipient: match doc.get("recipient") {
+                Some(Bson::String(value)) => Some(address_from_db(value)?),
+                _ => None,
+            },
             amount: doc.get_i64("amount")? as u64,
             status: status_from_db(doc.get_str("status")?)?,
             tx_hash: match doc.get("tx_hash") {
@@ -160,6 +385,78 @@ impl MongoStore {
                 _ => None,
             },
             attempt: doc.get_i64("attempt")? as u16,
+            available_at: match doc.get("available_at") {
+                Some(Bson::DateTime(dt)) => Some(dt.to_chrono()),
+                _ => None,
+            },
+            traceparent: match doc.get("traceparent") {
+                Some(Bson::String(value)) => Some(value.clone()),
+                _ => None,
+            },
+            http_request_id: match doc.get("http_request_id") {
+                Some(Bson::String(value)) => Some(value.clone()),
+                _ => None,
+            },
+            sequence_number: match doc.get("sequence_number") {
+                Some(Bson::Int64(value)) => Some(*value as u64),
+                _ => None,
+            },
+            tx_expires_at: match doc.get("tx_expires_at") {
+                Some(Bson::DateTime(dt)) => Some(dt.to_chrono()),
+                _ => None,
+            },
+            quota_day: match doc.get("quota_day") {
+                Some(Bson::String(day)) => Some(NaiveDate::parse_from_str(day, "%Y-%m-%d")?),
+                _ => None,
+            },
+        })
+    }
+
+    /// 在 `cap` 之内原子地累加 `minted_total`，`initial` 为当天首次写入的完整文档。
+    async fn guarded_increment(
+        collection: &Collection<Document>,
+        key: Document,
+        initial: Document,
+        amount: u64,
+        cap: u64,
+    ) -> Result<bool> {
+        if amount > cap {
+            return Ok(false);
+        }
+
+        // 先按上限条件累加；当天还没有记录时插入，插入撞上唯一索引说明
+        // 其他请求刚创建了记录，再按条件累加一次
+        let mut guarded = key;
+        guarded.insert("minted_total", doc! {"$lte": (cap - amount) as i64});
+        let increment = doc! {"$inc": {"minted_total": amount as i64}};
+        let result = collection
+            .update_one(guarded.clone(), increment.clone(), None)
+            .await?;
+        if result.matched_count == 1 {
+            return Ok(true);
+        }
+
+        match collection.insert_one(initial, None).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_duplicate_key(&err) => {
+                let result = collection.update_one(guarded, increment, None).await?;
+                Ok(result.matched_count == 1)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn doc_to_config(doc: Document) -> Result<SystemConfig> {
+        Ok(SystemConfig {
+            id: Uuid::parse_str(doc.get_str("id")?)?,
+            key: doc.get_str("key")?.to_string(),
+            value: doc.get_str("value")?.to_string(),
+            description: match doc.get("description") {
+                Some(Bson::String(value)) => Some(value.clone()),
+                _ => None,
+            },
+            created_at: doc.get_datetime("created_at")?.to_chrono(),
+            updated_at: doc.get_datetime("updated_at")?.to_chrono(),
         })
     }
 
@@ -177,31 +474,199 @@ impl MongoStore {
 #[async_trait::async_trait]
 impl UserRepository for MongoStore {
     async fn upsert_user(&self, user: &User) -> Result<()> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.accounts()
+            .update_one(
+                doc! {"id": user.account_id.to_string()},
+                doc! {"$setOnInsert": {"role": user.role.as_str()}},
+                options.clone(),
+            )
+            .await?;
+
         let filter = doc! {"id": user.id.to_string()};
         let update = doc! {"$set": Self::user_doc(user)};
-        let options = UpdateOptions::builder().upsert(true).build();
         self.users().update_one(filter, update, options).await?;
         Ok(())
     }
 
     async fn find_user(&self, channel: &str, handle: &str) -> Result<Option<User>> {
         let filter = doc! {"channel": channel, "handle": handle};
-        let result = self.users().find_one(filter, None).await?;
-        result.map(Self::doc_to_user).transpose()
+        let Some(doc) = self.users().find_one(filter, None).await? else {
+            return Ok(None);
+        };
+        let mut user = Self::doc_to_user(doc)?;
+
+        // 角色以账户为准，旧数据没有账户记录时沿用用户文档中的角色
+        let account = self
+            .accounts()
+            .find_one(doc! {"id": user.account_id.to_string()}, None)
+            .await?;
+        if let Some(account) = account {
+            user.role = role_from_db(account.get_str("role")?)?;
+        }
+        Ok(Some(user))
+    }
+
+    async fn set_role(&self, account_id: Uuid, role: Role) -> Result<()> {
+        self.accounts()
+            .update_one(
+                doc! {"id": account_id.to_string()},
+                doc! {"$set": {"role": role.as_str()}},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn link_user(&self, user_id: Uuid, account_id: Uuid) -> Result<()> {
+        let filter = doc! {"id": user_id.to_string()};
+        let update = doc! {"$set": {"account_id": account_id.to_string()}};
+        self.users().update_one(filter, update, None).await?;
+        Ok(())
     }
 
-    async fn set_role(&self, user_id: Uuid, role: Role) -> Result<()> {
+    async fn rename_user(&self, user_id: Uuid, handle: &str) -> Result<()> {
         let filter = doc! {"id": user_id.to_string()};
-        let update = doc! {"$set": {"role": role.as_str()}};
+        let update = doc! {"$set": {"handle": handle}};
         self.users().update_one(filter, update, None).await?;
         Ok(())
     }
+
+    async fn create_link_code(&self, code: &LinkCode) -> Result<()> {
+        self.link_codes()
+            .insert_one(
+                doc! {
+                    "code": &code.code,
+                    "account_id": code.account_id.to_string(),
+                    "expires_at": mongodb::bson::DateTime::from_chrono(code.expires_at),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn consume_link_code(&self, code: &str, now: DateTime<Utc>) -> Result<Option<LinkCode>> {
+        let Some(doc) = self
+            .link_codes()
+            .find_one_and_delete(doc! {"code": code}, None)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let link = LinkCode {
+            code: doc.get_str("code")?.to_string(),
+            account_id: Uuid::parse_str(doc.get_str("account_id")?)?,
+            expires_at: doc.get_datetime("expires_at")?.to_chrono(),
+        };
+        Ok((link.expires_at > now).then_some(link))
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionRepository for MongoStore {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<()> {
+        let now = mongodb::bson::DateTime::from_chrono(Utc::now());
+        self.refresh_tokens()
+            .delete_many(doc! {"expires_at": {"$lte": now}}, None)
+            .await?;
+        self.refresh_tokens()
+            .insert_one(
+                doc! {
+                    "token_hash": &token.token_hash,
+                    "session_id": token.session_id.to_string(),
+                    "channel": token.channel.as_str(),
+                    "handle": &token.handle,
+                    "expires_at": mongodb::bson::DateTime::from_chrono(token.expires_at),
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>> {
+        let Some(doc) = self
+            .refresh_tokens()
+            .find_one_and_delete(doc! {"token_hash": token_hash}, None)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let token = RefreshToken {
+            token_hash: doc.get_str("token_hash")?.to_string(),
+            session_id: Uuid::parse_str(doc.get_str("session_id")?)?,
+            channel: channel_from_db(doc.get_str("channel")?)?,
+            handle: doc.get_str("handle")?.to_string(),
+            expires_at: doc.get_datetime("expires_at")?.to_chrono(),
+        };
+        Ok((token.expires_at > now).then_some(token))
+    }
+
+    async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        self.refresh_tokens()
+            .delete_many(doc! {"session_id": session_id.to_string()}, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        let now = mongodb::bson::DateTime::from_chrono(Utc::now());
+        self.revoked_tokens()
+            .delete_many(doc! {"expires_at": {"$lte": now}}, None)
+            .await?;
+        self.revoked_tokens()
+            .update_one(
+                doc! {"jti": jti},
+                doc! {"$max": {"expires_at": mongodb::bson::DateTime::from_chrono(expires_at)}},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str, now: DateTime<Utc>) -> Result<bool> {
+        let found = self
+            .revoked_tokens()
+            .find_one(
+                doc! {"jti": jti, "expires_at": {"$gt": mongodb::bson::DateTime::from_chrono(now)}},
+                None,
+            )
+            .await?;
+        Ok(found.is_some())
+    }
+
+    async fn use_login_nonce(&self, nonce: &LoginNonce, now: DateTime<Utc>) -> Result<bool> {
+        self.login_nonces()
+            .delete_many(
+                doc! {"expires_at": {"$lte": mongodb::bson::DateTime::from_chrono(now)}},
+                None,
+            )
+            .await?;
+        // nonce 上有唯一索引，只有真正插入了文档的一方算作使用成功
+        let result = self
+            .login_nonces()
+            .update_one(
+                doc! {"nonce": &nonce.nonce},
+                doc! {"$setOnInsert": {
+                    "expires_at": mongodb::bson::DateTime::from_chrono(nonce.expires_at),
+                }},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(result.upserted_id.is_some())
+    }
 }
 
 #[async_trait::async_trait]
 impl MintRepository for MongoStore {
     async fn enqueue(&self, request: &MintRequest) -> Result<()> {
-        let doc = Self::request_doc(request);
+        let mut doc = Self::request_doc(request);
+        doc.insert("status", MintStatus::Pending.as_str());
         self.requests()
             .update_one(
                 doc! {"id": request.id.to_string()},
@@ -212,10 +677,28 @@ impl MintRepository for MongoStore {
         Ok(())
     }
 
-    async fn next_pending(&self) -> Result<Option<MintRequest>> {
-        let now = mongodb::bson::DateTime::from_chrono(Utc::now());
+    async fn next_pending(&self, lease: std::time::Duration) -> Result<Option<MintRequest>> {
+        let now = Utc::now();
+        let lease_until = now + Duration::from_std(lease)?;
+        let now = mongodb::bson::DateTime::from_chrono(now);
+        let filter = doc! {
+            "$or": [
+                {
+                    "status": MintStatus::Pending.as_str(),
+                    "$or": [{"available_at": Bson::Null}, {"available_at": {"$lte": now}}],
+                },
+                {
+                    "status": MintStatus::Processing.as_str(),
+                    "available_at": {"$lte": now},
+                },
+            ]
+        };
         let update = doc! {
-            "$set": {"status": MintStatus::Processing.as_str(), "processed_at": now},
+            "$set": {
+                "status": MintStatus::Processing.as_str(),
+                "processed_at": now,
+                "available_at": mongodb::bson::DateTime::from_chrono(lease_until),
+            },
             "$inc": {"attempt": 1},
         };
         let options = FindOneAndUpdateOptions::builder()
@@ -224,11 +707,7 @@ impl MintRepository for MongoStore {
             .build();
         let doc = self
             .requests()
-            .find_one_and_update(
-                doc! {"status": MintStatus::Pending.as_str()},
-                update,
-                options,
-            )
+            .find_one_and_update(filter, update, options)
             .await?;
 
         doc.map(Self::doc_to_request).transpose()
@@ -255,7 +734,32 @@ impl MintRepository for MongoStore {
         Ok(())
     }
 
-    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<()> {
+    async fn record_submission(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        transfer: &PendingTransfer,
+    ) -> Result<bool> {
+        let result = self
+            .requests()
+            .update_one(
+                doc! {
+                    "id": request_id.to_string(),
+                    "attempt": attempt as i64,
+                    "status": MintStatus::Processing.as_str(),
+                },
+                doc! {"$set": {
+                    "tx_hash": &transfer.hash,
+                    "sequence_number": transfer.sequence_number as i64,
+                    "tx_expires_at": mongodb::bson::DateTime::from_chrono(transfer.expires_at),
+                }},
+                None,
+            )
+            .await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<bool> {
         let mut set_doc = doc! {
             "status": outcome.request.status.as_str(),
             "tx_hash": outcome.tx_hash.clone().map(Bson::String).unwrap_or(Bson::Null),
@@ -265,7 +769,6 @@ impl MintRepository for MongoStore {
                 .processed_at
                 .map(|dt| Bson::DateTime(mongodb::bson::DateTime::from_chrono(dt)))
                 .unwrap_or(Bson::Null),
-            "attempt": outcome.request.attempt as i64,
         };
         if set_doc.get("processed_at") == Some(&Bson::Null) {
             set_doc.insert(
@@ -274,49 +777,259 @@ impl MintRepository for MongoStore {
             );
         }
 
-        self.requests()
+        let result = self
+            .requests()
             .update_one(
-                doc! {"id": outcome.request.id.to_string()},
+                doc! {
+                    "id": outcome.request.id.to_string(),
+                    "attempt": outcome.request.attempt as i64,
+                },
                 doc! {"$set": set_doc},
                 None,
             )
             .await?;
+        if result.matched_count == 0 {
+            return Ok(false);
+        }
 
         if outcome.request.status == MintStatus::Completed {
+            let day = outcome.request.charged_day();
+            let mut quota = Self::quota_doc(outcome.request.account_id, day);
+            quota.remove("success_count");
             self.quotas()
                 .update_one(
-                    doc! {"user_id": outcome.request.user_id.to_string(), "day": outcome.request.requested_at.date_naive().to_string()},
-                    doc! {"$inc": {"success_count": 1}},
-                    None,
+                    doc! {"user_id": outcome.request.account_id.to_string(), "day": day.to_string()},
+                    doc! {
+                        "$setOnInsert": quota,
+                        "$inc": {"success_count": 1i64}
+                    },
+                    UpdateOptions::builder().upsert(true).build(),
                 )
                 .await?;
         }
 
-        Ok(())
+        Ok(true)
+    }
+
+    async fn schedule_retry(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        error: &str,
+        available_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = self
+            .requests()
+            .update_one(
+                doc! {"id": request_id.to_string(), "attempt": attempt as i64},
+                doc! {"$set": {
+                    "status": MintStatus::Pending.as_str(),
+                    "error": error,
+                    "available_at": mongodb::bson::DateTime::from_chrono(available_at),
+                }},
+                None,
+            )
+            .await?;
+        Ok(result.matched_count == 1)
+    }
+
+    async fn find_request(&self, request_id: Uuid) -> Result<Option<MintRequest>> {
+        let doc = self
+            .requests()
+            .find_one(doc! {"id": request_id.to_string()}, None)
+            .await?;
+        doc.map(Self::doc_to_request).transpose()
+    }
+
+    async fn list_requests_for_account(
+        &self,
+        account_id: Uuid,
+        before: Option<MintCursor>,
+        limit: usize,
+    ) -> Result<Vec<MintRequest>> {
+        // 旧请求没有 account_id，此时账户 id 即用户 id
+        let mut clauses = vec![doc! {
+            "$or": [
+                {"account_id": account_id.to_string()},
+                {"account_id": {"$exists": false}, "user_id": account_id.to_string()},
+            ]
+        }];
+        if let Some(cursor) = before {
+            let at = mongodb::bson::DateTime::from_chrono(cursor.requested_at);
+            clauses.push(doc! {
+                "$or": [
+                    {"requested_at": {"$lt": at}},
+                    {"requested_at": at, "id": {"$lt": cursor.id.to_string()}},
+                ]
+            });
+        }
+        let filter = doc! {"$and": clauses};
+        let options = FindOptions::builder()
+            .sort(doc! {"requested_at": -1, "id": -1})
+            .limit(limit as i64)
+            .build();
+
+        let mut cursor = self.requests().find(filter, options).await?;
+        let mut requests = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            requests.push(Self::doc_to_request(doc)?);
+        }
+        Ok(requests)
+    }
+
+    async fn recent_mints(
+        &self,
+        account_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<MintRequest>> {
+        // 旧请求没有 account_id，此时账户 id 即用户 id
+        let filter = doc! {
+            "$or": [
+                {"account_id": account_id.to_string()},
+                {"account_id": {"$exists": false}, "user_id": account_id.to_string()},
+            ],
+            "requested_at": {"$gte": mongodb::bson::DateTime::from_chrono(since)},
+            "status": {"$ne": MintStatus::Failed.as_str()},
+        };
+        let options = FindOptions::builder().sort(doc! {"requested_at": 1}).build();
+
+        let mut cursor = self.requests().find(filter, options).await?;
+        let mut requests = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            requests.push(Self::doc_to_request(doc)?);
+        }
+        Ok(requests)
+    }
+
+    async fn pending_count(&self) -> Result<u64> {
+        let filter = doc! {"status": MintStatus::Pending.as_str()};
+        Ok(self.requests().count_documents(filter, None).await?)
     }
 }
 
 #[async_trait::async_trait]
 impl QuotaRepository for MongoStore {
-    async fn record_mint(&self, user_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
+    async fn record_mint(
+        &self,
+        account_id: Uuid,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        let Some(cap) = cap else {
+            let mut quota = Self::quota_doc(account_id, day);
+            quota.remove("minted_total");
+            self.quotas()
+                .update_one(
+                    doc! {"user_id": account_id.to_string(), "day": day.to_string()},
+                    doc! {
+                        "$setOnInsert": quota,
+                        "$inc": {"minted_total": amount as i64}
+                    },
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await?;
+            return Ok(true);
+        };
+
+        let key = doc! {"user_id": account_id.to_string(), "day": day.to_string()};
+        let mut quota = Self::quota_doc(account_id, day);
+        quota.insert("minted_total", amount as i64);
+        Self::guarded_increment(&self.quotas(), key, quota, amount, cap).await
+    }
+
+    async fn release_mint(
+        &self,
+        account_id: Uuid,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        let update = vec![doc! {
+            "$set": {
+                "minted_total": {"$max": [0i64, {"$subtract": ["$minted_total", amount as i64]}]}
+            }
+        }];
         self.quotas()
             .update_one(
-                doc! {"user_id": user_id.to_string(), "day": day.to_string()},
-                doc! {
-                    "$setOnInsert": Self::quota_doc(user_id, day),
-                    "$inc": {"minted_total": amount as i64}
-                },
+                doc! {"user_id": account_id.to_string(), "day": day.to_string()},
+                update,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn record_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        let key = doc! {"address": address.to_string(), "day": day.to_string()};
+        let Some(cap) = cap else {
+            self.address_quotas()
+                .update_one(
+                    key,
+                    doc! {"$inc": {"minted_total": amount as i64}},
+                    UpdateOptions::builder().upsert(true).build(),
+                )
+                .await?;
+            return Ok(true);
+        };
+
+        let mut initial = key.clone();
+        initial.insert("minted_total", amount as i64);
+        Self::guarded_increment(&self.address_quotas(), key, initial, amount, cap).await
+    }
+
+    async fn release_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        let update = vec![doc! {
+            "$set": {
+                "minted_total": {"$max": [0i64, {"$subtract": ["$minted_total", amount as i64]}]}
+            }
+        }];
+        self.address_quotas()
+            .update_one(
+                doc! {"address": address.to_string(), "day": day.to_string()},
+                update,
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn set_limits_reset(&self, account_id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        self.rate_limit_resets()
+            .update_one(
+                doc! {"account_id": account_id.to_string()},
+                doc! {"$set": {"reset_at": mongodb::bson::DateTime::from_chrono(at)}},
                 UpdateOptions::builder().upsert(true).build(),
             )
             .await?;
         Ok(())
     }
 
-    async fn fetch_quota(&self, user_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
+    async fn limits_reset_at(&self, account_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let doc = self
+            .rate_limit_resets()
+            .find_one(doc! {"account_id": account_id.to_string()}, None)
+            .await?;
+        Ok(doc
+            .map(|doc| doc.get_datetime("reset_at").map(|at| at.to_chrono()))
+            .transpose()?)
+    }
+
+    async fn fetch_quota(&self, account_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
         let doc = self
             .quotas()
             .find_one(
-                doc! {"user_id": user_id.to_string(), "day": day.to_string()},
+                doc! {"user_id": account_id.to_string(), "day": day.to_string()},
                 None,
             )
             .await?;
@@ -324,18 +1037,20 @@ impl QuotaRepository for MongoStore {
     }
 }
 
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == 11000,
+        _ => false,
+    }
+}
+
 #[async_trait::async_trait]
 impl ReportingRepository for MongoStore {
     async fn daily_summary(&self, day: NaiveDate) -> Result<Vec<DailyReportRow>> {
-        let start = mongodb::bson::DateTime::from_chrono(DateTime::<Utc>::from_utc(
-            day.and_hms_opt(0, 0, 0).unwrap(),
-            Utc,
-        ));
+        let start = mongodb::bson::DateTime::from_chrono(day.and_hms_opt(0, 0, 0).unwrap().and_utc());
         let end_date = day + Duration::days(1);
-        let end = mongodb::bson::DateTime::from_chrono(DateTime::<Utc>::from_utc(
-            end_date.and_hms_opt(0, 0, 0).unwrap(),
-            Utc,
-        ));
+        let end =
+            mongodb::bson::DateTime::from_chrono(end_date.and_hms_opt(0, 0, 0).unwrap().and_utc());
 
         let pipeline = vec![
             doc! {
@@ -349,16 +1064,17 @@ impl ReportingRepository for MongoStore {
                     "total_amount": {"$sum": "$amount"},
                     "success_count": {
                         "$sum": {
-                            "$cond": [{"$eq": ["$status", MintStatus::Completed.as_str()]}, 1, 0]
+                            "$cond": [{"$eq": ["$status", MintStatus::Completed.as_str()]}, 1i64, 0i64]
                         }
                     },
                     "failure_count": {
                         "$sum": {
-                            "$cond": [{"$eq": ["$status", MintStatus::Failed.as_str()]}, 1, 0]
+                            "$cond": [{"$eq": ["$status", MintStatus::Failed.as_str()]}, 1i64, 0i64]
                         }
                     }
                 }
             },
+            doc! {"$sort": {"_id": 1}},
         ];
 
         let mut cursor = self.requests().aggregate(pipeline, None).await?;
@@ -393,3 +1109,240 @@ impl ReportingRepository for MongoStore {
         Ok(())
     }
 }
+
+#[async_trait::async_trait]
+impl ConfigRepository for MongoStore {
+    async fn get_config(&self, key: &str) -> Result<Option<SystemConfig>> {
+        let doc = self.configs().find_one(doc! {"key": key}, None).await?;
+        doc.map(Self::doc_to_config).transpose()
+    }
+
+    async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> Result<()> {
+        let now = mongodb::bson::DateTime::from_chrono(Utc::now());
+        self.configs()
+            .update_one(
+                doc! {"key": key},
+                doc! {
+                    "$set": {
+                        "value": value,
+                        "description": description.map(|s| Bson::String(s.to_string())).unwrap_or(Bson::Null),
+                        "updated_at": now,
+                    },
+                    "$setOnInsert": {
+                        "id": Uuid::new_v4().to_string(),
+                        "created_at": now,
+                    }
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get_all_configs(&self) -> Result<Vec<SystemConfig>> {
+        let options = FindOptions::builder().sort(doc! {"key": 1}).build();
+        let mut cursor = self.configs().find(doc! {}, options).await?;
+        let mut configs = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            configs.push(Self::doc_to_config(doc)?);
+        }
+        Ok(configs)
+    }
+
+    async fn update_limit_config(&self, config: &LimitConfigUpdate) -> Result<()> {
+        if let Some(amount) = config.default_amount {
+            self.set_config("limits.default_amount", &amount.to_string(), Some("Default user amount")).await?;
+        }
+        if let Some(cap) = config.default_daily_cap {
+            self.set_config("limits.default_daily_cap", &cap.to_string(), Some("Default user daily cap")).await?;
+        }
+        if let Some(amount) = config.privileged_amount {
+            self.set_config("limits.privileged_amount", &amount.to_string(), Some("Privileged user amount")).await?;
+        }
+        if let Some(cap) = config.privileged_daily_cap {
+            self.set_config("limits.privileged_daily_cap", &cap.to_string(), Some("Privileged user daily cap")).await?;
+        }
+        if let Some(cap) = config.address_daily_cap {
+            self.set_config("limits.address_daily_cap", &cap.to_string(), Some("Per-address daily cap")).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_limit_config(&self) -> Result<Option<LimitConfigUpdate>> {
+        let default_amount = self.get_config("limits.default_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_amount = self.get_config("limits.privileged_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let address_daily_cap = self.get_config("limits.address_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+
+        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() || address_daily_cap.is_some() {
+            Ok(Some(LimitConfigUpdate {
+                default_amount,
+                default_daily_cap,
+                privileged_amount,
+                privileged_daily_cap,
+                address_daily_cap,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// 逐条读出整个集合
+fn export_collection<T, F>(collection: Collection<Document>, map: F) -> BoxStream<'static, Result<T>>
+where
+    T: Send + 'static,
+    F: Fn(Document) -> Result<T> + Send + 'static,
+{
+    stream::once(async move {
+        let cursor = collection.find(doc! {}, None).await?;
+        Ok::<_, anyhow::Error>(cursor.map(move |doc| map(doc?)))
+    })
+    .try_flatten()
+    .boxed()
+}
+
+#[async_trait::async_trait]
+impl TransferRepository for MongoStore {
+    fn export_users(&self) -> BoxStream<'_, Result<User>> {
+        let (users, accounts) = (self.users(), self.accounts());
+        stream::once(async move {
+            // 账户表远小于用户表，先整体读入以便为每个用户填上账户角色
+            let mut roles = HashMap::new();
+            let mut cursor = accounts.find(doc! {}, None).await?;
+            while let Some(account) = cursor.try_next().await? {
+                roles.insert(
+                    account.get_str("id")?.to_string(),
+                    role_from_db(account.get_str("role")?)?,
+                );
+            }
+            Ok::<_, anyhow::Error>(export_collection(users, move |doc| {
+                let mut user = Self::doc_to_user(doc)?;
+                if let Some(role) = roles.get(&user.account_id.to_string()) {
+                    user.role = role.clone();
+                }
+                Ok(user)
+            }))
+        })
+        .try_flatten()
+        .boxed()
+    }
+
+    fn export_requests(&self) -> BoxStream<'_, Result<MintRequest>> {
+        export_collection(self.requests(), Self::doc_to_request)
+    }
+
+    fn export_quotas(&self) -> BoxStream<'_, Result<Quota>> {
+        export_collection(self.quotas(), Self::doc_to_quota)
+    }
+
+    fn export_address_quotas(&self) -> BoxStream<'_, Result<AddressQuota>> {
+        export_collection(self.address_quotas(), |doc| {
+            Ok(AddressQuota {
+                address: address_from_db(doc.get_str("address")?)?,
+                day: NaiveDate::parse_from_str(doc.get_str("day")?, "%Y-%m-%d")?,
+                minted_total: doc.get_i64("minted_total")? as u64,
+            })
+        })
+    }
+
+    fn export_failures(&self) -> BoxStream<'_, Result<MintFailure>> {
+        export_collection(self.failures(), |doc| {
+            Ok(MintFailure {
+                id: Uuid::parse_str(doc.get_str("id")?)?,
+                request_id: Uuid::parse_str(doc.get_str("request_id")?)?,
+                failed_at: doc.get_datetime("failed_at")?.to_chrono(),
+                reason: doc.get_str("reason")?.to_string(),
+            })
+        })
+    }
+
+    fn export_configs(&self) -> BoxStream<'_, Result<SystemConfig>> {
+        export_collection(self.configs(), Self::doc_to_config)
+    }
+
+    async fn import_user(&self, user: &User) -> Result<bool> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.accounts()
+            .update_one(
+                doc! {"id": user.account_id.to_string()},
+                doc! {"$setOnInsert": {"role": user.role.as_str()}},
+                options.clone(),
+            )
+            .await?;
+        insert_missing(&self.users(), doc! {"id": user.id.to_string()}, Self::user_doc(user)).await
+    }
+
+    async fn import_request(&self, request: &MintRequest) -> Result<bool> {
+        insert_missing(
+            &self.requests(),
+            doc! {"id": request.id.to_string()},
+            Self::request_doc(request),
+        )
+        .await
+    }
+
+    async fn import_quota(&self, quota: &Quota) -> Result<bool> {
+        let key = doc! {"user_id": quota.user_id.to_string(), "day": quota.day.to_string()};
+        let mut quota_doc = key.clone();
+        quota_doc.insert("id", quota.id.to_string());
+        quota_doc.insert("minted_total", quota.minted_total as i64);
+        quota_doc.insert("success_count", quota.success_count as i64);
+        insert_missing(&self.quotas(), key, quota_doc).await
+    }
+
+    async fn import_address_quota(&self, quota: &AddressQuota) -> Result<bool> {
+        let key = doc! {"address": quota.address.to_string(), "day": quota.day.to_string()};
+        let mut quota_doc = key.clone();
+        quota_doc.insert("minted_total", quota.minted_total as i64);
+        insert_missing(&self.address_quotas(), key, quota_doc).await
+    }
+
+    async fn import_failure(&self, failure: &MintFailure) -> Result<bool> {
+        insert_missing(
+            &self.failures(),
+            doc! {"id": failure.id.to_string()},
+            doc! {
+                "id": failure.id.to_string(),
+                "request_id": failure.request_id.to_string(),
+                "failed_at": mongodb::bson::DateTime::from_chrono(failure.failed_at),
+                "reason": &failure.reason,
+            },
+        )
+        .await
+    }
+
+    async fn import_config(&self, config: &SystemConfig) -> Result<bool> {
+        insert_missing(
+            &self.configs(),
+            doc! {"key": &config.key},
+            doc! {
+                "id": config.id.to_string(),
+                "key": &config.key,
+                "value": &config.value,
+                "description": config.description.clone().map(Bson::String).unwrap_or(Bson::Null),
+                "created_at": mongodb::bson::DateTime::from_chrono(config.created_at),
+                "updated_at": mongodb::bson::DateTime::from_chrono(config.updated_at),
+            },
+        )
+        .await
+    }
+}
+
+/// 不存在匹配 `key` 的文档时插入 `document`；已存在或撞上其他唯一索引时返回 `false`
+async fn insert_missing(
+    collection: &Collection<Document>,
+    key: Document,
+    document: Document,
+) -> Result<bool> {
+    let options = UpdateOptions::builder().upsert(true).build();
+    match collection
+        .update_one(key, doc! {"$setOnInsert": document}, options)
+        .await
+    {
+        Ok(result) => Ok(result.upserted_id.is_some()),
+        Err(err) if is_duplicate_key(&err) => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
@@ -1,41 +1,56 @@
 use anyhow::Result;
 use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::stream::{BoxStream, StreamExt};
 use sqlx::{postgres::PgPoolOptions, PgPool, Row};
 use tracing::info;
 use uuid::Uuid;
 
 use crate::{
+    db::MigrationStep,
     models::{
-        channel_from_db, role_from_db, status_from_db, MintOutcome, MintRequest, MintStatus, Quota,
-        Role, User,
+        address_from_db, channel_from_db, role_from_db, status_from_db, AccountAddress,
+        AddressQuota, LinkCode, LoginNonce, MintFailure, MintOutcome, MintRequest, MintStatus,
+        PendingTransfer, Quota, RefreshToken, Role, SystemConfig, User,
     },
     repository::{
-        DailyReportRow, MintRepository, QuotaRepository, ReportingRepository, UserRepository,
+        DailyReportRow, MintCursor, MintRepository, QuotaRepository, ReportingRepository,
+        SessionRepository, TransferRepository, UserRepository,
     },
 };
 
 const MAX_CONNECTIONS: u32 = 10;
+/// `pg_advisory_xact_lock` 使用的锁 id，防止多个进程同时迁移
+const MIGRATION_LOCK_ID: i64 = 0x6661_7563_6574;
 
-#[derive(Clone)]
-pub struct PostgresStore {
-    pool: PgPool,
+struct Migration {
+    version: i64,
+    name: &'static str,
+    statements: &'static [&'static str],
 }
 
-impl PostgresStore {
-    pub async fn connect(url: &str) -> Result<Self> {
-        let pool = PgPoolOptions::new()
-            .max_connections(MAX_CONNECTIONS)
-            .connect(url)
-            .await?;
-
-        Self::ensure_schema(&pool).await?;
-        info!("postgres schema ready");
-
-        Ok(Self { pool })
+impl Migration {
+    fn step(&self) -> MigrationStep {
+        MigrationStep {
+            version: self.version,
+            name: self.name,
+            statements: self
+                .statements
+                .iter()
+                .map(|statement| statement.trim().to_string())
+                .collect(),
+        }
     }
+}
 
-    async fn ensure_schema(pool: &PgPool) -> Result<()> {
-        let statements = [
+/// 按版本号排列的 schema 迁移，已发布的版本不可修改，只能追加新版本。
+///
+/// 版本 1 至 3 对应引入版本管理之前 `ensure_schema` 建出的结构，语句均可重复执行，
+/// 因此已有数据库首次迁移时会直接记为已应用。
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        statements: &[
             r#"
             CREATE TABLE IF NOT EXISTS users (
                 id UUID PRIMARY KEY,
@@ -52,22 +67,36 @@ impl PostgresStore {
                 id UUID PRIMARY KEY,
                 user_id UUID NOT NULL REFERENCES users(id),
                 channel TEXT NOT NULL,
+                recipient TEXT NULL,
                 amount BIGINT NOT NULL,
                 status TEXT NOT NULL,
                 tx_hash TEXT NULL,
                 error TEXT NULL,
                 requested_at TIMESTAMPTZ NOT NULL,
                 processed_at TIMESTAMPTZ NULL,
-                attempt INTEGER NOT NULL DEFAULT 0
+                attempt INTEGER NOT NULL DEFAULT 0,
+                available_at TIMESTAMPTZ NULL
             );
             "#,
             r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS recipient TEXT NULL;
+            "#,
+            r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS available_at TIMESTAMPTZ NULL;
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_status_idx ON mint_requests(status, available_at);
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_user_idx ON mint_requests(user_id, requested_at DESC, id DESC);
+            "#,
+            r#"
             CREATE INDEX IF NOT EXISTS mint_requests_requested_idx ON mint_requests(requested_at);
             "#,
             r#"
             CREATE TABLE IF NOT EXISTS quotas (
                 id UUID PRIMARY KEY,
-                user_id UUID NOT NULL REFERENCES users(id),
+                user_id UUID NOT NULL,
                 day DATE NOT NULL,
                 minted_total BIGINT NOT NULL,
                 success_count BIGINT NOT NULL,
@@ -92,18 +121,239 @@ impl PostgresStore {
                 updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
             );
             "#,
-        ];
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "address_quotas",
+        statements: &[r#"
+            CREATE TABLE IF NOT EXISTS address_quotas (
+                address TEXT NOT NULL,
+                day DATE NOT NULL,
+                minted_total BIGINT NOT NULL,
+                PRIMARY KEY(address, day)
+            );
+            "#],
+    },
+    Migration {
+        version: 3,
+        name: "accounts",
+        statements: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS accounts (
+                id UUID PRIMARY KEY,
+                role TEXT NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+            "#,
+            r#"
+            ALTER TABLE users ADD COLUMN IF NOT EXISTS account_id UUID NULL REFERENCES accounts(id);
+            "#,
+            // 账户体系引入前的用户各自成为一个账户，账户 id 沿用用户 id
+            r#"
+            INSERT INTO accounts (id, role)
+            SELECT id, role FROM users WHERE account_id IS NULL
+            ON CONFLICT (id) DO NOTHING;
+            "#,
+            r#"
+            UPDATE users SET account_id = id WHERE account_id IS NULL;
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS users_account_idx ON users(account_id);
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS link_codes (
+                code TEXT PRIMARY KEY,
+                account_id UUID NOT NULL REFERENCES accounts(id),
+                expires_at TIMESTAMPTZ NOT NULL
+            );
+            "#,
+            r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS account_id UUID NULL;
+            "#,
+            r#"
+            UPDATE mint_requests SET account_id = user_id WHERE account_id IS NULL;
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_account_idx ON mint_requests(account_id, requested_at);
+            "#,
+            // 额度改为按账户统计，user_id 列保存账户 id
+            r#"
+            ALTER TABLE quotas DROP CONSTRAINT IF EXISTS quotas_user_id_fkey;
+            "#,
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "mint_request_traceparent",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS traceparent TEXT NULL;
+            "#],
+    },
+    Migration {
+        version: 5,
+        name: "sessions",
+        statements: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS refresh_tokens (
+                token_hash TEXT PRIMARY KEY,
+                session_id UUID NOT NULL,
+                channel TEXT NOT NULL,
+                handle TEXT NOT NULL,
+                expires_at TIMESTAMPTZ NOT NULL
+            );
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS refresh_tokens_session_idx ON refresh_tokens(session_id);
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS revoked_tokens (
+                jti TEXT PRIMARY KEY,
+                expires_at TIMESTAMPTZ NOT NULL
+            );
+            "#,
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "login_nonces",
+        statements: &[r#"
+            CREATE TABLE IF NOT EXISTS login_nonces (
+                nonce TEXT PRIMARY KEY,
+                expires_at TIMESTAMPTZ NOT NULL
+            );
+            "#],
+    },
+    Migration {
+        version: 7,
+        name: "mint_request_pending_transfer",
+        statements: &[r#"
+            ALTER TABLE mint_requests
+                ADD COLUMN IF NOT EXISTS sequence_number BIGINT NULL,
+                ADD COLUMN IF NOT EXISTS tx_expires_at TIMESTAMPTZ NULL;
+            "#],
+    },
+    Migration {
+        version: 8,
+        name: "mint_request_quota_day",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS quota_day DATE NULL;
+            "#],
+    },
+    Migration {
+        version: 9,
+        name: "rate_limit_resets",
+        statements: &[r#"
+            CREATE TABLE IF NOT EXISTS rate_limit_resets (
+                account_id UUID PRIMARY KEY,
+                reset_at TIMESTAMPTZ NOT NULL
+            );
+            "#],
+    },
+    Migration {
+        version: 10,
+        name: "mint_request_http_request_id",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN IF NOT EXISTS http_request_id TEXT NULL;
+            "#],
+    },
+];
+
+#[derive(Clone)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// 连接并执行未应用的迁移
+    pub async fn connect(url: &str) -> Result<Self> {
+        let store = Self::open(url).await?;
+        store.migrate().await?;
+        info!("postgres schema ready");
+        Ok(store)
+    }
+
+    /// 只建立连接池，不检查 schema
+    pub async fn open(url: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect(url)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    /// 待执行的迁移，不修改数据库
+    pub async fn pending_migrations(&self) -> Result<Vec<MigrationStep>> {
+        let applied = Self::applied_versions(&self.pool).await?;
+        Ok(MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(Migration::step)
+            .collect())
+    }
+
+    /// 按版本号依次执行未应用的迁移，返回本次执行的步骤。
+    ///
+    /// 所有步骤在同一事务中执行并持有咨询锁，多个副本同时启动时只有一个会真正执行。
+    pub async fn migrate(&self) -> Result<Vec<MigrationStep>> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version BIGINT PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
 
-        for statement in statements {
-            sqlx::query(statement).execute(pool).await?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(MIGRATION_LOCK_ID)
+            .execute(&mut *tx)
+            .await?;
+        let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_migrations")
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut steps = Vec::new();
+        for migration in MIGRATIONS {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+            for statement in migration.statements {
+                sqlx::query(statement).execute(&mut *tx).await?;
+            }
+            sqlx::query("INSERT INTO schema_migrations (version, name) VALUES ($1, $2)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .execute(&mut *tx)
+                .await?;
+            info!(version = migration.version, name = migration.name, "postgres_migration_applied");
+            steps.push(migration.step());
         }
+        tx.commit().await?;
+        Ok(steps)
+    }
 
-        Ok(())
+    async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT to_regclass('schema_migrations') IS NOT NULL")
+                .fetch_one(pool)
+                .await?;
+        if !exists {
+            return Ok(Vec::new());
+        }
+        Ok(sqlx::query_scalar("SELECT version FROM schema_migrations")
+            .fetch_all(pool)
+            .await?)
     }
 
     fn map_user(row: &sqlx::postgres::PgRow) -> Result<User> {
         Ok(User {
             id: row.try_get("id")?,
+            account_id: row.try_get("account_id")?,
             channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
             handle: row.try_get("handle")?,
             role: role_from_db(row.try_get::<&str, _>("role")?)?,
@@ -113,10 +363,16 @@ impl PostgresStore {
     }
 
     fn map_request(row: &sqlx::postgres::PgRow) -> Result<MintRequest> {
+        let user_id = row.try_get("user_id")?;
         Ok(MintRequest {
             id: row.try_get("id")?,
-            user_id: row.try_get("user_id")?,
+            user_id,
+            account_id: row.try_get::<Option<Uuid>, _>("account_id")?.unwrap_or(user_id),
             channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
+            recipient: row
+                .try_get::<Option<&str>, _>("recipient")?
+                .map(address_from_db)
+                .transpose()?,
             amount: row.try_get::<i64, _>("amount")? as u64,
             status: status_from_db(row.try_get::<&str, _>("status")?)?,
             tx_hash: row.try_get("tx_hash").ok(),
@@ -124,6 +380,14 @@ impl PostgresStore {
             requested_at: row.try_get("requested_at")?,
             processed_at: row.try_get("processed_at").ok(),
             attempt: row.try_get::<i32, _>("attempt")? as u16,
+            available_at: row.try_get("available_at").ok(),
+            traceparent: row.try_get("traceparent")?,
+            http_request_id: row.try_get("http_request_id")?,
+            sequence_number: row
+                .try_get::<Option<i64>, _>("sequence_number")?
+                .map(|value| value as u64),
+            tx_expires_at: row.try_get("tx_expires_at")?,
+            quota_day: row.try_get("quota_day")?,
         })
     }
 
@@ -150,11 +414,23 @@ impl PostgresStore {
 #[async_trait::async_trait]
 impl UserRepository for PostgresStore {
     async fn upsert_user(&self, user: &User) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
             r#"
-            INSERT INTO users (id, channel, handle, role, domain, last_seen_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO accounts (id, role) VALUES ($1, $2)
+            ON CONFLICT (id) DO NOTHING;
+            "#,
+        )
+        .bind(user.account_id)
+        .bind(user.role.as_str())
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, account_id, channel, handle, role, domain, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             ON CONFLICT (id) DO UPDATE SET
+                account_id = EXCLUDED.account_id,
                 channel = EXCLUDED.channel,
                 handle = EXCLUDED.handle,
                 role = EXCLUDED.role,
@@ -163,20 +439,27 @@ impl UserRepository for PostgresStore {
             "#,
         )
         .bind(user.id)
+        .bind(user.account_id)
         .bind(user.channel.as_str())
         .bind(&user.handle)
         .bind(user.role.as_str())
         .bind(&user.domain)
         .bind(user.last_seen_at)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        tx.commit().await?;
         Ok(())
     }
 
     async fn find_user(&self, channel: &str, handle: &str) -> Result<Option<User>> {
+        // 角色以账户为准，users.role 仅为兼容旧数据保留
         let row = sqlx::query(
             r#"
-            SELECT * FROM users WHERE channel = $1 AND handle = $2 LIMIT 1
+            SELECT u.id, u.account_id, u.channel, u.handle, a.role, u.domain, u.last_seen_at
+            FROM users u
+            JOIN accounts a ON a.id = u.account_id
+            WHERE u.channel = $1 AND u.handle = $2
+            LIMIT 1
             "#,
         )
         .bind(channel)
@@ -187,14 +470,170 @@ impl UserRepository for PostgresStore {
         row.map(|r| Self::map_user(&r)).transpose()
     }
 
-    async fn set_role(&self, user_id: Uuid, role: Role) -> Result<()> {
-        sqlx::query(r#"UPDATE users SET role = $2 WHERE id = $1"#)
-            .bind(user_id)
+    async fn set_role(&self, account_id: Uuid, role: Role) -> Result<()> {
+        sqlx::query(r#"UPDATE accounts SET role = $2 WHERE id = $1"#)
+            .bind(account_id)
             .bind(role.as_str())
             .execute(&self.pool)
             .await?;
         Ok(())
     }
+
+    async fn link_user(&self, user_id: Uuid, account_id: Uuid) -> Result<()> {
+        sqlx::query(r#"UPDATE users SET account_id = $2 WHERE id = $1"#)
+            .bind(user_id)
+            .bind(account_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn rename_user(&self, user_id: Uuid, handle: &str) -> Result<()> {
+        sqlx::query(r#"UPDATE users SET handle = $2 WHERE id = $1"#)
+            .bind(user_id)
+            .bind(handle)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_link_code(&self, code: &LinkCode) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO link_codes (code, account_id, expires_at) VALUES ($1, $2, $3)
+            "#,
+        )
+        .bind(&code.code)
+        .bind(code.account_id)
+        .bind(code.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn consume_link_code(&self, code: &str, now: DateTime<Utc>) -> Result<Option<LinkCode>> {
+        let row = sqlx::query(
+            r#"
+            DELETE FROM link_codes WHERE code = $1
+            RETURNING code, account_id, expires_at
+            "#,
+        )
+        .bind(code)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let link = LinkCode {
+            code: row.try_get("code")?,
+            account_id: row.try_get("account_id")?,
+            expires_at: row.try_get("expires_at")?,
+        };
+        Ok((link.expires_at > now).then_some(link))
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionRepository for PostgresStore {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<()> {
+        sqlx::query(r#"DELETE FROM refresh_tokens WHERE expires_at <= NOW()"#)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, session_id, channel, handle, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(token.session_id)
+        .bind(token.channel.as_str())
+        .bind(&token.handle)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>> {
+        let row = sqlx::query(
+            r#"
+            DELETE FROM refresh_tokens WHERE token_hash = $1
+            RETURNING token_hash, session_id, channel, handle, expires_at
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let token = RefreshToken {
+            token_hash: row.try_get("token_hash")?,
+            session_id: row.try_get("session_id")?,
+            channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
+            handle: row.try_get("handle")?,
+            expires_at: row.try_get("expires_at")?,
+        };
+        Ok((token.expires_at > now).then_some(token))
+    }
+
+    async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        sqlx::query(r#"DELETE FROM refresh_tokens WHERE session_id = $1"#)
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(r#"DELETE FROM revoked_tokens WHERE expires_at <= NOW()"#)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2)
+            ON CONFLICT (jti) DO UPDATE SET expires_at = GREATEST(revoked_tokens.expires_at, EXCLUDED.expires_at)
+            "#,
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str, now: DateTime<Utc>) -> Result<bool> {
+        let revoked: Option<bool> = sqlx::query_scalar(
+            r#"SELECT TRUE FROM revoked_tokens WHERE jti = $1 AND expires_at > $2"#,
+        )
+        .bind(jti)
+        .bind(now)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(revoked.is_some())
+    }
+
+    async fn use_login_nonce(&self, nonce: &LoginNonce, now: DateTime<Utc>) -> Result<bool> {
+        sqlx::query(r#"DELETE FROM login_nonces WHERE expires_at <= $1"#)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query(
+            r#"INSERT INTO login_nonces (nonce, expires_at) VALUES ($1, $2) ON CONFLICT (nonce) DO NOTHING"#,
+        )
+        .bind(&nonce.nonce)
+        .bind(nonce.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
 }
 
 #[async_trait::async_trait]
@@ -202,58 +641,77 @@ impl MintRepository for PostgresStore {
     async fn enqueue(&self, request: &MintRequest) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO mint_requests (id, user_id, channel, amount, status, tx_hash, error, requested_at, processed_at, attempt)
-            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10)
+            INSERT INTO mint_requests (id, user_id, channel, amount, status, tx_hash, error, requested_at, processed_at, attempt, recipient, available_at, account_id, traceparent, sequence_number, tx_expires_at, quota_day, http_request_id)
+            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12,$13,$14,$15,$16,$17,$18)
             ON CONFLICT (id) DO UPDATE SET
                 channel = EXCLUDED.channel,
+                recipient = EXCLUDED.recipient,
                 amount = EXCLUDED.amount,
                 status = EXCLUDED.status,
                 tx_hash = EXCLUDED.tx_hash,
                 error = EXCLUDED.error,
                 requested_at = EXCLUDED.requested_at,
                 processed_at = EXCLUDED.processed_at,
-                attempt = EXCLUDED.attempt;
+                attempt = EXCLUDED.attempt,
+                available_at = EXCLUDED.available_at,
+                traceparent = EXCLUDED.traceparent,
+                sequence_number = EXCLUDED.sequence_number,
+                tx_expires_at = EXCLUDED.tx_expires_at,
+                quota_day = EXCLUDED.quota_day,
+                http_request_id = EXCLUDED.http_request_id;
             "#,
         )
         .bind(request.id)
         .bind(request.user_id)
         .bind(request.channel.as_str())
         .bind(request.amount as i64)
-        .bind(request.status.as_str())
+        .bind(MintStatus::Pending.as_str())
         .bind(&request.tx_hash)
         .bind(&request.error)
         .bind(request.requested_at)
         .bind(request.processed_at)
         .bind(request.attempt as i32)
+        .bind(request.recipient.map(|address| address.to_string()))
+        .bind(request.available_at)
+        .bind(request.account_id)
+        .bind(&request.traceparent)
+        .bind(request.sequence_number.map(|value| value as i64))
+        .bind(request.tx_expires_at)
+        .bind(request.quota_day)
+        .bind(&request.http_request_id)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    async fn next_pending(&self) -> Result<Option<MintRequest>> {
+    async fn next_pending(&self, lease: std::time::Duration) -> Result<Option<MintRequest>> {
+        let now = Utc::now();
         let mut tx = self.pool.begin().await?;
         let row = sqlx::query(
             r#"
             SELECT * FROM mint_requests
-            WHERE status = 'pending'
+            WHERE (status = 'pending' AND (available_at IS NULL OR available_at <= $1))
+               OR (status = 'processing' AND available_at <= $1)
             ORDER BY requested_at ASC
             FOR UPDATE SKIP LOCKED
             LIMIT 1
             "#,
         )
+        .bind(now)
         .fetch_optional(&mut *tx)
         .await?;
 
         if let Some(row) = row {
             let mut request = Self::map_request(&row)?;
             request.status = MintStatus::Processing;
-            request.processed_at = Some(Utc::now());
+            request.processed_at = Some(now);
             request.attempt += 1;
+            request.available_at = Some(now + Duration::from_std(lease)?);
 
             sqlx::query(
                 r#"
                 UPDATE mint_requests
-                SET status = $2, processed_at = $3, attempt = $4
+                SET status = $2, processed_at = $3, attempt = $4, available_at = $5
                 WHERE id = $1
                 "#,
             )
@@ -261,6 +719,7 @@ impl MintRepository for PostgresStore {
             .bind(request.status.as_str())
             .bind(request.processed_at)
             .bind(request.attempt as i32)
+            .bind(request.available_at)
             .execute(&mut *tx)
             .await?;
 
@@ -294,16 +753,39 @@ impl MintRepository for PostgresStore {
         Ok(())
     }
 
-    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<()> {
-        sqlx::query(
+    async fn record_submission(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        transfer: &PendingTransfer,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET tx_hash = $3, sequence_number = $4, tx_expires_at = $5
+            WHERE id = $1 AND attempt = $2 AND status = 'processing'
+            "#,
+        )
+        .bind(request_id)
+        .bind(attempt as i32)
+        .bind(&transfer.hash)
+        .bind(transfer.sequence_number as i64)
+        .bind(transfer.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
             r#"
             UPDATE mint_requests
             SET status = $2,
                 tx_hash = $3,
                 error = $4,
-                processed_at = $5,
-                attempt = $6
-            WHERE id = $1
+                processed_at = $5
+            WHERE id = $1 AND attempt = $6
             "#,
         )
         .bind(outcome.request.id)
@@ -312,40 +794,160 @@ impl MintRepository for PostgresStore {
         .bind(&outcome.request.error)
         .bind(outcome.request.processed_at)
         .bind(outcome.request.attempt as i32)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await.ok();
+            return Ok(false);
+        }
 
         if outcome.request.status == MintStatus::Completed {
             sqlx::query(
                 r#"
-                UPDATE quotas
-                SET success_count = success_count + 1
-                WHERE user_id = $1 AND day = $2
+                INSERT INTO quotas (id, user_id, day, minted_total, success_count)
+                VALUES ($3, $1, $2, 0, 1)
+                ON CONFLICT (user_id, day) DO UPDATE SET
+                    success_count = quotas.success_count + 1
                 "#,
             )
-            .bind(outcome.request.user_id)
-            .bind(outcome.request.requested_at.date_naive())
-            .execute(&self.pool)
+            .bind(outcome.request.account_id)
+            .bind(outcome.request.charged_day())
+            .bind(Uuid::new_v4())
+            .execute(&mut *tx)
             .await?;
         }
 
-        Ok(())
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn schedule_retry(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        error: &str,
+        available_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET status = 'pending', error = $3, available_at = $4
+            WHERE id = $1 AND attempt = $2
+            "#,
+        )
+        .bind(request_id)
+        .bind(attempt as i32)
+        .bind(error)
+        .bind(available_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn find_request(&self, request_id: Uuid) -> Result<Option<MintRequest>> {
+        let row = sqlx::query(r#"SELECT * FROM mint_requests WHERE id = $1"#)
+            .bind(request_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| Self::map_request(&r)).transpose()
+    }
+
+    async fn list_requests_for_account(
+        &self,
+        account_id: Uuid,
+        before: Option<MintCursor>,
+        limit: usize,
+    ) -> Result<Vec<MintRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM mint_requests
+            WHERE account_id = $1
+              AND ($2::timestamptz IS NULL OR (requested_at, id) < ($2, $3))
+            ORDER BY requested_at DESC, id DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(account_id)
+        .bind(before.map(|cursor| cursor.requested_at))
+        .bind(before.map(|cursor| cursor.id))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Self::map_request(&row))
+            .collect()
+    }
+
+    async fn recent_mints(&self, account_id: Uuid, since: DateTime<Utc>) -> Result<Vec<MintRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM mint_requests
+            WHERE account_id = $1 AND requested_at >= $2 AND status <> $3
+            ORDER BY requested_at ASC
+            "#,
+        )
+        .bind(account_id)
+        .bind(since)
+        .bind(MintStatus::Failed.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Self::map_request(&row))
+            .collect()
+    }
+
+    async fn pending_count(&self) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM mint_requests WHERE status = $1")
+            .bind(MintStatus::Pending.as_str())
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as u64)
     }
 }
 
 #[async_trait::async_trait]
 impl QuotaRepository for PostgresStore {
-    async fn record_mint(&self, user_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
-        sqlx::query(
+    async fn record_mint(
+        &self,
+        account_id: Uuid,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        // 冲突时行锁保证并发请求串行判断上限，未返回行即表示超出
+        let row = sqlx::query(
             r#"
             INSERT INTO quotas (id, user_id, day, minted_total, success_count)
-            VALUES ($1, $2, $3, $4, 0)
+            SELECT $1, $2, $3, $4, 0
+            WHERE $5::BIGINT IS NULL OR $4 <= $5
             ON CONFLICT (user_id, day) DO UPDATE SET
                 minted_total = quotas.minted_total + EXCLUDED.minted_total
+            WHERE $5::BIGINT IS NULL OR quotas.minted_total + EXCLUDED.minted_total <= $5
+            RETURNING minted_total
             "#,
         )
         .bind(Uuid::new_v4())
-        .bind(user_id)
+        .bind(account_id)
+        .bind(day)
+        .bind(amount as i64)
+        .bind(cap.map(|cap| cap as i64))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+
+    async fn release_mint(&self, account_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE quotas
+            SET minted_total = GREATEST(minted_total - $3, 0)
+            WHERE user_id = $1 AND day = $2
+            "#,
+        )
+        .bind(account_id)
         .bind(day)
         .bind(amount as i64)
         .execute(&self.pool)
@@ -353,32 +955,103 @@ impl QuotaRepository for PostgresStore {
         Ok(())
     }
 
-    async fn fetch_quota(&self, user_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
+    async fn fetch_quota(&self, account_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
         let row = sqlx::query(r#"SELECT * FROM quotas WHERE user_id = $1 AND day = $2"#)
-            .bind(user_id)
+            .bind(account_id)
             .bind(day)
             .fetch_optional(&self.pool)
             .await?;
 
         row.map(|r| Self::map_quota(&r)).transpose()
     }
+
+    async fn record_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO address_quotas (address, day, minted_total)
+            SELECT $1, $2, $3
+            WHERE $4::BIGINT IS NULL OR $3 <= $4
+            ON CONFLICT (address, day) DO UPDATE SET
+                minted_total = address_quotas.minted_total + EXCLUDED.minted_total
+            WHERE $4::BIGINT IS NULL OR address_quotas.minted_total + EXCLUDED.minted_total <= $4
+            RETURNING minted_total
+            "#,
+        )
+        .bind(address.to_string())
+        .bind(day)
+        .bind(amount as i64)
+        .bind(cap.map(|cap| cap as i64))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.is_some())
+    }
+
+    async fn release_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE address_quotas
+            SET minted_total = GREATEST(minted_total - $3, 0)
+            WHERE address = $1 AND day = $2
+            "#,
+        )
+        .bind(address.to_string())
+        .bind(day)
+        .bind(amount as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_limits_reset(&self, account_id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO rate_limit_resets (account_id, reset_at) VALUES ($1, $2)
+            ON CONFLICT (account_id) DO UPDATE SET reset_at = EXCLUDED.reset_at
+            "#,
+        )
+        .bind(account_id)
+        .bind(at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn limits_reset_at(&self, account_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let row = sqlx::query("SELECT reset_at FROM rate_limit_resets WHERE account_id = $1")
+            .bind(account_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|r| r.try_get("reset_at")).transpose()?)
+    }
 }
 
 #[async_trait::async_trait]
 impl ReportingRepository for PostgresStore {
     async fn daily_summary(&self, day: NaiveDate) -> Result<Vec<DailyReportRow>> {
-        let start = DateTime::<Utc>::from_utc(day.and_hms_opt(0, 0, 0).unwrap(), Utc);
+        let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
         let end_date = day + Duration::days(1);
-        let end = DateTime::<Utc>::from_utc(end_date.and_hms_opt(0, 0, 0).unwrap(), Utc);
+        let end = end_date.and_hms_opt(0, 0, 0).unwrap().and_utc();
         let rows = sqlx::query(
             r#"
             SELECT channel,
-                   COALESCE(SUM(amount),0) AS total_amount,
+                   COALESCE(SUM(amount), 0)::BIGINT AS total_amount,
                    SUM(CASE WHEN status = 'completed' THEN 1 ELSE 0 END) AS success_count,
                    SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failure_count
             FROM mint_requests
             WHERE requested_at >= $1 AND requested_at < $2
             GROUP BY channel
+            ORDER BY channel
             "#,
         )
         .bind(start)
@@ -483,24 +1156,233 @@ impl crate::repository::ConfigRepository for PostgresStore {
         if let Some(cap) = config.privileged_daily_cap {
             self.set_config("limits.privileged_daily_cap", &cap.to_string(), Some("Privileged user daily cap")).await?;
         }
+        if let Some(cap) = config.address_daily_cap {
+            self.set_config("limits.address_daily_cap", &cap.to_string(), Some("Per-address daily cap")).await?;
+        }
         Ok(())
     }
 
     async fn get_limit_config(&self) -> Result<Option<crate::models::LimitConfigUpdate>> {
-        let default_amount = self.get_config("limits.default_amount").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let privileged_amount = self.get_config("limits.privileged_amount").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
-        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.map(|c| c.value.parse::<u64>().ok()).flatten();
+        let default_amount = self.get_config("limits.default_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_amount = self.get_config("limits.privileged_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let address_daily_cap = self.get_config("limits.address_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
 
-        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() {
+        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() || address_daily_cap.is_some() {
             Ok(Some(crate::models::LimitConfigUpdate {
                 default_amount,
                 default_daily_cap,
                 privileged_amount,
                 privileged_daily_cap,
+                address_daily_cap,
             }))
         } else {
             Ok(None)
         }
     }
 }
+
+#[async_trait::async_trait]
+impl TransferRepository for PostgresStore {
+    fn export_users(&self) -> BoxStream<'_, Result<User>> {
+        sqlx::query(
+            r#"
+            SELECT u.id, u.account_id, u.channel, u.handle, a.role, u.domain, u.last_seen_at
+            FROM users u
+            JOIN accounts a ON a.id = u.account_id
+            "#,
+        )
+        .fetch(&self.pool)
+        .map(|row| Self::map_user(&row?))
+        .boxed()
+    }
+
+    fn export_requests(&self) -> BoxStream<'_, Result<MintRequest>> {
+        sqlx::query("SELECT * FROM mint_requests")
+            .fetch(&self.pool)
+            .map(|row| Self::map_request(&row?))
+            .boxed()
+    }
+
+    fn export_quotas(&self) -> BoxStream<'_, Result<Quota>> {
+        sqlx::query("SELECT * FROM quotas")
+            .fetch(&self.pool)
+            .map(|row| Self::map_quota(&row?))
+            .boxed()
+    }
+
+    fn export_address_quotas(&self) -> BoxStream<'_, Result<AddressQuota>> {
+        sqlx::query("SELECT address, day, minted_total FROM address_quotas")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(AddressQuota {
+                    address: address_from_db(row.try_get("address")?)?,
+                    day: row.try_get("day")?,
+                    minted_total: row.try_get::<i64, _>("minted_total")? as u64,
+                })
+            })
+            .boxed()
+    }
+
+    fn export_failures(&self) -> BoxStream<'_, Result<MintFailure>> {
+        sqlx::query("SELECT id, request_id, failed_at, reason FROM mint_failures")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(MintFailure {
+                    id: row.try_get("id")?,
+                    request_id: row.try_get("request_id")?,
+                    failed_at: row.try_get("failed_at")?,
+                    reason: row.try_get("reason")?,
+                })
+            })
+            .boxed()
+    }
+
+    fn export_configs(&self) -> BoxStream<'_, Result<SystemConfig>> {
+        sqlx::query("SELECT id, key, value, description, created_at, updated_at FROM system_configs")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(SystemConfig {
+                    id: row.try_get("id")?,
+                    key: row.try_get("key")?,
+                    value: row.try_get("value")?,
+                    description: row.try_get("description")?,
+                    created_at: row.try_get("created_at")?,
+                    updated_at: row.try_get("updated_at")?,
+                })
+            })
+            .boxed()
+    }
+
+    async fn import_user(&self, user: &User) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("INSERT INTO accounts (id, role) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING")
+            .bind(user.account_id)
+            .bind(user.role.as_str())
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query(
+            r#"
+            INSERT INTO users (id, account_id, channel, handle, role, domain, last_seen_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(user.id)
+        .bind(user.account_id)
+        .bind(user.channel.as_str())
+        .bind(&user.handle)
+        .bind(user.role.as_str())
+        .bind(&user.domain)
+        .bind(user.last_seen_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_request(&self, request: &MintRequest) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO mint_requests (id, user_id, account_id, channel, recipient, amount, status, tx_hash, error, requested_at, processed_at, attempt, available_at, traceparent, sequence_number, tx_expires_at, quota_day, http_request_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(request.id)
+        .bind(request.user_id)
+        .bind(request.account_id)
+        .bind(request.channel.as_str())
+        .bind(request.recipient.map(|address| address.to_string()))
+        .bind(request.amount as i64)
+        .bind(request.status.as_str())
+        .bind(&request.tx_hash)
+        .bind(&request.error)
+        .bind(request.requested_at)
+        .bind(request.processed_at)
+        .bind(request.attempt as i32)
+        .bind(request.available_at)
+        .bind(&request.traceparent)
+        .bind(request.sequence_number.map(|value| value as i64))
+        .bind(request.tx_expires_at)
+        .bind(request.quota_day)
+        .bind(&request.http_request_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_quota(&self, quota: &Quota) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO quotas (id, user_id, day, minted_total, success_count)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(quota.id)
+        .bind(quota.user_id)
+        .bind(quota.day)
+        .bind(quota.minted_total as i64)
+        .bind(quota.success_count as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_address_quota(&self, quota: &AddressQuota) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO address_quotas (address, day, minted_total)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(quota.address.to_string())
+        .bind(quota.day)
+        .bind(quota.minted_total as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_failure(&self, failure: &MintFailure) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO mint_failures (id, request_id, failed_at, reason)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(failure.id)
+        .bind(failure.request_id)
+        .bind(failure.failed_at)
+        .bind(&failure.reason)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_config(&self, config: &SystemConfig) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO system_configs (id, key, value, description, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(config.id)
+        .bind(&config.key)
+        .bind(&config.value)
+        .bind(&config.description)
+        .bind(config.created_at)
+        .bind(config.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,1337 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDate, SecondsFormat, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+    Row, SqlitePool,
+};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    db::MigrationStep,
+    models::{
+        address_from_db, channel_from_db, role_from_db, status_from_db, AccountAddress,
+        AddressQuota, LimitConfigUpdate, LinkCode, MintFailure, MintOutcome, MintRequest,
+        LoginNonce, MintStatus, PendingTransfer, Quota, RefreshToken, Role, SystemConfig, User,
+    },
+    repository::{
+        ConfigRepository, DailyReportRow, MintCursor, MintRepository, QuotaRepository,
+        ReportingRepository, SessionRepository, TransferRepository, UserRepository,
+    },
+};
+
+const MAX_CONNECTIONS: u32 = 4;
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    statements: &'static [&'static str],
+}
+
+impl Migration {
+    fn step(&self) -> MigrationStep {
+        MigrationStep {
+            version: self.version,
+            name: self.name,
+            statements: self
+                .statements
+                .iter()
+                .map(|statement| statement.trim().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// 按版本号排列的 schema 迁移，已发布的版本只能追加。
+///
+/// SQLite 没有原生的 UUID 与时间类型：id 以文本保存，时间统一为微秒精度的 RFC 3339
+/// 文本（见 [`timestamp`]），定长格式保证按字符串比较即按时间比较。
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        statements: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS accounts (
+                id TEXT PRIMARY KEY,
+                role TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL REFERENCES accounts(id),
+                channel TEXT NOT NULL,
+                handle TEXT NOT NULL,
+                role TEXT NOT NULL,
+                domain TEXT NULL,
+                last_seen_at TEXT NOT NULL,
+                UNIQUE(channel, handle)
+            );
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS users_account_idx ON users(account_id);
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS link_codes (
+                code TEXT PRIMARY KEY,
+                account_id TEXT NOT NULL REFERENCES accounts(id),
+                expires_at TEXT NOT NULL
+            );
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS mint_requests (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                account_id TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                recipient TEXT NULL,
+                amount INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                tx_hash TEXT NULL,
+                error TEXT NULL,
+                requested_at TEXT NOT NULL,
+                processed_at TEXT NULL,
+                attempt INTEGER NOT NULL DEFAULT 0,
+                available_at TEXT NULL
+            );
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_status_idx ON mint_requests(status, available_at);
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_user_idx ON mint_requests(user_id, requested_at DESC, id DESC);
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_account_idx ON mint_requests(account_id, requested_at);
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS mint_requests_requested_idx ON mint_requests(requested_at);
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS quotas (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                day TEXT NOT NULL,
+                minted_total INTEGER NOT NULL,
+                success_count INTEGER NOT NULL,
+                UNIQUE(user_id, day)
+            );
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS address_quotas (
+                address TEXT NOT NULL,
+                day TEXT NOT NULL,
+                minted_total INTEGER NOT NULL,
+                PRIMARY KEY(address, day)
+            );
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS mint_failures (
+                id TEXT PRIMARY KEY,
+                request_id TEXT NOT NULL REFERENCES mint_requests(id),
+                failed_at TEXT NOT NULL,
+                reason TEXT NOT NULL
+            );
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS system_configs (
+                id TEXT PRIMARY KEY,
+                key TEXT UNIQUE NOT NULL,
+                value TEXT NOT NULL,
+                description TEXT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            "#,
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "mint_request_traceparent",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN traceparent TEXT NULL;
+            "#],
+    },
+    Migration {
+        version: 3,
+        name: "sessions",
+        statements: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS refresh_tokens (
+                token_hash TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                channel TEXT NOT NULL,
+                handle TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            );
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS refresh_tokens_session_idx ON refresh_tokens(session_id);
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS revoked_tokens (
+                jti TEXT PRIMARY KEY,
+                expires_at TEXT NOT NULL
+            );
+            "#,
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "login_nonces",
+        statements: &[r#"
+            CREATE TABLE IF NOT EXISTS login_nonces (
+                nonce TEXT PRIMARY KEY,
+                expires_at TEXT NOT NULL
+            );
+            "#],
+    },
+    Migration {
+        version: 5,
+        name: "mint_request_pending_transfer",
+        statements: &[
+            r#"
+            ALTER TABLE mint_requests ADD COLUMN sequence_number INTEGER NULL;
+            "#,
+            r#"
+            ALTER TABLE mint_requests ADD COLUMN tx_expires_at TEXT NULL;
+            "#,
+        ],
+    },
+    Migration {
+        version: 6,
+        name: "mint_request_quota_day",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN quota_day TEXT NULL;
+            "#],
+    },
+    Migration {
+        version: 7,
+        name: "rate_limit_resets",
+        statements: &[r#"
+            CREATE TABLE IF NOT EXISTS rate_limit_resets (
+                account_id TEXT PRIMARY KEY,
+                reset_at TEXT NOT NULL
+            );
+            "#],
+    },
+    Migration {
+        version: 8,
+        name: "mint_request_http_request_id",
+        statements: &[r#"
+            ALTER TABLE mint_requests ADD COLUMN http_request_id TEXT NULL;
+            "#],
+    },
+];
+
+/// 单文件持久化存储，适合小规模部署、本地开发与不依赖外部服务的集成测试。
+#[derive(Clone)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    /// 打开（必要时创建）数据库文件并执行未应用的迁移
+    pub async fn connect(path: &str) -> Result<Self> {
+        let store = Self::open(path).await?;
+        store.migrate().await?;
+        info!(path, "sqlite schema ready");
+        Ok(store)
+    }
+
+    /// 只打开数据库文件，不检查 schema
+    pub async fn open(path: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::from_str(path)?
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(MAX_CONNECTIONS)
+            .connect_with(options)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    /// 待执行的迁移，不修改数据库
+    pub async fn pending_migrations(&self) -> Result<Vec<MigrationStep>> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'schema_migrations'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let applied: Vec<i64> = if exists {
+            sqlx::query_scalar("SELECT version FROM schema_migrations")
+                .fetch_all(&self.pool)
+                .await?
+        } else {
+            Vec::new()
+        };
+        Ok(MIGRATIONS
+            .iter()
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(Migration::step)
+            .collect())
+    }
+
+    /// 按版本号依次执行未应用的迁移，返回本次执行的步骤。
+    pub async fn migrate(&self) -> Result<Vec<MigrationStep>> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let mut tx = self.pool.begin().await?;
+        let applied: Vec<i64> = sqlx::query_scalar("SELECT version FROM schema_migrations")
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut steps = Vec::new();
+        for migration in MIGRATIONS {
+            if applied.contains(&migration.version) {
+                continue;
+            }
+            for statement in migration.statements {
+                sqlx::query(statement).execute(&mut *tx).await?;
+            }
+            sqlx::query("INSERT INTO schema_migrations (version, name, applied_at) VALUES (?, ?, ?)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(timestamp(Utc::now()))
+                .execute(&mut *tx)
+                .await?;
+            info!(version = migration.version, name = migration.name, "sqlite_migration_applied");
+            steps.push(migration.step());
+        }
+        tx.commit().await?;
+        Ok(steps)
+    }
+
+    fn map_user(row: &SqliteRow) -> Result<User> {
+        Ok(User {
+            id: uuid_column(row, "id")?,
+            account_id: uuid_column(row, "account_id")?,
+            channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
+            handle: row.try_get("handle")?,
+            role: role_from_db(row.try_get::<&str, _>("role")?)?,
+            domain: row.try_get("domain")?,
+            last_seen_at: timestamp_column(row, "last_seen_at")?,
+        })
+    }
+
+    fn map_request(row: &SqliteRow) -> Result<MintRequest> {
+        Ok(MintRequest {
+            id: uuid_column(row, "id")?,
+            user_id: uuid_column(row, "user_id")?,
+            account_id: uuid_column(row, "account_id")?,
+            channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
+            recipient: row
+                .try_get::<Option<&str>, _>("recipient")?
+                .map(address_from_db)
+                .transpose()?,
+            amount: row.try_get::<i64, _>("amount")? as u64,
+            status: status_from_db(row.try_get::<&str, _>("status")?)?,
+            tx_hash: row.try_get("tx_hash")?,
+            error: row.try_get("error")?,
+            requested_at: timestamp_column(row, "requested_at")?,
+            processed_at: optional_timestamp_column(row, "processed_at")?,
+            attempt: row.try_get::<i64, _>("attempt")? as u16,
+            available_at: optional_timestamp_column(row, "available_at")?,
+            traceparent: row.try_get("traceparent")?,
+            http_request_id: row.try_get("http_request_id")?,
+            sequence_number: row
+                .try_get::<Option<i64>, _>("sequence_number")?
+                .map(|value| value as u64),
+            tx_expires_at: optional_timestamp_column(row, "tx_expires_at")?,
+            quota_day: row
+                .try_get::<Option<&str>, _>("quota_day")?
+                .map(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d"))
+                .transpose()?,
+        })
+    }
+
+    fn map_quota(row: &SqliteRow) -> Result<Quota> {
+        Ok(Quota {
+            id: uuid_column(row, "id")?,
+            user_id: uuid_column(row, "user_id")?,
+            day: NaiveDate::parse_from_str(row.try_get("day")?, "%Y-%m-%d")?,
+            minted_total: row.try_get::<i64, _>("minted_total")? as u64,
+            success_count: row.try_get::<i64, _>("success_count")? as u64,
+        })
+    }
+
+    fn map_config(row: &SqliteRow) -> Result<SystemConfig> {
+        Ok(SystemConfig {
+            id: uuid_column(row, "id")?,
+            key: row.try_get("key")?,
+            value: row.try_get("value")?,
+            description: row.try_get("description")?,
+            created_at: timestamp_column(row, "created_at")?,
+            updated_at: timestamp_column(row, "updated_at")?,
+        })
+    }
+}
+
+/// 定长的微秒精度 UTC 时间文本，按字符串排序即按时间排序
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn timestamp_column(row: &SqliteRow, column: &str) -> Result<DateTime<Utc>> {
+    Ok(DateTime::parse_from_rfc3339(row.try_get(column)?)?.with_timezone(&Utc))
+}
+
+fn optional_timestamp_column(row: &SqliteRow, column: &str) -> Result<Option<DateTime<Utc>>> {
+    row.try_get::<Option<&str>, _>(column)?
+        .map(|value| Ok(DateTime::parse_from_rfc3339(value)?.with_timezone(&Utc)))
+        .transpose()
+}
+
+fn uuid_column(row: &SqliteRow, column: &str) -> Result<Uuid> {
+    Ok(Uuid::parse_str(row.try_get(column)?)?)
+}
+
+#[async_trait::async_trait]
+impl UserRepository for SqliteStore {
+    async fn upsert_user(&self, user: &User) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, role, created_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT (id) DO NOTHING;
+            "#,
+        )
+        .bind(user.account_id.to_string())
+        .bind(user.role.as_str())
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, account_id, channel, handle, role, domain, last_seen_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (id) DO UPDATE SET
+                account_id = excluded.account_id,
+                channel = excluded.channel,
+                handle = excluded.handle,
+                role = excluded.role,
+                domain = excluded.domain,
+                last_seen_at = excluded.last_seen_at;
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(user.account_id.to_string())
+        .bind(user.channel.as_str())
+        .bind(&user.handle)
+        .bind(user.role.as_str())
+        .bind(&user.domain)
+        .bind(timestamp(user.last_seen_at))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_user(&self, channel: &str, handle: &str) -> Result<Option<User>> {
+        let row = sqlx::query(
+            r#"
+            SELECT u.id, u.account_id, u.channel, u.handle, a.role, u.domain, u.last_seen_at
+            FROM users u
+            JOIN accounts a ON a.id = u.account_id
+            WHERE u.channel = ?1 AND u.handle = ?2
+            LIMIT 1
+            "#,
+        )
+        .bind(channel)
+        .bind(handle)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|r| Self::map_user(&r)).transpose()
+    }
+
+    async fn set_role(&self, account_id: Uuid, role: Role) -> Result<()> {
+        sqlx::query("UPDATE accounts SET role = ?2 WHERE id = ?1")
+            .bind(account_id.to_string())
+            .bind(role.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn link_user(&self, user_id: Uuid, account_id: Uuid) -> Result<()> {
+        sqlx::query("UPDATE users SET account_id = ?2 WHERE id = ?1")
+            .bind(user_id.to_string())
+            .bind(account_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn rename_user(&self, user_id: Uuid, handle: &str) -> Result<()> {
+        sqlx::query("UPDATE users SET handle = ?2 WHERE id = ?1")
+            .bind(user_id.to_string())
+            .bind(handle)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_link_code(&self, code: &LinkCode) -> Result<()> {
+        sqlx::query("INSERT INTO link_codes (code, account_id, expires_at) VALUES (?1, ?2, ?3)")
+            .bind(&code.code)
+            .bind(code.account_id.to_string())
+            .bind(timestamp(code.expires_at))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn consume_link_code(&self, code: &str, now: DateTime<Utc>) -> Result<Option<LinkCode>> {
+        let row = sqlx::query(
+            "DELETE FROM link_codes WHERE code = ?1 RETURNING code, account_id, expires_at",
+        )
+        .bind(code)
+        .fetch_all(&self.pool)
+        .await?
+        .pop();
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let link = LinkCode {
+            code: row.try_get("code")?,
+            account_id: uuid_column(&row, "account_id")?,
+            expires_at: timestamp_column(&row, "expires_at")?,
+        };
+        Ok((link.expires_at > now).then_some(link))
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionRepository for SqliteStore {
+    async fn create_refresh_token(&self, token: &RefreshToken) -> Result<()> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= ?1")
+            .bind(timestamp(Utc::now()))
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, session_id, channel, handle, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(&token.token_hash)
+        .bind(token.session_id.to_string())
+        .bind(token.channel.as_str())
+        .bind(&token.handle)
+        .bind(timestamp(token.expires_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn consume_refresh_token(
+        &self,
+        token_hash: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<RefreshToken>> {
+        let row = sqlx::query(
+            r#"
+            DELETE FROM refresh_tokens WHERE token_hash = ?1
+            RETURNING token_hash, session_id, channel, handle, expires_at
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_all(&self.pool)
+        .await?
+        .pop();
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let token = RefreshToken {
+            token_hash: row.try_get("token_hash")?,
+            session_id: uuid_column(&row, "session_id")?,
+            channel: channel_from_db(row.try_get::<&str, _>("channel")?)?,
+            handle: row.try_get("handle")?,
+            expires_at: timestamp_column(&row, "expires_at")?,
+        };
+        Ok((token.expires_at > now).then_some(token))
+    }
+
+    async fn revoke_session(&self, session_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE session_id = ?1")
+            .bind(session_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> Result<()> {
+        sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= ?1")
+            .bind(timestamp(Utc::now()))
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO revoked_tokens (jti, expires_at) VALUES (?1, ?2)
+            ON CONFLICT (jti) DO UPDATE SET expires_at = MAX(expires_at, excluded.expires_at)
+            "#,
+        )
+        .bind(jti)
+        .bind(timestamp(expires_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn is_token_revoked(&self, jti: &str, now: DateTime<Utc>) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM revoked_tokens WHERE jti = ?1 AND expires_at > ?2")
+            .bind(jti)
+            .bind(timestamp(now))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn use_login_nonce(&self, nonce: &LoginNonce, now: DateTime<Utc>) -> Result<bool> {
+        sqlx::query("DELETE FROM login_nonces WHERE expires_at <= ?1")
+            .bind(timestamp(now))
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query(
+            "INSERT INTO login_nonces (nonce, expires_at) VALUES (?1, ?2) ON CONFLICT (nonce) DO NOTHING",
+        )
+        .bind(&nonce.nonce)
+        .bind(timestamp(nonce.expires_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+#[async_trait::async_trait]
+impl MintRepository for SqliteStore {
+    async fn enqueue(&self, request: &MintRequest) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO mint_requests (id, user_id, account_id, channel, recipient, amount, status, tx_hash, error, requested_at, processed_at, attempt, available_at, traceparent, sequence_number, tx_expires_at, quota_day, http_request_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+            ON CONFLICT (id) DO UPDATE SET
+                channel = excluded.channel,
+                recipient = excluded.recipient,
+                amount = excluded.amount,
+                status = excluded.status,
+                tx_hash = excluded.tx_hash,
+                error = excluded.error,
+                requested_at = excluded.requested_at,
+                processed_at = excluded.processed_at,
+                attempt = excluded.attempt,
+                available_at = excluded.available_at,
+                traceparent = excluded.traceparent,
+                sequence_number = excluded.sequence_number,
+                tx_expires_at = excluded.tx_expires_at,
+                quota_day = excluded.quota_day,
+                http_request_id = excluded.http_request_id;
+            "#,
+        )
+        .bind(request.id.to_string())
+        .bind(request.user_id.to_string())
+        .bind(request.account_id.to_string())
+        .bind(request.channel.as_str())
+        .bind(request.recipient.map(|address| address.to_string()))
+        .bind(request.amount as i64)
+        .bind(MintStatus::Pending.as_str())
+        .bind(&request.tx_hash)
+        .bind(&request.error)
+        .bind(timestamp(request.requested_at))
+        .bind(request.processed_at.map(timestamp))
+        .bind(request.attempt as i64)
+        .bind(request.available_at.map(timestamp))
+        .bind(&request.traceparent)
+        .bind(request.sequence_number.map(|value| value as i64))
+        .bind(request.tx_expires_at.map(timestamp))
+        .bind(request.quota_day.map(|day| day.to_string()))
+        .bind(&request.http_request_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn next_pending(&self, lease: std::time::Duration) -> Result<Option<MintRequest>> {
+        // 单条 UPDATE ... RETURNING 完成挑选与领取，SQLite 的写锁保证不会重复领取。
+        // 带 RETURNING 的语句在被重置后才提交，因此这类语句一律用 `fetch_all`：
+        // `fetch_optional` 读到第一行就停下，写入对其他连接不可见。
+        let now = Utc::now();
+        let row = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET status = ?2, processed_at = ?1, attempt = attempt + 1, available_at = ?3
+            WHERE id = (
+                SELECT id FROM mint_requests
+                WHERE (status = 'pending' AND (available_at IS NULL OR available_at <= ?1))
+                   OR (status = 'processing' AND available_at <= ?1)
+                ORDER BY requested_at ASC
+                LIMIT 1
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(timestamp(now))
+        .bind(MintStatus::Processing.as_str())
+        .bind(timestamp(now + Duration::from_std(lease)?))
+        .fetch_all(&self.pool)
+        .await?
+        .pop();
+
+        row.map(|r| Self::map_request(&r)).transpose()
+    }
+
+    async fn update_status(&self, request_id: Uuid, status: MintStatus) -> Result<()> {
+        let processed_at = match status {
+            MintStatus::Completed | MintStatus::Failed => Some(timestamp(Utc::now())),
+            _ => None,
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET status = ?2,
+                processed_at = COALESCE(?3, processed_at)
+            WHERE id = ?1
+            "#,
+        )
+        .bind(request_id.to_string())
+        .bind(status.as_str())
+        .bind(processed_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn record_submission(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        transfer: &PendingTransfer,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET tx_hash = ?3, sequence_number = ?4, tx_expires_at = ?5
+            WHERE id = ?1 AND attempt = ?2 AND status = 'processing'
+            "#,
+        )
+        .bind(request_id.to_string())
+        .bind(attempt as i64)
+        .bind(&transfer.hash)
+        .bind(transfer.sequence_number as i64)
+        .bind(timestamp(transfer.expires_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn record_outcome(&self, outcome: &MintOutcome) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET status = ?2,
+                tx_hash = ?3,
+                error = ?4,
+                processed_at = ?5
+            WHERE id = ?1 AND attempt = ?6
+            "#,
+        )
+        .bind(outcome.request.id.to_string())
+        .bind(outcome.request.status.as_str())
+        .bind(&outcome.tx_hash)
+        .bind(&outcome.request.error)
+        .bind(outcome.request.processed_at.map(timestamp))
+        .bind(outcome.request.attempt as i64)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            tx.rollback().await.ok();
+            return Ok(false);
+        }
+
+        if outcome.request.status == MintStatus::Completed {
+            sqlx::query(
+                r#"
+                INSERT INTO quotas (id, user_id, day, minted_total, success_count)
+                VALUES (?3, ?1, ?2, 0, 1)
+                ON CONFLICT (user_id, day) DO UPDATE SET
+                    success_count = quotas.success_count + 1
+                "#,
+            )
+            .bind(outcome.request.account_id.to_string())
+            .bind(outcome.request.charged_day().to_string())
+            .bind(Uuid::new_v4().to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn schedule_retry(
+        &self,
+        request_id: Uuid,
+        attempt: u16,
+        error: &str,
+        available_at: DateTime<Utc>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE mint_requests
+            SET status = 'pending', error = ?3, available_at = ?4
+            WHERE id = ?1 AND attempt = ?2
+            "#,
+        )
+        .bind(request_id.to_string())
+        .bind(attempt as i64)
+        .bind(error)
+        .bind(timestamp(available_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn find_request(&self, request_id: Uuid) -> Result<Option<MintRequest>> {
+        let row = sqlx::query("SELECT * FROM mint_requests WHERE id = ?1")
+            .bind(request_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| Self::map_request(&r)).transpose()
+    }
+
+    async fn list_requests_for_account(
+        &self,
+        account_id: Uuid,
+        before: Option<MintCursor>,
+        limit: usize,
+    ) -> Result<Vec<MintRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM mint_requests
+            WHERE account_id = ?1
+              AND (?2 IS NULL OR requested_at < ?2 OR (requested_at = ?2 AND id < ?3))
+            ORDER BY requested_at DESC, id DESC
+            LIMIT ?4
+            "#,
+        )
+        .bind(account_id.to_string())
+        .bind(before.map(|cursor| timestamp(cursor.requested_at)))
+        .bind(before.map(|cursor| cursor.id.to_string()))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Self::map_request(&row))
+            .collect()
+    }
+
+    async fn recent_mints(
+        &self,
+        account_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<MintRequest>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM mint_requests
+            WHERE account_id = ?1 AND requested_at >= ?2 AND status <> ?3
+            ORDER BY requested_at ASC
+            "#,
+        )
+        .bind(account_id.to_string())
+        .bind(timestamp(since))
+        .bind(MintStatus::Failed.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Self::map_request(&row))
+            .collect()
+    }
+
+    async fn pending_count(&self) -> Result<u64> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM mint_requests WHERE status = ?1")
+            .bind(MintStatus::Pending.as_str())
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as u64)
+    }
+}
+
+#[async_trait::async_trait]
+impl QuotaRepository for SqliteStore {
+    async fn record_mint(
+        &self,
+        account_id: Uuid,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        // 单条语句在写锁内完成判断与累加，未返回行即表示超出
+        let row = sqlx::query(
+            r#"
+            INSERT INTO quotas (id, user_id, day, minted_total, success_count)
+            SELECT ?1, ?2, ?3, ?4, 0
+            WHERE ?5 IS NULL OR ?4 <= ?5
+            ON CONFLICT (user_id, day) DO UPDATE SET
+                minted_total = quotas.minted_total + excluded.minted_total
+            WHERE ?5 IS NULL OR quotas.minted_total + excluded.minted_total <= ?5
+            RETURNING minted_total
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(account_id.to_string())
+        .bind(day.to_string())
+        .bind(amount as i64)
+        .bind(cap.map(|cap| cap as i64))
+        .fetch_all(&self.pool)
+        .await?
+        .pop();
+        Ok(row.is_some())
+    }
+
+    async fn release_mint(&self, account_id: Uuid, day: NaiveDate, amount: u64) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE quotas
+            SET minted_total = MAX(minted_total - ?3, 0)
+            WHERE user_id = ?1 AND day = ?2
+            "#,
+        )
+        .bind(account_id.to_string())
+        .bind(day.to_string())
+        .bind(amount as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn fetch_quota(&self, account_id: Uuid, day: NaiveDate) -> Result<Option<Quota>> {
+        let row = sqlx::query("SELECT * FROM quotas WHERE user_id = ?1 AND day = ?2")
+            .bind(account_id.to_string())
+            .bind(day.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| Self::map_quota(&r)).transpose()
+    }
+
+    async fn record_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+        cap: Option<u64>,
+    ) -> Result<bool> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO address_quotas (address, day, minted_total)
+            SELECT ?1, ?2, ?3
+            WHERE ?4 IS NULL OR ?3 <= ?4
+            ON CONFLICT (address, day) DO UPDATE SET
+                minted_total = address_quotas.minted_total + excluded.minted_total
+            WHERE ?4 IS NULL OR address_quotas.minted_total + excluded.minted_total <= ?4
+            RETURNING minted_total
+            "#,
+        )
+        .bind(address.to_string())
+        .bind(day.to_string())
+        .bind(amount as i64)
+        .bind(cap.map(|cap| cap as i64))
+        .fetch_all(&self.pool)
+        .await?
+        .pop();
+        Ok(row.is_some())
+    }
+
+    async fn release_address_mint(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE address_quotas
+            SET minted_total = MAX(minted_total - ?3, 0)
+            WHERE address = ?1 AND day = ?2
+            "#,
+        )
+        .bind(address.to_string())
+        .bind(day.to_string())
+        .bind(amount as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_limits_reset(&self, account_id: Uuid, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO rate_limit_resets (account_id, reset_at) VALUES (?1, ?2)
+            ON CONFLICT (account_id) DO UPDATE SET reset_at = excluded.reset_at
+            "#,
+        )
+        .bind(account_id.to_string())
+        .bind(timestamp(at))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn limits_reset_at(&self, account_id: Uuid) -> Result<Option<DateTime<Utc>>> {
+        let row = sqlx::query("SELECT reset_at FROM rate_limit_resets WHERE account_id = ?1")
+            .bind(account_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        row.map(|r| timestamp_column(&r, "reset_at")).transpose()
+    }
+}
+
+#[async_trait::async_trait]
+impl ReportingRepository for SqliteStore {
+    async fn daily_summary(&self, day: NaiveDate) -> Result<Vec<DailyReportRow>> {
+        let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let end = start + Duration::days(1);
+        let rows = sqlx::query(
+            r#"
+            SELECT channel,
+                   COALESCE(SUM(amount), 0) AS total_amount,
+                   SUM(CASE WHEN status = 'completed' THEN 1 ELSE 0 END) AS success_count,
+                   SUM(CASE WHEN status = 'failed' THEN 1 ELSE 0 END) AS failure_count
+            FROM mint_requests
+            WHERE requested_at >= ?1 AND requested_at < ?2
+            GROUP BY channel
+            ORDER BY channel
+            "#,
+        )
+        .bind(timestamp(start))
+        .bind(timestamp(end))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(DailyReportRow {
+                    channel: row.try_get("channel")?,
+                    total_amount: row.try_get::<i64, _>("total_amount")? as u64,
+                    success_count: row.try_get::<i64, _>("success_count")? as u64,
+                    failure_count: row.try_get::<i64, _>("failure_count")? as u64,
+                })
+            })
+            .collect()
+    }
+
+    async fn log_failure(&self, request_id: Uuid, when: DateTime<Utc>, reason: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO mint_failures (id, request_id, failed_at, reason) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(request_id.to_string())
+        .bind(timestamp(when))
+        .bind(reason)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigRepository for SqliteStore {
+    async fn get_config(&self, key: &str) -> Result<Option<SystemConfig>> {
+        let row = sqlx::query("SELECT * FROM system_configs WHERE key = ?1")
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| Self::map_config(&r)).transpose()
+    }
+
+    async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> Result<()> {
+        let now = timestamp(Utc::now());
+        sqlx::query(
+            r#"
+            INSERT INTO system_configs (id, key, value, description, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+            ON CONFLICT (key) DO UPDATE SET
+                value = excluded.value,
+                description = excluded.description,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(key)
+        .bind(value)
+        .bind(description)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_all_configs(&self) -> Result<Vec<SystemConfig>> {
+        let rows = sqlx::query("SELECT * FROM system_configs ORDER BY key")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(|row| Self::map_config(&row)).collect()
+    }
+
+    async fn update_limit_config(&self, config: &LimitConfigUpdate) -> Result<()> {
+        if let Some(amount) = config.default_amount {
+            self.set_config("limits.default_amount", &amount.to_string(), Some("Default user amount")).await?;
+        }
+        if let Some(cap) = config.default_daily_cap {
+            self.set_config("limits.default_daily_cap", &cap.to_string(), Some("Default user daily cap")).await?;
+        }
+        if let Some(amount) = config.privileged_amount {
+            self.set_config("limits.privileged_amount", &amount.to_string(), Some("Privileged user amount")).await?;
+        }
+        if let Some(cap) = config.privileged_daily_cap {
+            self.set_config("limits.privileged_daily_cap", &cap.to_string(), Some("Privileged user daily cap")).await?;
+        }
+        if let Some(cap) = config.address_daily_cap {
+            self.set_config("limits.address_daily_cap", &cap.to_string(), Some("Per-address daily cap")).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_limit_config(&self) -> Result<Option<LimitConfigUpdate>> {
+        let default_amount = self.get_config("limits.default_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let default_daily_cap = self.get_config("limits.default_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_amount = self.get_config("limits.privileged_amount").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let privileged_daily_cap = self.get_config("limits.privileged_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+        let address_daily_cap = self.get_config("limits.address_daily_cap").await?.and_then(|c| c.value.parse::<u64>().ok());
+
+        if default_amount.is_some() || default_daily_cap.is_some() || privileged_amount.is_some() || privileged_daily_cap.is_some() || address_daily_cap.is_some() {
+            Ok(Some(LimitConfigUpdate {
+                default_amount,
+                default_daily_cap,
+                privileged_amount,
+                privileged_daily_cap,
+                address_daily_cap,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TransferRepository for SqliteStore {
+    fn export_users(&self) -> BoxStream<'_, Result<User>> {
+        sqlx::query(
+            r#"
+            SELECT u.id, u.account_id, u.channel, u.handle, a.role, u.domain, u.last_seen_at
+            FROM users u
+            JOIN accounts a ON a.id = u.account_id
+            "#,
+        )
+        .fetch(&self.pool)
+        .map(|row| Self::map_user(&row?))
+        .boxed()
+    }
+
+    fn export_requests(&self) -> BoxStream<'_, Result<MintRequest>> {
+        sqlx::query("SELECT * FROM mint_requests")
+            .fetch(&self.pool)
+            .map(|row| Self::map_request(&row?))
+            .boxed()
+    }
+
+    fn export_quotas(&self) -> BoxStream<'_, Result<Quota>> {
+        sqlx::query("SELECT * FROM quotas")
+            .fetch(&self.pool)
+            .map(|row| Self::map_quota(&row?))
+            .boxed()
+    }
+
+    fn export_address_quotas(&self) -> BoxStream<'_, Result<AddressQuota>> {
+        sqlx::query("SELECT address, day, minted_total FROM address_quotas")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(AddressQuota {
+                    address: address_from_db(row.try_get("address")?)?,
+                    day: NaiveDate::parse_from_str(row.try_get("day")?, "%Y-%m-%d")?,
+                    minted_total: row.try_get::<i64, _>("minted_total")? as u64,
+                })
+            })
+            .boxed()
+    }
+
+    fn export_failures(&self) -> BoxStream<'_, Result<MintFailure>> {
+        sqlx::query("SELECT id, request_id, failed_at, reason FROM mint_failures")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                Ok(MintFailure {
+                    id: uuid_column(&row, "id")?,
+                    request_id: uuid_column(&row, "request_id")?,
+                    failed_at: timestamp_column(&row, "failed_at")?,
+                    reason: row.try_get("reason")?,
+                })
+            })
+            .boxed()
+    }
+
+    fn export_configs(&self) -> BoxStream<'_, Result<SystemConfig>> {
+        sqlx::query("SELECT * FROM system_configs")
+            .fetch(&self.pool)
+            .map(|row| Self::map_config(&row?))
+            .boxed()
+    }
+
+    async fn import_user(&self, user: &User) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO accounts (id, role, created_at) VALUES (?1, ?2, ?3) ON CONFLICT (id) DO NOTHING",
+        )
+        .bind(user.account_id.to_string())
+        .bind(user.role.as_str())
+        .bind(timestamp(Utc::now()))
+        .execute(&mut *tx)
+        .await?;
+        let result = sqlx::query(
+            r#"
+            INSERT INTO users (id, account_id, channel, handle, role, domain, last_seen_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(user.id.to_string())
+        .bind(user.account_id.to_string())
+        .bind(user.channel.as_str())
+        .bind(&user.handle)
+        .bind(user.role.as_str())
+        .bind(&user.domain)
+        .bind(timestamp(user.last_seen_at))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_request(&self, request: &MintRequest) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO mint_requests (id, user_id, account_id, channel, recipient, amount, status, tx_hash, error, requested_at, processed_at, attempt, available_at, traceparent, sequence_number, tx_expires_at, quota_day, http_request_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(request.id.to_string())
+        .bind(request.user_id.to_string())
+        .bind(request.account_id.to_string())
+        .bind(request.channel.as_str())
+        .bind(request.recipient.map(|address| address.to_string()))
+        .bind(request.amount as i64)
+        .bind(request.status.as_str())
+        .bind(&request.tx_hash)
+        .bind(&request.error)
+        .bind(timestamp(request.requested_at))
+        .bind(request.processed_at.map(timestamp))
+        .bind(request.attempt as i64)
+        .bind(request.available_at.map(timestamp))
+        .bind(&request.traceparent)
+        .bind(request.sequence_number.map(|value| value as i64))
+        .bind(request.tx_expires_at.map(timestamp))
+        .bind(request.quota_day.map(|day| day.to_string()))
+        .bind(&request.http_request_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_quota(&self, quota: &Quota) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO quotas (id, user_id, day, minted_total, success_count)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(quota.id.to_string())
+        .bind(quota.user_id.to_string())
+        .bind(quota.day.to_string())
+        .bind(quota.minted_total as i64)
+        .bind(quota.success_count as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_address_quota(&self, quota: &AddressQuota) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO address_quotas (address, day, minted_total)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(quota.address.to_string())
+        .bind(quota.day.to_string())
+        .bind(quota.minted_total as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_failure(&self, failure: &MintFailure) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO mint_failures (id, request_id, failed_at, reason)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (id) DO NOTHING
+            "#,
+        )
+        .bind(failure.id.to_string())
+        .bind(failure.request_id.to_string())
+        .bind(timestamp(failure.failed_at))
+        .bind(&failure.reason)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn import_config(&self, config: &SystemConfig) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO system_configs (id, key, value, description, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(config.id.to_string())
+        .bind(&config.key)
+        .bind(&config.value)
+        .bind(&config.description)
+        .bind(timestamp(config.created_at))
+        .bind(timestamp(config.updated_at))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
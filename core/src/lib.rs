@@ -1,13 +1,19 @@
+#[cfg(feature = "aptos")]
+pub mod aptos;
 pub mod config;
 pub mod db;
 pub mod logging;
+pub mod metrics;
 pub mod models;
+pub mod policy;
 pub mod queue;
 pub mod rate_limit;
 pub mod repository;
 pub mod service;
+pub mod transfer;
 
-pub use db::DatabaseStore;
+pub use db::Store;
+pub use queue::ChainClient;
 pub use service::{FaucetService, Identity};
 
 use anyhow::Result;
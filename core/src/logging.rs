@@ -1,10 +1,35 @@
+use std::{collections::HashMap, future::Future};
+
+use anyhow::Result;
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{
     fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
 };
 
 use crate::config::TelemetryConfig;
 
-pub fn init_telemetry(config: &TelemetryConfig) {
+/// 持有 OTLP 导出器，drop 时把尚未发送的 span 写出。需要在 `main` 中保留到进程退出前。
+#[must_use]
+pub struct TelemetryGuard {
+    provider: Option<TracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("OTLP 导出器关闭失败: {err}");
+            }
+        }
+    }
+}
+
+/// 初始化日志；配置了 `otlp_endpoint` 时同时通过 OTLP/HTTP 导出 span，`service` 作为服务名。
+pub fn init_telemetry(service: &'static str, config: &TelemetryConfig) -> Result<TelemetryGuard> {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info,hyper=warn,sqlx=warn"));
 
@@ -14,11 +39,128 @@ pub fn init_telemetry(config: &TelemetryConfig) {
         fmt::layer().with_target(false).boxed()
     };
 
-    let subscriber = Registry::default().with(filter).with(fmt_layer);
+    let endpoint = config
+        .otlp_endpoint
+        .as_deref()
+        .map(str::trim)
+        .filter(|endpoint| !endpoint.is_empty());
+    let provider = endpoint
+        .map(|endpoint| tracer_provider(service, endpoint))
+        .transpose()?;
+    let otel_layer = provider.as_ref().map(|provider| {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(service))
+    });
+
+    Registry::default()
+        .with(filter)
+        .with(fmt_layer)
+        .with(otel_layer)
+        .init();
+
+    if let Some(endpoint) = endpoint {
+        tracing::info!(endpoint, "OTLP 追踪导出已启用");
+    }
+    Ok(TelemetryGuard { provider })
+}
+
+/// `endpoint` 为 collector 的基础地址（如 `http://localhost:4318`），span 发送到其下的 `/v1/traces`
+fn tracer_provider(service: &'static str, endpoint: &str) -> Result<TracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service)]))
+        .build())
+}
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// 在 HTTP 请求的 `x-request-id` 下执行 `future`，其间发起的铸币请求会记下该 id
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+/// 当前 HTTP 请求的 `x-request-id`；不在 [`with_request_id`] 中（如机器人命令）时为 `None`
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// 当前 span 的 W3C `traceparent`；未启用 OTLP 时为 `None`
+pub fn current_traceparent() -> Option<String> {
+    let context = Span::current().context();
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier.remove("traceparent")
+}
+
+/// 把 `span` 接到上游传来的 `traceparent` 之下，值无效时保持为新的根 span
+pub fn set_remote_parent(span: &Span, traceparent: Option<&str>) {
+    let Some(traceparent) = traceparent else {
+        return;
+    };
+    let carrier = HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+    let context = global::get_text_map_propagator(|propagator| propagator.extract(&carrier));
+    span.set_parent(context);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{body::Bytes, extract::State, routing::post, Router};
+    use opentelemetry::trace::TraceContextExt;
+
+    use super::*;
+
+    /// 充当 collector：记录收到的 OTLP 请求体
+    async fn collector() -> (String, Arc<Mutex<Vec<Bytes>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .route(
+                "/v1/traces",
+                post(|State(received): State<Arc<Mutex<Vec<Bytes>>>>, body: Bytes| async move {
+                    received.lock().unwrap().push(body);
+                }),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), received)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn exports_spans_and_propagates_traceparent() {
+        let (endpoint, received) = collector().await;
+        let provider = tracer_provider("faucet-test", &endpoint).unwrap();
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("faucet-test")));
+
+        let (traceparent, worker_trace) = tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("faucet.mint");
+            let traceparent = request.in_scope(current_traceparent).unwrap();
 
-    subscriber.init();
+            // 异步 worker 从保存的 traceparent 恢复链路
+            let worker = tracing::info_span!("mint_worker.process");
+            set_remote_parent(&worker, Some(&traceparent));
+            let worker_trace = worker.context().span().span_context().trace_id();
+            (traceparent, worker_trace)
+        });
 
-    if let Some(_endpoint) = &config.otlp_endpoint {
-        tracing::warn!("OTLP 导出尚未实现");
+        assert_eq!(traceparent.split('-').nth(1), Some(worker_trace.to_string().as_str()));
+        for result in provider.force_flush() {
+            result.unwrap();
+        }
+        let bodies = received.lock().unwrap();
+        assert!(!bodies.is_empty());
+        // protobuf 中的 span 名称以原始字节出现
+        let body = bodies.concat();
+        assert!(body.windows(b"faucet.mint".len()).any(|w| w == b"faucet.mint"));
     }
 }
@@ -0,0 +1,139 @@
+//! Prometheus 指标。
+//!
+//! 业务代码只通过本模块的函数记录指标，未调用 [`install`] 时（例如报表任务、单元测试）
+//! 这些调用不会产生任何开销。
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use anyhow::Result;
+use metrics::{counter, describe_counter, describe_gauge, describe_histogram, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+pub use metrics_exporter_prometheus::PrometheusHandle;
+use tracing::{error, info};
+
+use crate::models::{Channel, Role};
+
+const MINTS: &str = "faucet_mints_total";
+const QUEUE_OUTCOMES: &str = "faucet_queue_outcomes_total";
+const RATE_LIMIT_REJECTIONS: &str = "faucet_rate_limit_rejections_total";
+const SUBMIT_TRANSFER_DURATION: &str = "faucet_submit_transfer_duration_seconds";
+const QUEUE_DEPTH: &str = "faucet_queue_depth";
+const DB_CALL_DURATION: &str = "faucet_db_call_duration_seconds";
+
+/// 数据库调用在毫秒级，链上提交可能长达数十秒，共用一组覆盖两端的桶
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// 安装全局 recorder，返回的 handle 用于渲染 `/metrics`。
+///
+/// `listen_addr` 不为空时另起一个只提供指标的 HTTP 监听，供没有 Web 服务的机器人使用。
+pub fn install(listen_addr: Option<SocketAddr>) -> Result<PrometheusHandle> {
+    let handle = match listen_addr {
+        Some(addr) => {
+            let (recorder, exporter) = builder()?.with_http_listener(addr).build()?;
+            let handle = recorder.handle();
+            metrics::set_global_recorder(recorder)?;
+            // 端口在 build 时已经绑定，这里只会在连接处理出错时返回（错误类型未实现 Debug）
+            tokio::spawn(async move {
+                if exporter.await.is_err() {
+                    error!("metrics_listener_failed");
+                }
+            });
+            info!(%addr, "指标监听已启动");
+            handle
+        }
+        None => builder()?.install_recorder()?,
+    };
+    describe();
+    Ok(handle)
+}
+
+fn builder() -> Result<PrometheusBuilder> {
+    Ok(PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)?)
+}
+
+fn describe() {
+    describe_counter!(MINTS, "Mint requests handled by FaucetService, by channel, role and status");
+    describe_counter!(QUEUE_OUTCOMES, "Requests processed by the async mint worker");
+    describe_counter!(RATE_LIMIT_REJECTIONS, "Mint requests rejected by the rate limiter");
+    describe_histogram!(SUBMIT_TRANSFER_DURATION, "Latency of submit_transfer calls");
+    describe_gauge!(QUEUE_DEPTH, "Pending requests in the mint queue");
+    describe_histogram!(DB_CALL_DURATION, "Latency of repository calls");
+}
+
+/// `status`：`completed`、`failed`，异步模式下为 `queued`
+pub fn mint(channel: &Channel, role: &Role, status: &'static str) {
+    counter!(
+        MINTS,
+        "channel" => channel.as_str(),
+        "role" => role.as_str(),
+        "status" => status
+    )
+    .increment(1);
+}
+
+/// `status`：`completed`、`retry`、`failed`
+pub fn queue_outcome(channel: &Channel, status: &'static str) {
+    counter!(QUEUE_OUTCOMES, "channel" => channel.as_str(), "status" => status).increment(1);
+}
+
+pub fn rate_limit_rejection(reason: &'static str) {
+    counter!(RATE_LIMIT_REJECTIONS, "reason" => reason).increment(1);
+}
+
+pub fn queue_depth(pending: u64) {
+    gauge!(QUEUE_DEPTH).set(pending as f64);
+}
+
+pub async fn time_transfer<T>(call: impl Future<Output = Result<T>>) -> Result<T> {
+    timed(SUBMIT_TRANSFER_DURATION, None, call).await
+}
+
+pub async fn time_db<T>(op: &'static str, call: impl Future<Output = Result<T>>) -> Result<T> {
+    timed(DB_CALL_DURATION, Some(op), call).await
+}
+
+async fn timed<T>(
+    name: &'static str,
+    op: Option<&'static str>,
+    call: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let started = Instant::now();
+    let result = call.await;
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    let elapsed = started.elapsed().as_secs_f64();
+    match op {
+        Some(op) => histogram!(name, "op" => op, "outcome" => outcome).record(elapsed),
+        None => histogram!(name, "outcome" => outcome).record(elapsed),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_latency_as_histograms() {
+        let recorder = builder().unwrap().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            mint(&Channel::Web, &Role::User, "completed");
+            rate_limit_rejection("daily_cap");
+            histogram!(DB_CALL_DURATION, "op" => "find_user", "outcome" => "ok").record(0.002);
+        });
+
+        let rendered = handle.render();
+        assert!(rendered.contains(
+            r#"faucet_mints_total{channel="web",role="user",status="completed"} 1"#
+        ));
+        assert!(rendered.contains(r#"faucet_rate_limit_rejections_total{reason="daily_cap"} 1"#));
+        assert!(rendered.contains(
+            r#"faucet_db_call_duration_seconds_bucket{op="find_user",outcome="ok",le="0.005"} 1"#
+        ));
+    }
+}
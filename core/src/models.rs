@@ -11,6 +11,8 @@ pub enum Channel {
     Web,
     Telegram,
     Discord,
+    /// 以 Aptos 钱包签名登录，handle 为账户地址
+    Wallet,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -21,21 +23,75 @@ pub enum Role {
     Admin,
 }
 
+/// 某个渠道下的身份。多个身份可以关联到同一账户，角色与额度都归属账户。
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
+    pub account_id: Uuid,
     pub channel: Channel,
     pub handle: String,
+    /// 所属账户的角色
     pub role: Role,
     pub domain: Option<String>,
     pub last_seen_at: DateTime<Utc>,
 }
 
+impl User {
+    /// 新身份，归属一个新建的普通账户
+    pub fn new(channel: Channel, handle: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            account_id: Uuid::new_v4(),
+            channel,
+            handle: handle.to_string(),
+            role: Role::User,
+            domain: None,
+            last_seen_at: Utc::now(),
+        }
+    }
+}
+
+/// 网页端签发的一次性关联码，机器人身份凭此加入网页账户
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCode {
+    pub code: String,
+    pub account_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 网页会话的刷新令牌。只保存令牌的 SHA-256，同一会话每次刷新都换一个新令牌
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub session_id: Uuid,
+    pub channel: Channel,
+    pub handle: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// 钱包登录的一次性挑战。`nonce` 由 web 签名并自带过期时间，只有登录成功用过的才会写入存储
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginNonce {
+    pub nonce: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Aptos 账户地址（32 字节）。
+///
+/// 解析时要求 `0x` 前缀，接受省略前导零的短格式（如 `0x1`），输出统一为 64 位十六进制的长格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct AccountAddress([u8; 32]);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintRequest {
     pub id: Uuid,
     pub user_id: Uuid,
+    /// 发起请求时身份所属的账户，额度按账户统计
+    pub account_id: Uuid,
     pub channel: Channel,
+    /// 历史数据可能没有记录收款地址
+    pub recipient: Option<AccountAddress>,
     pub amount: u64,
     pub status: MintStatus,
     pub tx_hash: Option<String>,
@@ -43,6 +99,52 @@ pub struct MintRequest {
     pub requested_at: DateTime<Utc>,
     pub processed_at: Option<DateTime<Utc>>,
     pub attempt: u16,
+    /// 队列可以（重新）领取该请求的时间：等待重试时为退避结束时间，处理中时为租约到期时间
+    #[serde(default)]
+    pub available_at: Option<DateTime<Utc>>,
+    /// 发起请求时的 W3C `traceparent`，异步 worker 据此把链上提交接回同一条链路
+    #[serde(default)]
+    pub traceparent: Option<String>,
+    /// 发起请求时的 HTTP `x-request-id`，可据此从接口日志查到对应的铸币与 worker 日志
+    #[serde(default)]
+    pub http_request_id: Option<String>,
+    /// 广播前记下的交易 sequence number，与 `tx_hash`、`tx_expires_at` 一同写入
+    #[serde(default)]
+    pub sequence_number: Option<u64>,
+    /// 已广播交易的过期时间，过期后仍未上链的交易不会再被执行
+    #[serde(default)]
+    pub tx_expires_at: Option<DateTime<Utc>>,
+    /// 扣减账户与地址额度的日期，退还时必须落在同一天
+    #[serde(default)]
+    pub quota_day: Option<NaiveDate>,
+}
+
+impl MintRequest {
+    /// 扣减额度的日期，旧请求没有记录时按请求时间推算
+    pub fn charged_day(&self) -> NaiveDate {
+        self.quota_day
+            .unwrap_or_else(|| self.requested_at.date_naive())
+    }
+
+    /// 之前的尝试已签名并可能广播过、尚未确认结果的交易
+    pub fn pending_transfer(&self) -> Option<PendingTransfer> {
+        if self.status == MintStatus::Completed {
+            return None;
+        }
+        Some(PendingTransfer {
+            hash: self.tx_hash.clone()?,
+            sequence_number: self.sequence_number?,
+            expires_at: self.tx_expires_at?,
+        })
+    }
+}
+
+/// 已签名的转账交易，广播前写入请求，重试时据此查询而不是直接重发
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingTransfer {
+    pub hash: String,
+    pub sequence_number: u64,
+    pub expires_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -57,24 +159,114 @@ pub enum MintStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Quota {
     pub id: Uuid,
+    /// 额度所属的账户；账户体系引入前的记录中即为用户 id
     pub user_id: Uuid,
     pub day: NaiveDate,
     pub minted_total: u64,
     pub success_count: u64,
 }
 
+/// 收款地址的当日累计额度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressQuota {
+    pub address: AccountAddress,
+    pub day: NaiveDate,
+    pub minted_total: u64,
+}
+
+/// 最终失败的请求及原因
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintFailure {
+    pub id: Uuid,
+    pub request_id: Uuid,
+    pub failed_at: DateTime<Utc>,
+    pub reason: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MintOutcome {
     pub request: MintRequest,
     pub tx_hash: Option<String>,
 }
 
+impl AccountAddress {
+    pub const LENGTH: usize = 32;
+
+    pub fn new(bytes: [u8; Self::LENGTH]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; Self::LENGTH] {
+        &self.0
+    }
+}
+
+impl FromStr for AccountAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        let digits = trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+            .with_context(|| format!("address must start with 0x: {trimmed}"))?;
+        if digits.is_empty() || digits.len() > Self::LENGTH * 2 {
+            anyhow::bail!("address must have 1 to 64 hex digits: {trimmed}");
+        }
+
+        let mut bytes = [0u8; Self::LENGTH];
+        hex::decode_to_slice(format!("{digits:0>64}"), &mut bytes)
+            .with_context(|| format!("address is not valid hex: {trimmed}"))?;
+        Ok(Self(bytes))
+    }
+}
+
+impl std::fmt::Display for AccountAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl TryFrom<String> for AccountAddress {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<AccountAddress> for String {
+    fn from(value: AccountAddress) -> Self {
+        value.to_string()
+    }
+}
+
+/// 解析机器人 `mint` 命令的参数：`<命令> [地址] [数量]`，参数顺序不限。
+///
+/// 能解析为地址的参数即为收款地址，其余按数量解析。
+pub fn parse_mint_args(text: &str) -> anyhow::Result<(Option<AccountAddress>, Option<u64>)> {
+    let mut recipient = None;
+    let mut amount = None;
+    for arg in text.split_whitespace().skip(1) {
+        if let Ok(address) = AccountAddress::from_str(arg) {
+            recipient = Some(address);
+        } else if arg.starts_with("0x") || arg.starts_with("0X") {
+            // 带前缀但不是合法地址，报告地址本身的错误
+            recipient = Some(AccountAddress::from_str(arg)?);
+        } else {
+            amount = Some(arg.parse::<u64>().with_context(|| format!("amount must be an integer: {arg}"))?);
+        }
+    }
+    Ok((recipient, amount))
+}
+
 impl Channel {
     pub fn as_str(&self) -> &'static str {
         match self {
             Channel::Web => "web",
             Channel::Telegram => "telegram",
             Channel::Discord => "discord",
+            Channel::Wallet => "wallet",
         }
     }
 }
@@ -87,6 +279,7 @@ impl FromStr for Channel {
             "web" => Ok(Channel::Web),
             "telegram" => Ok(Channel::Telegram),
             "discord" => Ok(Channel::Discord),
+            "wallet" => Ok(Channel::Wallet),
             other => anyhow::bail!("unknown channel: {other}"),
         }
     }
@@ -148,6 +341,10 @@ pub fn role_from_db(value: &str) -> anyhow::Result<Role> {
     Role::from_str(value).with_context(|| format!("invalid role value: {value}"))
 }
 
+pub fn address_from_db(value: &str) -> anyhow::Result<AccountAddress> {
+    AccountAddress::from_str(value).with_context(|| format!("invalid address value: {value}"))
+}
+
 pub fn status_from_db(value: &str) -> anyhow::Result<MintStatus> {
     MintStatus::from_str(value).with_context(|| format!("invalid status value: {value}"))
 }
@@ -162,10 +359,48 @@ pub struct SystemConfig {
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LimitConfigUpdate {
     pub default_amount: Option<u64>,
     pub default_daily_cap: Option<u64>,
     pub privileged_amount: Option<u64>,
     pub privileged_daily_cap: Option<u64>,
+    pub address_daily_cap: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_address_parsing() {
+        let short: AccountAddress = "0x1".parse().unwrap();
+        assert_eq!(short.as_bytes()[31], 1);
+        assert_eq!(short.to_string(), format!("0x{}1", "0".repeat(63)));
+
+        let long = format!("0x{}", "ab".repeat(32));
+        assert_eq!(long.parse::<AccountAddress>().unwrap().to_string(), long);
+
+        assert!("1".parse::<AccountAddress>().is_err());
+        assert!("0x".parse::<AccountAddress>().is_err());
+        assert!("0xzz".parse::<AccountAddress>().is_err());
+        assert!(format!("0x{}", "1".repeat(65)).parse::<AccountAddress>().is_err());
+
+        let json = serde_json::to_string(&short).unwrap();
+        assert_eq!(serde_json::from_str::<AccountAddress>(&json).unwrap(), short);
+        assert!(serde_json::from_str::<AccountAddress>("\"abc\"").is_err());
+    }
+
+    #[test]
+    fn mint_args_parsing() {
+        let address: AccountAddress = "0xab".parse().unwrap();
+        assert_eq!(parse_mint_args("/mint").unwrap(), (None, None));
+        assert_eq!(parse_mint_args("/mint 0xab 5").unwrap(), (Some(address), Some(5)));
+        assert_eq!(parse_mint_args("!mint 5 0XAB").unwrap(), (Some(address), Some(5)));
+        assert_eq!(parse_mint_args("/mint 7").unwrap(), (None, Some(7)));
+
+        let err = parse_mint_args("/mint 0Xzz").unwrap_err();
+        assert!(err.to_string().contains("not valid hex"), "{err}");
+        assert!(parse_mint_args("/mint ten").is_err());
+    }
 }
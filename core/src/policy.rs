@@ -0,0 +1,188 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::RatePolicyConfig;
+use crate::models::MintRequest;
+
+/// 基于近期铸币记录的限流策略。
+///
+/// `RateLimiter` 按所有策略中最长的 `window` 取一次历史，再逐个调用 `check`，
+/// 任一策略返回错误即拒绝本次请求。
+pub trait RatePolicy: Send + Sync {
+    /// 拒绝时记入指标的原因
+    fn name(&self) -> &'static str;
+
+    /// 需要回看的时间范围
+    fn window(&self) -> Duration;
+
+    /// `history` 为窗口内未失败的请求，按 `requested_at` 升序排列
+    fn check(&self, history: &[MintRequest], amount: u64, now: DateTime<Utc>) -> Result<()>;
+}
+
+/// 任意连续 `window` 时长内的累计额度上限，避免在 UTC 零点前后连续领取两天的额度
+pub struct RollingWindow {
+    pub window: Duration,
+    pub cap: u64,
+}
+
+impl RatePolicy for RollingWindow {
+    fn name(&self) -> &'static str {
+        "rolling_window"
+    }
+
+    fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn check(&self, history: &[MintRequest], amount: u64, now: DateTime<Utc>) -> Result<()> {
+        let used: u64 = within(history, now - self.window)
+            .map(|request| request.amount)
+            .sum();
+        if used + amount > self.cap {
+            anyhow::bail!("rolling window cap reached");
+        }
+        Ok(())
+    }
+}
+
+/// 两次领取之间的最短间隔
+pub struct Cooldown {
+    pub interval: Duration,
+}
+
+impl RatePolicy for Cooldown {
+    fn name(&self) -> &'static str {
+        "cooldown"
+    }
+
+    fn window(&self) -> Duration {
+        self.interval
+    }
+
+    fn check(&self, history: &[MintRequest], _amount: u64, now: DateTime<Utc>) -> Result<()> {
+        if let Some(last) = history.last() {
+            let ready_at = last.requested_at + self.interval;
+            if ready_at > now {
+                anyhow::bail!(
+                    "cooldown active, retry in {}s",
+                    (ready_at - now).num_seconds().max(1)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 每分钟最多发起的请求数，限制短时间内的突发
+pub struct RequestsPerMinute {
+    pub limit: u32,
+}
+
+impl RatePolicy for RequestsPerMinute {
+    fn name(&self) -> &'static str {
+        "per_minute"
+    }
+
+    fn window(&self) -> Duration {
+        Duration::minutes(1)
+    }
+
+    fn check(&self, history: &[MintRequest], _amount: u64, now: DateTime<Utc>) -> Result<()> {
+        if within(history, now - self.window()).count() >= self.limit as usize {
+            anyhow::bail!("too many requests, limit is {} per minute", self.limit);
+        }
+        Ok(())
+    }
+}
+
+/// 按配置构造策略列表，未配置的项不生效
+pub fn policies_for(config: &RatePolicyConfig) -> Vec<Box<dyn RatePolicy>> {
+    let mut policies: Vec<Box<dyn RatePolicy>> = Vec::new();
+    if let Some(cap) = config.rolling_cap {
+        policies.push(Box::new(RollingWindow {
+            window: Duration::hours(24),
+            cap,
+        }));
+    }
+    if let Some(interval) = config.cooldown {
+        let interval =
+            Duration::from_std(interval).expect("cooldown range is checked when loading config");
+        policies.push(Box::new(Cooldown { interval }));
+    }
+    if let Some(limit) = config.per_minute {
+        policies.push(Box::new(RequestsPerMinute { limit }));
+    }
+    policies
+}
+
+fn within(history: &[MintRequest], since: DateTime<Utc>) -> impl Iterator<Item = &MintRequest> {
+    history
+        .iter()
+        .filter(move |request| request.requested_at > since)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Channel, User};
+    use crate::queue::new_request;
+
+    fn minted(at: DateTime<Utc>, amount: u64) -> MintRequest {
+        let mut request = new_request(
+            &User::new(Channel::Web, "alice"),
+            "0x1".parse().unwrap(),
+            amount,
+        );
+        request.requested_at = at;
+        request
+    }
+
+    #[test]
+    fn rolling_window_spans_midnight() {
+        let now = Utc::now();
+        let policy = RollingWindow {
+            window: Duration::hours(24),
+            cap: 100,
+        };
+        let history = [
+            minted(now - Duration::hours(25), 100),
+            minted(now - Duration::hours(2), 60),
+        ];
+
+        assert!(policy.check(&history, 40, now).is_ok());
+        assert!(policy.check(&history, 41, now).is_err());
+    }
+
+    #[test]
+    fn cooldown_blocks_until_interval_elapses() {
+        let now = Utc::now();
+        let policy = Cooldown {
+            interval: Duration::minutes(10),
+        };
+
+        assert!(policy
+            .check(&[minted(now - Duration::minutes(5), 1)], 1, now)
+            .is_err());
+        assert!(policy
+            .check(&[minted(now - Duration::minutes(11), 1)], 1, now)
+            .is_ok());
+        assert!(policy.check(&[], 1, now).is_ok());
+    }
+
+    #[test]
+    fn requests_per_minute_limits_bursts() {
+        let now = Utc::now();
+        let policy = RequestsPerMinute { limit: 2 };
+        let history = [
+            minted(now - Duration::seconds(90), 1),
+            minted(now - Duration::seconds(30), 1),
+        ];
+
+        assert!(policy.check(&history, 1, now).is_ok());
+        let burst = [
+            minted(now - Duration::seconds(20), 1),
+            minted(now - Duration::seconds(10), 1),
+        ];
+        assert!(policy.check(&burst, 1, now).is_err());
+    }
+}
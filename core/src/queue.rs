@@ -1,101 +1,393 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use async_trait::async_trait;
-use tokio::sync::mpsc;
-use tracing::{info, warn};
+use chrono::Utc;
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
-use crate::models::{MintOutcome, MintRequest, MintStatus};
-use crate::repository::{MintRepository, UserRepository};
+use crate::config::{AptosConfig, QueueConfig};
+use crate::{logging, metrics};
+use crate::models::{AccountAddress, MintOutcome, MintRequest, MintStatus, PendingTransfer, User};
+use crate::repository::{MintRepository, QuotaRepository, ReportingRepository};
+use crate::Service;
 
 #[async_trait]
 pub trait AptosClient: Send + Sync {
-    async fn submit_transfer(&self, request: &MintRequest) -> Result<String>;
+    /// 提交转账并等待上链。每次广播前先把签名后的交易交给 `journal`，记录失败时不得广播。
+    async fn submit_transfer(
+        &self,
+        request: &MintRequest,
+        journal: &dyn TransferJournal,
+    ) -> Result<String>;
+    /// 查询之前记录的交易，交易仍可能上链时一直等待，直到有确定结果。
+    async fn confirm_transfer(&self, transfer: &PendingTransfer) -> Result<TransferState>;
+    /// 一次尝试（确认上一笔交易并提交新交易）最坏情况下的耗时，队列租约必须长于它。
+    fn max_attempt_duration(&self) -> Duration;
 }
 
-#[derive(Clone)]
-pub struct MintQueue<R, U, C> {
-    repo: Arc<R>,
-    users: Arc<U>,
-    client: Arc<C>,
-    tx: mpsc::Sender<MintRequest>,
+/// 已记录交易的链上结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferState {
+    Committed,
+    /// 已上链但执行失败，没有发生转账
+    Aborted(String),
+    /// 过期前未上链，之后也不会再被执行
+    Expired,
+}
+
+/// 广播前持久化签名后的交易，使超时或崩溃后的重试能够先确认结果而不是重复转账。
+#[async_trait]
+pub trait TransferJournal: Send + Sync {
+    async fn record(&self, transfer: &PendingTransfer) -> Result<()>;
+    /// 已确定不会转账的交易：被节点拒绝、链上执行失败或已过期
+    fn discard(&self, transfer: &PendingTransfer);
+}
+
+/// 把交易写入当前持有租约的请求，租约已被其他 worker 接管时返回错误。
+pub struct LeaseJournal<'a, R: ?Sized> {
+    repo: &'a R,
+    request_id: Uuid,
+    attempt: u16,
+    unsettled: std::sync::Mutex<Option<PendingTransfer>>,
+}
+
+impl<'a, R: ?Sized> LeaseJournal<'a, R> {
+    pub fn new(repo: &'a R, request: &MintRequest) -> Self {
+        Self {
+            repo,
+            request_id: request.id,
+            attempt: request.attempt,
+            unsettled: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// 本次尝试记录过、且结果未知的交易；提交失败后它仍可能上链
+    pub fn unsettled(&self) -> Option<PendingTransfer> {
+        self.unsettled.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl<R: ?Sized + MintRepository> TransferJournal for LeaseJournal<'_, R> {
+    async fn record(&self, transfer: &PendingTransfer) -> Result<()> {
+        if !self
+            .repo
+            .record_submission(self.request_id, self.attempt, transfer)
+            .await?
+        {
+            anyhow::bail!(
+                "mint request {} attempt {} no longer holds its lease",
+                self.request_id,
+                self.attempt
+            );
+        }
+        *self.unsettled.lock().unwrap() = Some(transfer.clone());
+        Ok(())
+    }
+
+    fn discard(&self, transfer: &PendingTransfer) {
+        let mut unsettled = self.unsettled.lock().unwrap();
+        if unsettled.as_ref() == Some(transfer) {
+            *unsettled = None;
+        }
+    }
 }
 
-impl<R, U, C> MintQueue<R, U, C>
+/// 退还请求在扣减当天占用的账户与地址额度
+pub(crate) async fn release_quota<R>(repo: &R, request: &MintRequest) -> Result<()>
 where
-    R: MintRepository + 'static,
-    U: UserRepository + 'static,
-    C: AptosClient + 'static,
+    R: ?Sized + QuotaRepository,
 {
-    pub fn new(
-        repo: Arc<R>,
-        users: Arc<U>,
-        client: Arc<C>,
-        depth: usize,
-    ) -> (Self, mpsc::Receiver<MintRequest>) {
-        let (tx, rx) = mpsc::channel(depth);
-        (
-            Self {
-                repo,
-                users,
-                client,
-                tx,
-            },
-            rx,
-        )
-    }
-
-    pub async fn enqueue(&self, mut request: MintRequest) -> Result<()> {
-        request.status = MintStatus::Pending;
-        self.repo.enqueue(&request).await?;
-        self.tx
-            .send(request)
-            .await
-            .map_err(|_| anyhow::anyhow!("queue closed"))
+    let day = request.charged_day();
+    repo.release_mint(request.account_id, day, request.amount)
+        .await?;
+    match &request.recipient {
+        Some(address) => repo.release_address_mint(address, day, request.amount).await,
+        None => Ok(()),
     }
 }
 
-pub async fn worker_loop<R, U, C>(
-    mut rx: mpsc::Receiver<MintRequest>,
+/// 基于 `MintRepository` 的持久化队列。
+///
+/// 请求写入存储后由 worker 轮询 `next_pending` 领取，因此进程重启或多副本部署时
+/// 队列内容不会丢失；同进程内入队会立即唤醒 worker，跨进程则依赖轮询间隔。
+pub struct MintQueue<R: ?Sized, C> {
     repo: Arc<R>,
     client: Arc<C>,
-) -> Result<()>
+    config: QueueConfig,
+    notify: Arc<Notify>,
+}
+
+impl<R: ?Sized, C> Clone for MintQueue<R, C> {
+    fn clone(&self) -> Self {
+        Self {
+            repo: self.repo.clone(),
+            client: self.client.clone(),
+            config: self.config.clone(),
+            notify: self.notify.clone(),
+        }
+    }
+}
+
+impl<R, C> MintQueue<R, C>
 where
-    R: MintRepository + 'static,
-    U: UserRepository + 'static,
+    R: ?Sized + MintRepository + QuotaRepository + ReportingRepository + 'static,
     C: AptosClient + 'static,
 {
-    while let Some(mut request) = rx.recv().await {
-        repo.update_status(request.id, MintStatus::Processing)
+    /// 租约不长于一次尝试的最坏耗时时拒绝创建，否则提交尚未结束就会被其他 worker 重新领取。
+    pub fn new(repo: Arc<R>, client: Arc<C>, config: QueueConfig) -> Result<Self> {
+        let required = client.max_attempt_duration();
+        if config.visibility_timeout <= required {
+            anyhow::bail!(
+                "queue.visibility_timeout ({:?}) must be longer than the worst-case transfer time ({:?})",
+                config.visibility_timeout,
+                required
+            );
+        }
+        Ok(Self {
+            repo,
+            client,
+            config,
+            notify: Arc::new(Notify::new()),
+        })
+    }
+
+    pub async fn enqueue(&self, mut request: MintRequest) -> Result<MintRequest> {
+        request.status = MintStatus::Pending;
+        self.repo.enqueue(&request).await?;
+        self.notify.notify_one();
+        Ok(request)
+    }
+
+    /// 领取并处理一条请求，队列为空时返回 `false`。
+    pub async fn process_next(&self) -> Result<bool> {
+        let Some(request) = self.repo.next_pending(self.config.visibility_timeout).await? else {
+            return Ok(false);
+        };
+
+        // 接回发起请求时的链路，入队与提交在同一条 trace 中
+        let span = info_span!(
+            "mint_worker.process",
+            request_id = %request.id,
+            attempt = request.attempt,
+            http.request_id = request.http_request_id.as_deref(),
+        );
+        logging::set_remote_parent(&span, request.traceparent.as_deref());
+        self.process(request).instrument(span).await?;
+        Ok(true)
+    }
+
+    async fn process(&self, mut request: MintRequest) -> Result<()> {
+        // 首次提交也算一次尝试，因此最多尝试 max_retries + 1 次
+        let max_attempts = self.config.max_retries.saturating_add(1);
+
+        // 之前的尝试记录过交易（提交超时或 worker 崩溃）：先确认结果，仍可能上链时绝不重发
+        if let Some(transfer) = request.pending_transfer() {
+            match self.client.confirm_transfer(&transfer).await {
+                Ok(TransferState::Committed) => {
+                    return self.complete(&mut request, transfer.hash).await;
+                }
+                // 之前的交易没有转账，可以重新提交
+                Ok(TransferState::Aborted(vm_status)) => warn!(
+                    request_id = %request.id,
+                    hash = %transfer.hash,
+                    %vm_status,
+                    "mint_previous_transfer_aborted"
+                ),
+                Ok(TransferState::Expired) => warn!(
+                    request_id = %request.id,
+                    hash = %transfer.hash,
+                    "mint_previous_transfer_expired"
+                ),
+                Err(err) => {
+                    return self
+                        .retry_or_fail(&mut request, err, Some(transfer), max_attempts)
+                        .await
+                }
+            }
+        }
+
+        if request.attempt > max_attempts {
+            // 租约多次过期（worker 崩溃）后不再提交，避免无限重放；
+            // 之前记录的交易已确认没有转账，可以退还额度
+            return self
+                .fail(&mut request, "retries exhausted after lease expiry".to_string(), true)
+                .await;
+        }
+
+        let journal = LeaseJournal::new(self.repo.as_ref(), &request);
+        match metrics::time_transfer(self.client.submit_transfer(&request, &journal)).await {
+            Ok(hash) => self.complete(&mut request, hash).await,
+            Err(err) => {
+                self.retry_or_fail(&mut request, err, journal.unsettled(), max_attempts)
+                    .await
+            }
+        }
+    }
+
+    async fn complete(&self, request: &mut MintRequest, hash: String) -> Result<()> {
+        request.status = MintStatus::Completed;
+        request.tx_hash = Some(hash.clone());
+        request.error = None;
+        request.processed_at = Some(Utc::now());
+        let recorded = self
+            .repo
+            .record_outcome(&MintOutcome {
+                request: request.clone(),
+                tx_hash: Some(hash.clone()),
+            })
             .await?;
-        match client.submit_transfer(&request).await {
-            Ok(hash) => {
-                request.status = MintStatus::Completed;
-                request.tx_hash = Some(hash.clone());
-                repo.record_outcome(&MintOutcome {
-                    request,
-                    tx_hash: Some(hash),
-                })
-                .await?;
+        if !recorded {
+            warn!(request_id = %request.id, attempt = request.attempt, "mint_lease_lost");
+            return Ok(());
+        }
+        metrics::queue_outcome(&request.channel, "completed");
+        info!(request_id = %request.id, attempt = request.attempt, ?hash, "mint_success");
+        Ok(())
+    }
+
+    /// `unsettled` 为本次尝试中可能已经上链的交易
+    async fn retry_or_fail(
+        &self,
+        request: &mut MintRequest,
+        err: anyhow::Error,
+        unsettled: Option<PendingTransfer>,
+        max_attempts: u16,
+    ) -> Result<()> {
+        if request.attempt >= max_attempts {
+            return self.give_up(request, err.to_string(), unsettled).await;
+        }
+
+        let retry_at = Utc::now() + chrono::Duration::from_std(self.config.retry_backoff)?;
+        warn!(
+            request_id = %request.id,
+            attempt = request.attempt,
+            error = %err,
+            %retry_at,
+            "mint_retry_scheduled"
+        );
+        if !self
+            .repo
+            .schedule_retry(request.id, request.attempt, &err.to_string(), retry_at)
+            .await?
+        {
+            warn!(request_id = %request.id, attempt = request.attempt, "mint_lease_lost");
+            return Ok(());
+        }
+        metrics::queue_outcome(&request.channel, "retry");
+        Ok(())
+    }
+
+    /// 放弃前再确认一次可能已上链的交易：已上链按成功记录，结果仍未知时不退还额度
+    async fn give_up(
+        &self,
+        request: &mut MintRequest,
+        error_message: String,
+        unsettled: Option<PendingTransfer>,
+    ) -> Result<()> {
+        let Some(transfer) = unsettled else {
+            return self.fail(request, error_message, true).await;
+        };
+        match self.client.confirm_transfer(&transfer).await {
+            Ok(TransferState::Committed) => self.complete(request, transfer.hash).await,
+            Ok(TransferState::Aborted(_) | TransferState::Expired) => {
+                self.fail(request, error_message, true).await
             }
             Err(err) => {
-                warn!(request_id = %request.id, error = %err, "mint_failed");
-                repo.update_status(request.id, MintStatus::Failed).await?;
+                warn!(request_id = %request.id, hash = %transfer.hash, error = %err, "mint_transfer_unconfirmed");
+                request.tx_hash = Some(transfer.hash);
+                self.fail(request, error_message, false).await
             }
         }
     }
 
-    info!("Mint worker 已停止");
-    Ok(())
+    /// `refund` 为 `false` 时交易可能已经转出，保留已扣的额度
+    async fn fail(&self, request: &mut MintRequest, error_message: String, refund: bool) -> Result<()> {
+        request.status = MintStatus::Failed;
+        request.error = Some(error_message.clone());
+        request.processed_at = Some(Utc::now());
+        let recorded = self
+            .repo
+            .record_outcome(&MintOutcome {
+                request: request.clone(),
+                // 结果未知时保留交易哈希，便于人工核对
+                tx_hash: if refund { None } else { request.tx_hash.clone() },
+            })
+            .await?;
+        if !recorded {
+            // 租约已被接管，结果与额度由当前持有者处理
+            warn!(request_id = %request.id, attempt = request.attempt, "mint_lease_lost");
+            return Ok(());
+        }
+        warn!(request_id = %request.id, attempt = request.attempt, error = %error_message, refund, "mint_failed");
+        metrics::queue_outcome(&request.channel, "failed");
+        self.repo
+            .log_failure(request.id, Utc::now(), &error_message)
+            .await?;
+        // 最终失败的请求不占用当日额度
+        if refund {
+            release_quota(self.repo.as_ref(), request).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<R, C> Service for MintQueue<R, C>
+where
+    R: ?Sized + MintRepository + QuotaRepository + ReportingRepository + Send + Sync + 'static,
+    C: AptosClient + 'static,
+{
+    fn spawn(self) -> JoinHandle<Result<()>> {
+        tokio::spawn(worker_loop(self))
+    }
+}
+
+pub async fn worker_loop<R, C>(queue: MintQueue<R, C>) -> Result<()>
+where
+    R: ?Sized + MintRepository + QuotaRepository + ReportingRepository + 'static,
+    C: AptosClient + 'static,
+{
+    info!(
+        max_retries = queue.config.max_retries,
+        visibility_timeout = ?queue.config.visibility_timeout,
+        "Mint worker 已启动"
+    );
+
+    // 队列深度每个轮询间隔最多采样一次，避免忙碌时每处理一条就查询一次
+    let mut sampled_at: Option<Instant> = None;
+    loop {
+        if sampled_at.is_none_or(|at| at.elapsed() >= queue.config.poll_interval) {
+            match queue.repo.pending_count().await {
+                Ok(pending) => metrics::queue_depth(pending),
+                Err(err) => warn!(error = %err, "queue_depth_unavailable"),
+            }
+            sampled_at = Some(Instant::now());
+        }
+
+        match queue.process_next().await {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(err) => error!(error = %err, "mint_worker_error"),
+        }
+
+        tokio::select! {
+            _ = queue.notify.notified() => {}
+            _ = tokio::time::sleep(queue.config.poll_interval) => {}
+        }
+    }
 }
 
-pub fn new_request(user_id: Uuid, channel: crate::models::Channel, amount: u64) -> MintRequest {
+pub fn new_request(user: &User, recipient: AccountAddress, amount: u64) -> MintRequest {
     let now = chrono::Utc::now();
     MintRequest {
         id: Uuid::new_v4(),
-        user_id,
-        channel,
+        user_id: user.id,
+        account_id: user.account_id,
+        channel: user.channel.clone(),
+        recipient: Some(recipient),
         amount,
         status: MintStatus::Pending,
         tx_hash: None,
@@ -103,15 +395,365 @@ pub fn new_request(user_id: Uuid, channel: crate::models::Channel, amount: u64)
         requested_at: now,
         processed_at: None,
         attempt: 0,
+        available_at: None,
+        traceparent: None,
+        http_request_id: None,
+        sequence_number: None,
+        tx_expires_at: None,
+        quota_day: None,
     }
 }
 
+#[derive(Clone)]
 pub struct LoggingAptosClient;
 
 #[async_trait]
 impl AptosClient for LoggingAptosClient {
-    async fn submit_transfer(&self, request: &MintRequest) -> Result<String> {
-        info!(user_id = %request.user_id, amount = request.amount, "mock_aptos_transfer");
-        Ok(format!("mock-tx-{}", Uuid::new_v4()))
+    async fn submit_transfer(
+        &self,
+        request: &MintRequest,
+        journal: &dyn TransferJournal,
+    ) -> Result<String> {
+        let transfer = PendingTransfer {
+            hash: format!("mock-tx-{}", Uuid::new_v4()),
+            sequence_number: 0,
+            expires_at: Utc::now(),
+        };
+        journal.record(&transfer).await?;
+        info!(
+            user_id = %request.user_id,
+            recipient = ?request.recipient,
+            amount = request.amount,
+            "mock_aptos_transfer"
+        );
+        Ok(transfer.hash)
+    }
+
+    /// 模拟转账一经记录即视为成功
+    async fn confirm_transfer(&self, _transfer: &PendingTransfer) -> Result<TransferState> {
+        Ok(TransferState::Committed)
+    }
+
+    fn max_attempt_duration(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// 按配置选择链上客户端：配置了 `[aptos]` 时提交真实交易，否则仅记录日志。
+#[derive(Clone)]
+pub enum ChainClient {
+    #[cfg(feature = "aptos")]
+    Rest(crate::aptos::RestAptosClient),
+    Logging(LoggingAptosClient),
+}
+
+impl ChainClient {
+    pub fn from_config(config: Option<&AptosConfig>) -> Result<Self> {
+        match config {
+            #[cfg(feature = "aptos")]
+            Some(config) => Ok(Self::Rest(crate::aptos::RestAptosClient::new(config)?)),
+            // 配置了节点却只能模拟转账时拒绝启动，避免误以为已在发币
+            #[cfg(not(feature = "aptos"))]
+            Some(_) => anyhow::bail!(
+                "[aptos] is configured but this build lacks the aptos feature; rebuild with --features faucet-core/aptos"
+            ),
+            None => {
+                warn!("未配置 Aptos 节点，使用模拟转账");
+                Ok(Self::Logging(LoggingAptosClient))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AptosClient for ChainClient {
+    async fn submit_transfer(
+        &self,
+        request: &MintRequest,
+        journal: &dyn TransferJournal,
+    ) -> Result<String> {
+        match self {
+            #[cfg(feature = "aptos")]
+            ChainClient::Rest(client) => client.submit_transfer(request, journal).await,
+            ChainClient::Logging(client) => client.submit_transfer(request, journal).await,
+        }
+    }
+
+    async fn confirm_transfer(&self, transfer: &PendingTransfer) -> Result<TransferState> {
+        match self {
+            #[cfg(feature = "aptos")]
+            ChainClient::Rest(client) => client.confirm_transfer(transfer).await,
+            ChainClient::Logging(client) => client.confirm_transfer(transfer).await,
+        }
+    }
+
+    fn max_attempt_duration(&self) -> Duration {
+        match self {
+            #[cfg(feature = "aptos")]
+            ChainClient::Rest(client) => client.max_attempt_duration(),
+            ChainClient::Logging(client) => client.max_attempt_duration(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::MintMode;
+    use crate::db::memory::MemoryStore;
+    use crate::models::Channel;
+
+    /// 前 `failures` 次提交在广播前失败；随后 `lost_responses` 次提交已经上链，但响应超时。
+    /// `unreachable` 时查询交易结果总是失败
+    struct FlakyClient {
+        failures: usize,
+        lost_responses: usize,
+        unreachable: bool,
+        calls: AtomicUsize,
+        landed: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl AptosClient for FlakyClient {
+        async fn submit_transfer(
+            &self,
+            _request: &MintRequest,
+            journal: &dyn TransferJournal,
+        ) -> Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            if call < self.failures {
+                anyhow::bail!("node unavailable");
+            }
+            let transfer = PendingTransfer {
+                hash: format!("0x{call}"),
+                sequence_number: call as u64,
+                expires_at: Utc::now(),
+            };
+            journal.record(&transfer).await?;
+            self.landed.lock().unwrap().push(transfer.hash.clone());
+            if call < self.failures.saturating_add(self.lost_responses) {
+                anyhow::bail!("request timed out");
+            }
+            Ok(transfer.hash)
+        }
+
+        async fn confirm_transfer(&self, transfer: &PendingTransfer) -> Result<TransferState> {
+            if self.unreachable {
+                anyhow::bail!("node unavailable");
+            }
+            if self.landed.lock().unwrap().contains(&transfer.hash) {
+                Ok(TransferState::Committed)
+            } else {
+                Ok(TransferState::Expired)
+            }
+        }
+
+        fn max_attempt_duration(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+    }
+
+    fn client(failures: usize, lost_responses: usize) -> FlakyClient {
+        FlakyClient {
+            failures,
+            lost_responses,
+            unreachable: false,
+            calls: AtomicUsize::new(0),
+            landed: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    fn config(max_retries: u16) -> QueueConfig {
+        QueueConfig {
+            mode: MintMode::Async,
+            visibility_timeout: Duration::from_secs(30),
+            retry_backoff: Duration::ZERO,
+            max_retries,
+            poll_interval: Duration::from_millis(10),
+        }
+    }
+
+    fn queue_with(client: FlakyClient, max_retries: u16) -> (Arc<MemoryStore>, MintQueue<MemoryStore, FlakyClient>) {
+        let store = Arc::new(MemoryStore::new());
+        let queue = MintQueue::new(store.clone(), Arc::new(client), config(max_retries)).unwrap();
+        (store, queue)
+    }
+
+    fn queue(failures: usize, max_retries: u16) -> (Arc<MemoryStore>, MintQueue<MemoryStore, FlakyClient>) {
+        queue_with(client(failures, 0), max_retries)
+    }
+
+    fn request() -> MintRequest {
+        new_request(&User::new(Channel::Web, "alice"), "0x1".parse().unwrap(), 10)
+    }
+
+    async fn stored(store: &MemoryStore, id: Uuid) -> MintRequest {
+        store.find_request(id).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let (store, queue) = queue(2, 5);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        while queue.process_next().await.unwrap() {}
+
+        let done = stored(&store, request.id).await;
+        assert_eq!(done.status, MintStatus::Completed);
+        assert_eq!(done.attempt, 3);
+        assert_eq!(done.tx_hash.as_deref(), Some("0x2"));
+        assert_eq!(done.error, None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (store, queue) = queue(usize::MAX, 1);
+        let mut request = request();
+        // 跨零点的请求按扣减额度的那一天退还
+        let day = request.requested_at.date_naive().pred_opt().unwrap();
+        request.quota_day = Some(day);
+        store.record_mint(request.account_id, day, request.amount, None).await.unwrap();
+        let request = queue.enqueue(request).await.unwrap();
+
+        while queue.process_next().await.unwrap() {}
+
+        let failed = stored(&store, request.id).await;
+        assert_eq!(failed.status, MintStatus::Failed);
+        assert_eq!(failed.attempt, 2);
+        assert_eq!(failed.error.as_deref(), Some("node unavailable"));
+        let quota = store.fetch_quota(request.account_id, day).await.unwrap().unwrap();
+        assert_eq!(quota.minted_total, 0);
+    }
+
+    #[tokio::test]
+    async fn keeps_quota_when_transfer_is_unconfirmed() {
+        let client = FlakyClient {
+            unreachable: true,
+            ..client(0, usize::MAX)
+        };
+        let (store, queue) = queue_with(client, 0);
+        let request = request();
+        let day = request.charged_day();
+        store.record_mint(request.account_id, day, request.amount, None).await.unwrap();
+        let request = queue.enqueue(request).await.unwrap();
+
+        while queue.process_next().await.unwrap() {}
+
+        // 交易可能已经转出，不能退还额度
+        let failed = stored(&store, request.id).await;
+        assert_eq!(failed.status, MintStatus::Failed);
+        assert_eq!(failed.tx_hash.as_deref(), Some("0x0"));
+        let quota = store.fetch_quota(request.account_id, day).await.unwrap().unwrap();
+        assert_eq!(quota.minted_total, request.amount);
+    }
+
+    #[tokio::test]
+    async fn backoff_delays_retry() {
+        let (store, mut queue) = queue(1, 3);
+        queue.config.retry_backoff = Duration::from_secs(60);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        assert!(queue.process_next().await.unwrap());
+        assert!(!queue.process_next().await.unwrap());
+        assert_eq!(stored(&store, request.id).await.status, MintStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn reclaims_expired_lease() {
+        let (store, queue) = queue(0, 3);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        // 模拟 worker 领取后崩溃：租约立即过期
+        let claimed = store.next_pending(Duration::ZERO).await.unwrap().unwrap();
+        assert_eq!(claimed.status, MintStatus::Processing);
+        assert!(queue.process_next().await.unwrap());
+
+        let done = stored(&store, request.id).await;
+        assert_eq!(done.status, MintStatus::Completed);
+        assert_eq!(done.attempt, 2);
+    }
+
+    #[tokio::test]
+    async fn live_lease_is_not_reclaimed() {
+        let (store, queue) = queue(0, 3);
+        queue.enqueue(request()).await.unwrap();
+
+        store.next_pending(Duration::from_secs(60)).await.unwrap().unwrap();
+        assert!(!queue.process_next().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn timed_out_transfer_is_not_resubmitted() {
+        let (store, queue) = queue_with(client(0, 1), 3);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        // 第一次提交已上链但响应超时，重试时确认到交易而不是再转一次
+        assert!(queue.process_next().await.unwrap());
+        let retried = stored(&store, request.id).await;
+        assert_eq!(retried.status, MintStatus::Pending);
+        assert_eq!(retried.tx_hash.as_deref(), Some("0x0"));
+
+        while queue.process_next().await.unwrap() {}
+        let done = stored(&store, request.id).await;
+        assert_eq!(done.status, MintStatus::Completed);
+        assert_eq!(done.tx_hash.as_deref(), Some("0x0"));
+        assert_eq!(queue.client.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(queue.client.landed.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn reclaimed_lease_confirms_recorded_transfer() {
+        let (store, queue) = queue(0, 3);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        // 模拟 worker 记录并广播交易后崩溃
+        let claimed = store.next_pending(Duration::ZERO).await.unwrap().unwrap();
+        let transfer = PendingTransfer {
+            hash: "0xdead".to_string(),
+            sequence_number: 7,
+            expires_at: Utc::now(),
+        };
+        assert!(store.record_submission(claimed.id, claimed.attempt, &transfer).await.unwrap());
+        queue.client.landed.lock().unwrap().push(transfer.hash.clone());
+
+        assert!(queue.process_next().await.unwrap());
+        let done = stored(&store, request.id).await;
+        assert_eq!(done.status, MintStatus::Completed);
+        assert_eq!(done.tx_hash.as_deref(), Some("0xdead"));
+        assert_eq!(queue.client.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn stale_worker_cannot_broadcast_or_overwrite() {
+        let (store, queue) = queue(0, 3);
+        let request = queue.enqueue(request()).await.unwrap();
+
+        // 第一个 worker 的租约过期，第二个 worker 接手并完成
+        let stale = store.next_pending(Duration::ZERO).await.unwrap().unwrap();
+        assert!(queue.process_next().await.unwrap());
+
+        queue.process(stale).await.unwrap();
+        let done = stored(&store, request.id).await;
+        assert_eq!(done.status, MintStatus::Completed);
+        assert_eq!(done.attempt, 2);
+        assert_eq!(done.error, None);
+        assert_eq!(queue.client.landed.lock().unwrap().len(), 1);
+        let quota = store
+            .fetch_quota(request.account_id, request.requested_at.date_naive())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.success_count, 1);
+    }
+
+    #[test]
+    fn rejects_lease_shorter_than_transfer() {
+        let mut config = config(3);
+        config.visibility_timeout = Duration::from_secs(1);
+        let result = MintQueue::new(Arc::new(MemoryStore::new()), Arc::new(client(0, 0)), config);
+        assert!(result.is_err());
     }
 }
@@ -1,66 +1,307 @@
-use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use chrono::{DateTime, NaiveDate, Utc};
-use tokio::sync::Mutex;
+use tokio::sync::RwLock;
+use tracing::warn;
 use uuid::Uuid;
 
 use crate::config::LimitConfig;
-use crate::models::{Role, User};
-use crate::repository::QuotaRepository;
+use crate::metrics;
+use crate::models::{AccountAddress, LimitConfigUpdate, Role, User};
+use crate::policy::policies_for;
+use crate::repository::{ConfigRepository, MintRepository, QuotaRepository};
 
-pub struct RateLimiter<R> {
+/// 配置文件默认值叠加数据库覆盖项后的生效限额。
+///
+/// 合并结果缓存 `refresh_interval`：本进程通过 `update` 修改时立即刷新，
+/// 其他进程（web、tg-bot、dc-bot 各自一份）在缓存过期后读到新值。
+pub struct LimitSource<R> {
     repo: R,
-    memory: Mutex<HashMap<(Uuid, NaiveDate), u64>>,
-    limits: LimitConfig,
+    defaults: LimitConfig,
+    cache: RwLock<Option<(Instant, LimitConfig)>>,
 }
 
-impl<R: QuotaRepository> RateLimiter<R> {
-    pub fn new(repo: R, limits: LimitConfig) -> Self {
+impl<R: ConfigRepository> LimitSource<R> {
+    pub fn new(repo: R, defaults: LimitConfig) -> Self {
         Self {
             repo,
-            memory: Mutex::new(HashMap::new()),
-            limits,
+            defaults,
+            cache: RwLock::new(None),
         }
     }
 
-    pub fn max_amount(&self, role: &Role) -> u64 {
-        match role {
-            Role::Admin | Role::Privileged => self.limits.privileged_amount,
-            Role::User => self.limits.default_amount,
+    pub fn defaults(&self) -> &LimitConfig {
+        &self.defaults
+    }
+
+    pub async fn current(&self) -> Result<LimitConfig> {
+        if let Some((loaded_at, limits)) = self.cache.read().await.as_ref() {
+            if loaded_at.elapsed() < self.defaults.refresh_interval {
+                return Ok(limits.clone());
+            }
         }
+        self.refresh().await
+    }
+
+    /// 忽略缓存重新读取数据库配置
+    pub async fn refresh(&self) -> Result<LimitConfig> {
+        let mut cache = self.cache.write().await;
+        let limits = match self.repo.get_limit_config().await {
+            Ok(Some(overrides)) => self.merge(&overrides),
+            Ok(None) => self.defaults.clone(),
+            Err(err) => {
+                // 读取失败时沿用上一次的结果，避免数据库抖动导致限额回退
+                warn!(error = %err, "limit_config_refresh_failed");
+                match cache.as_ref() {
+                    Some((_, limits)) => limits.clone(),
+                    None => self.defaults.clone(),
+                }
+            }
+        };
+        *cache = Some((Instant::now(), limits.clone()));
+        Ok(limits)
     }
 
-    fn max_daily_cap(&self, role: &Role) -> Option<u64> {
-        match role {
-            Role::Admin => self.limits.privileged_daily_cap,
-            Role::Privileged => self.limits.privileged_daily_cap,
-            Role::User => Some(self.limits.default_daily_cap),
+    pub async fn update(&self, overrides: &LimitConfigUpdate) -> Result<LimitConfig> {
+        self.repo.update_limit_config(overrides).await?;
+        self.refresh().await
+    }
+
+    fn merge(&self, overrides: &LimitConfigUpdate) -> LimitConfig {
+        LimitConfig {
+            default_amount: overrides.default_amount.unwrap_or(self.defaults.default_amount),
+            default_daily_cap: overrides
+                .default_daily_cap
+                .unwrap_or(self.defaults.default_daily_cap),
+            privileged_amount: overrides
+                .privileged_amount
+                .unwrap_or(self.defaults.privileged_amount),
+            privileged_daily_cap: overrides
+                .privileged_daily_cap
+                .or(self.defaults.privileged_daily_cap),
+            address_daily_cap: overrides
+                .address_daily_cap
+                .or(self.defaults.address_daily_cap),
+            refresh_interval: self.defaults.refresh_interval,
+            policies: self.defaults.policies.clone(),
         }
     }
+}
 
-    pub async fn check_and_record(&self, user: &User, amount: u64) -> Result<()> {
-        let today = Utc::now().date_naive();
-        if amount > self.max_amount(&user.role) {
+/// 每日上限的检查与累加都在存储中原子完成，因此重启或多进程部署时额度依然共享。
+///
+/// 角色配置的 `RatePolicy` 在此之前基于存储中的近期请求判断；它们只读取历史，
+/// 同一用户的并发请求可能同时通过，严格的总量约束仍由每日上限保证。
+pub struct RateLimiter<R> {
+    repo: R,
+    limits: Arc<LimitSource<R>>,
+}
+
+impl<R: QuotaRepository + MintRepository + ConfigRepository> RateLimiter<R> {
+    pub fn new(repo: R, limits: Arc<LimitSource<R>>) -> Self {
+        Self { repo, limits }
+    }
+
+    pub async fn max_amount(&self, role: &Role) -> Result<u64> {
+        Ok(self.limits.current().await?.max_amount(role))
+    }
+
+    /// 通过时返回扣减额度的日期，退还时必须使用同一天
+    pub async fn check_and_record(&self, user: &User, amount: u64) -> Result<NaiveDate> {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let limits = self.limits.current().await?;
+        if amount > limits.max_amount(&user.role) {
+            metrics::rate_limit_rejection("role_amount");
             anyhow::bail!("amount exceeds role limit");
         }
 
-        if let Some(cap) = self.max_daily_cap(&user.role) {
-            let mut guard = self.memory.lock().await;
-            let key = (user.id, today);
-            let entry = guard.entry(key).or_insert(0);
-            if *entry + amount > cap {
-                anyhow::bail!("daily cap reached");
+        let policies = policies_for(limits.policies.for_role(&user.role));
+        if let Some(lookback) = policies.iter().map(|policy| policy.window()).max() {
+            let mut since = now - lookback;
+            if let Some(reset_at) = self.repo.limits_reset_at(user.account_id).await? {
+                since = since.max(reset_at);
+            }
+            let history = self.repo.recent_mints(user.account_id, since).await?;
+            for policy in &policies {
+                if let Err(err) = policy.check(&history, amount, now) {
+                    metrics::rate_limit_rejection(policy.name());
+                    return Err(err);
+                }
             }
-            *entry += amount;
         }
 
-        self.repo.record_mint(user.id, today, amount).await
+        let cap = limits.daily_cap(&user.role);
+        if !self.repo.record_mint(user.account_id, today, amount, cap).await? {
+            metrics::rate_limit_rejection("daily_cap");
+            anyhow::bail!("daily cap reached");
+        }
+        Ok(today)
+    }
+
+    /// 按收款地址累计 `day` 的额度，多个账号向同一钱包领取时共享 `address_daily_cap`。
+    /// `day` 应与账户额度的扣减日期一致，跨零点时两者才会一起退还。
+    pub async fn check_and_record_address(
+        &self,
+        address: &AccountAddress,
+        day: NaiveDate,
+        amount: u64,
+    ) -> Result<()> {
+        let cap = self.limits.current().await?.address_daily_cap;
+        if !self
+            .repo
+            .record_address_mint(address, day, amount, cap)
+            .await?
+        {
+            metrics::rate_limit_rejection("address_daily_cap");
+            anyhow::bail!("address daily cap reached");
+        }
+        Ok(())
     }
 }
 
+/// 重置账户的限流策略状态，`when` 之前的请求不再计入滑动窗口、冷却与突发限制。
 #[async_trait]
 pub trait LimitRefresh {
-    async fn refresh(&self, user_id: Uuid, when: DateTime<Utc>) -> Result<()>;
+    async fn refresh(&self, account_id: Uuid, when: DateTime<Utc>) -> Result<()>;
+}
+
+#[async_trait]
+impl<R> LimitRefresh for RateLimiter<R>
+where
+    R: QuotaRepository + MintRepository + ConfigRepository,
+{
+    async fn refresh(&self, account_id: Uuid, when: DateTime<Utc>) -> Result<()> {
+        // 记录在存储中，其他进程的限流器同样可见；当日累计额度不受影响
+        self.repo.set_limits_reset(account_id, when).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::{RatePolicyConfig, RolePolicies};
+    use crate::db::memory::MemoryStore;
+    use crate::models::Channel;
+    use crate::queue::new_request;
+
+    fn limits(refresh_interval: Duration) -> LimitConfig {
+        LimitConfig {
+            default_amount: 10,
+            default_daily_cap: 100,
+            privileged_amount: 10,
+            privileged_daily_cap: None,
+            address_daily_cap: Some(100),
+            refresh_interval,
+            policies: RolePolicies {
+                user: RatePolicyConfig {
+                    cooldown: Some(Duration::from_secs(600)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    fn user() -> User {
+        User::new(Channel::Web, "alice")
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn daily_cap_holds_under_concurrency() {
+        let store = Arc::new(MemoryStore::new());
+        let source = Arc::new(LimitSource::new(store.clone(), limits(Duration::from_secs(30))));
+        let limiter = Arc::new(RateLimiter::new(store.clone(), source));
+        let user = user();
+
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let limiter = limiter.clone();
+                let user = user.clone();
+                tokio::spawn(async move { limiter.check_and_record(&user, 10).await.is_ok() })
+            })
+            .collect();
+        let mut accepted = 0;
+        for task in tasks {
+            if task.await.unwrap() {
+                accepted += 1;
+            }
+        }
+
+        assert_eq!(accepted, 10);
+        let quota = store
+            .fetch_quota(user.account_id, Utc::now().date_naive())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(quota.minted_total, 100);
+    }
+
+    #[tokio::test]
+    async fn enforces_limits_updated_at_runtime() {
+        let store = Arc::new(MemoryStore::new());
+        let admin = Arc::new(LimitSource::new(store.clone(), limits(Duration::from_secs(3600))));
+        // 另一个进程中的缓存，TTL 过期前仍使用旧值
+        let bot = Arc::new(LimitSource::new(store.clone(), limits(Duration::from_secs(3600))));
+        let limiter = RateLimiter::new(store.clone(), admin.clone());
+        let user = user();
+        assert_eq!(bot.current().await.unwrap().default_amount, 10);
+
+        admin
+            .update(&LimitConfigUpdate {
+                default_amount: Some(50),
+                address_daily_cap: Some(200),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        limiter.check_and_record(&user, 50).await.unwrap();
+        assert_eq!(limiter.max_amount(&user.role).await.unwrap(), 50);
+        assert_eq!(admin.current().await.unwrap().address_daily_cap, Some(200));
+        assert_eq!(bot.current().await.unwrap().default_amount, 10);
+
+        let expired = LimitSource::new(store.clone(), limits(Duration::ZERO));
+        assert_eq!(expired.current().await.unwrap().default_amount, 50);
+    }
+
+    #[tokio::test]
+    async fn refresh_clears_cooldown() {
+        let store = Arc::new(MemoryStore::new());
+        let source = Arc::new(LimitSource::new(store.clone(), limits(Duration::from_secs(30))));
+        let limiter = RateLimiter::new(store.clone(), source);
+        let user = user();
+        let request = new_request(&user, "0x1".parse().unwrap(), 1);
+        store.enqueue(&request).await.unwrap();
+
+        let err = limiter.check_and_record(&user, 1).await.unwrap_err();
+        assert!(err.to_string().contains("cooldown"));
+
+        limiter.refresh(user.account_id, Utc::now()).await.unwrap();
+        limiter.check_and_record(&user, 1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn address_cap_is_shared_across_users() {
+        let store = Arc::new(MemoryStore::new());
+        let source = Arc::new(LimitSource::new(store.clone(), limits(Duration::from_secs(30))));
+        let limiter = RateLimiter::new(store.clone(), source);
+        let wallet: AccountAddress = "0xcafe".parse().unwrap();
+        let today = Utc::now().date_naive();
+
+        limiter.check_and_record_address(&wallet, today, 60).await.unwrap();
+        let err = limiter
+            .check_and_record_address(&wallet, today, 60)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("address daily cap"));
+        limiter
+            .check_and_record_address(&"0xbeef".parse().unwrap(), today, 60)
+            .await
+            .unwrap();
+    }
 }
@@ -1,39 +1,188 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
 use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::BoxStream;
 use uuid::Uuid;
 
-use crate::models::{MintOutcome, MintRequest, MintStatus, Quota, Role, User, SystemConfig, LimitConfigUpdate};
+use crate::models::{
+    AccountAddress, AddressQuota, LinkCode, LoginNonce, MintFailure, MintOutcome, MintRequest, MintStatus,
+    PendingTransfer, Quota, RefreshToken, Role, User, SystemConfig, LimitConfigUpdate,
+};
+
+/// 定义一个仓储 trait，并由 [`crate::db::forward_repository`] 生成 `Arc<T>` 与
+/// [`crate::db::instrumented::InstrumentedStore`] 的转发实现；新增方法只需写在 trait 中并由各后端实现。
+macro_rules! repository {
+    (
+        $(#[$meta:meta])*
+        pub trait $trait:ident { $($body:tt)* }
+    ) => {
+        $(#[$meta])*
+        #[async_trait]
+        pub trait $trait: Send + Sync { $($body)* }
+
+        $crate::db::forward_repository!($trait { $($body)* });
+    };
+}
+
+repository! {
+    pub trait UserRepository {
+        /// 写入身份；账户不存在时以 `user.role` 创建，已有账户的角色只由 `set_role` 修改。
+        async fn upsert_user(&self, user: &User) -> anyhow::Result<()>;
+        async fn find_user(&self, channel: &str, handle: &str) -> anyhow::Result<Option<User>>;
+        async fn set_role(&self, account_id: Uuid, role: Role) -> anyhow::Result<()>;
+        /// 将身份转移到另一个账户
+        async fn link_user(&self, user_id: Uuid, account_id: Uuid) -> anyhow::Result<()>;
+        /// 修改身份的 handle，账户与角色不变
+        async fn rename_user(&self, user_id: Uuid, handle: &str) -> anyhow::Result<()>;
+        async fn create_link_code(&self, code: &LinkCode) -> anyhow::Result<()>;
+        /// 取出并删除未过期的关联码，同一个码只能成功使用一次。
+        async fn consume_link_code(
+            &self,
+            code: &str,
+            now: DateTime<Utc>,
+        ) -> anyhow::Result<Option<LinkCode>>;
+    }
+}
+
+repository! {
+    pub trait MintRepository {
+        /// 写入请求，无论传入的状态如何都以 `pending` 保存。
+        async fn enqueue(&self, request: &MintRequest) -> anyhow::Result<()>;
+        /// 领取最早的一条到期请求：`pending` 且已过退避时间，或 `processing` 但租约已过期。
+        /// 领取后状态置为 `processing`、`attempt` 加一，并持有 `lease` 时长的租约。
+        async fn next_pending(&self, lease: Duration) -> anyhow::Result<Option<MintRequest>>;
+        async fn update_status(&self, request_id: Uuid, status: MintStatus) -> anyhow::Result<()>;
+        /// 广播前记下签名后的交易。仅当请求仍为 `processing` 且 `attempt` 未被其他 worker
+        /// 重新领取时写入，返回 `false` 表示租约已丢失、不得广播。
+        async fn record_submission(
+            &self,
+            request_id: Uuid,
+            attempt: u16,
+            transfer: &PendingTransfer,
+        ) -> anyhow::Result<bool>;
+        /// 写入最终结果。仅当存储中的 `attempt` 仍为 `outcome.request.attempt` 时生效，
+        /// 返回 `false` 表示该次尝试的租约已被其他 worker 接管。
+        async fn record_outcome(&self, outcome: &MintOutcome) -> anyhow::Result<bool>;
+        /// 将失败的请求放回 `pending`，在 `available_at` 之后才能被再次领取。
+        /// 与 `record_outcome` 一样只对持有租约的 `attempt` 生效，已记录的交易保留供下次确认。
+        async fn schedule_retry(
+            &self,
+            request_id: Uuid,
+            attempt: u16,
+            error: &str,
+            available_at: DateTime<Utc>,
+        ) -> anyhow::Result<bool>;
+        async fn find_request(&self, request_id: Uuid) -> anyhow::Result<Option<MintRequest>>;
+        /// 按 `(requested_at, id)` 倒序返回账户下所有身份的请求，`before` 为上一页最后一条的游标。
+        async fn list_requests_for_account(
+            &self,
+            account_id: Uuid,
+            before: Option<MintCursor>,
+            limit: usize,
+        ) -> anyhow::Result<Vec<MintRequest>>;
+        /// 按时间升序返回 `since` 之后账户未失败的请求，供滑动窗口类限流策略使用。
+        async fn recent_mints(
+            &self,
+            account_id: Uuid,
+            since: DateTime<Utc>,
+        ) -> anyhow::Result<Vec<MintRequest>>;
+        /// 处于 `pending` 的请求数，包括等待退避的重试。
+        async fn pending_count(&self) -> anyhow::Result<u64>;
+    }
+}
+
+/// 铸币历史的分页游标，对应上一页最后一条记录的 `(requested_at, id)`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MintCursor {
+    pub requested_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl MintCursor {
+    pub fn from_request(request: &MintRequest) -> Self {
+        Self {
+            requested_at: request.requested_at,
+            id: request.id,
+        }
+    }
 
-#[async_trait]
-pub trait UserRepository: Send + Sync {
-    async fn upsert_user(&self, user: &User) -> anyhow::Result<()>;
-    async fn find_user(&self, channel: &str, handle: &str) -> anyhow::Result<Option<User>>;
-    async fn set_role(&self, user_id: Uuid, role: Role) -> anyhow::Result<()>;
+    /// 游标是否排在 `request` 之后（即 `request` 属于下一页）
+    pub fn is_after(&self, request: &MintRequest) -> bool {
+        (request.requested_at, request.id) < (self.requested_at, self.id)
+    }
 }
 
-#[async_trait]
-pub trait MintRepository: Send + Sync {
-    async fn enqueue(&self, request: &MintRequest) -> anyhow::Result<()>;
-    async fn next_pending(&self) -> anyhow::Result<Option<MintRequest>>;
-    async fn update_status(&self, request_id: Uuid, status: MintStatus) -> anyhow::Result<()>;
-    async fn record_outcome(&self, outcome: &MintOutcome) -> anyhow::Result<()>;
+impl std::fmt::Display for MintCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 纳秒精度保证内存存储中的时间戳可以无损往返
+        let nanos = self.requested_at.timestamp_nanos_opt().unwrap_or(i64::MAX);
+        write!(f, "{nanos}_{}", self.id.simple())
+    }
+}
+
+impl std::str::FromStr for MintCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (nanos, id) = s
+            .split_once('_')
+            .ok_or_else(|| anyhow::anyhow!("invalid cursor: {s}"))?;
+        Ok(Self {
+            requested_at: DateTime::from_timestamp_nanos(nanos.parse()?),
+            id: Uuid::parse_str(id)?,
+        })
+    }
 }
 
-#[async_trait]
-pub trait QuotaRepository: Send + Sync {
-    async fn record_mint(&self, user_id: Uuid, day: NaiveDate, amount: u64) -> anyhow::Result<()>;
-    async fn fetch_quota(&self, user_id: Uuid, day: NaiveDate) -> anyhow::Result<Option<Quota>>;
+repository! {
+    pub trait QuotaRepository {
+        /// 原子地累加账户当日额度；设置了 `cap` 且累加后会超出时不写入并返回 `false`。
+        async fn record_mint(
+            &self,
+            account_id: Uuid,
+            day: NaiveDate,
+            amount: u64,
+            cap: Option<u64>,
+        ) -> anyhow::Result<bool>;
+        /// 退还 `record_mint` 记下的额度，用于转账失败后的补偿，最多减到 0。
+        async fn release_mint(
+            &self,
+            account_id: Uuid,
+            day: NaiveDate,
+            amount: u64,
+        ) -> anyhow::Result<()>;
+        async fn fetch_quota(&self, account_id: Uuid, day: NaiveDate) -> anyhow::Result<Option<Quota>>;
+        /// 与 `record_mint` 相同，但按收款地址累计，防止多个账号向同一钱包叠加额度。
+        async fn record_address_mint(
+            &self,
+            address: &AccountAddress,
+            day: NaiveDate,
+            amount: u64,
+            cap: Option<u64>,
+        ) -> anyhow::Result<bool>;
+        async fn release_address_mint(
+            &self,
+            address: &AccountAddress,
+            day: NaiveDate,
+            amount: u64,
+        ) -> anyhow::Result<()>;
+        /// 记录账户限流策略的重置时间，每个账户只保留最近一次。
+        async fn set_limits_reset(&self, account_id: Uuid, at: DateTime<Utc>) -> anyhow::Result<()>;
+        async fn limits_reset_at(&self, account_id: Uuid) -> anyhow::Result<Option<DateTime<Utc>>>;
+    }
 }
 
-#[async_trait]
-pub trait ReportingRepository: Send + Sync {
-    async fn daily_summary(&self, day: NaiveDate) -> anyhow::Result<Vec<DailyReportRow>>;
-    async fn log_failure(
-        &self,
-        request_id: Uuid,
-        when: DateTime<Utc>,
-        reason: &str,
-    ) -> anyhow::Result<()>;
+repository! {
+    pub trait ReportingRepository {
+        async fn daily_summary(&self, day: NaiveDate) -> anyhow::Result<Vec<DailyReportRow>>;
+        async fn log_failure(
+            &self,
+            request_id: Uuid,
+            when: DateTime<Utc>,
+            reason: &str,
+        ) -> anyhow::Result<()>;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -44,11 +193,125 @@ pub struct DailyReportRow {
     pub failure_count: u64,
 }
 
-#[async_trait]
-pub trait ConfigRepository: Send + Sync {
-    async fn get_config(&self, key: &str) -> anyhow::Result<Option<SystemConfig>>;
-    async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> anyhow::Result<()>;
-    async fn get_all_configs(&self) -> anyhow::Result<Vec<SystemConfig>>;
-    async fn update_limit_config(&self, config: &LimitConfigUpdate) -> anyhow::Result<()>;
-    async fn get_limit_config(&self) -> anyhow::Result<Option<LimitConfigUpdate>>;
+repository! {
+    pub trait ConfigRepository {
+        async fn get_config(&self, key: &str) -> anyhow::Result<Option<SystemConfig>>;
+        async fn set_config(&self, key: &str, value: &str, description: Option<&str>) -> anyhow::Result<()>;
+        async fn get_all_configs(&self) -> anyhow::Result<Vec<SystemConfig>>;
+        async fn update_limit_config(&self, config: &LimitConfigUpdate) -> anyhow::Result<()>;
+        async fn get_limit_config(&self) -> anyhow::Result<Option<LimitConfigUpdate>>;
+    }
+}
+
+repository! {
+    /// 网页会话：刷新令牌与提前吊销的访问令牌（按 `jti` 记录）。
+    ///
+    /// 两者都带过期时间，过期的记录由写入时顺带清理。
+    pub trait SessionRepository {
+        async fn create_refresh_token(&self, token: &RefreshToken) -> anyhow::Result<()>;
+        /// 取出并删除未过期的刷新令牌，同一个令牌只能成功使用一次。
+        async fn consume_refresh_token(
+            &self,
+            token_hash: &str,
+            now: DateTime<Utc>,
+        ) -> anyhow::Result<Option<RefreshToken>>;
+        /// 删除会话的全部刷新令牌
+        async fn revoke_session(&self, session_id: Uuid) -> anyhow::Result<()>;
+        /// 在 `expires_at` 之前拒绝该访问令牌
+        async fn revoke_token(&self, jti: &str, expires_at: DateTime<Utc>) -> anyhow::Result<()>;
+        async fn is_token_revoked(&self, jti: &str, now: DateTime<Utc>) -> anyhow::Result<bool>;
+        /// 记下登录成功用过的挑战并保留到它过期，同时清理已过期的记录。
+        /// 挑战此前已被记下时返回 `false`，同一个挑战只能成功登录一次。
+        async fn use_login_nonce(&self, nonce: &LoginNonce, now: DateTime<Utc>) -> anyhow::Result<bool>;
+    }
+}
+
+repository! {
+    /// 在存储后端之间迁移数据时逐表读出与原样写入。
+    ///
+    /// 导出以流的形式返回，不会把整张表读进内存；导入保留原有的 id 与时间戳，
+    /// 记录（或其唯一键）已存在时不做修改并返回 `false`。关联码与会话只在短时间内有效，不参与迁移。
+    pub trait TransferRepository {
+        /// 用户的角色为所属账户的角色
+        fn export_users(&self) -> BoxStream<'_, anyhow::Result<User>>;
+        fn export_requests(&self) -> BoxStream<'_, anyhow::Result<MintRequest>>;
+        fn export_quotas(&self) -> BoxStream<'_, anyhow::Result<Quota>>;
+        fn export_address_quotas(&self) -> BoxStream<'_, anyhow::Result<AddressQuota>>;
+        fn export_failures(&self) -> BoxStream<'_, anyhow::Result<MintFailure>>;
+        fn export_configs(&self) -> BoxStream<'_, anyhow::Result<SystemConfig>>;
+        /// 账户不存在时以用户的角色创建
+        async fn import_user(&self, user: &User) -> anyhow::Result<bool>;
+        /// 与 `enqueue` 不同，按原状态保存
+        async fn import_request(&self, request: &MintRequest) -> anyhow::Result<bool>;
+        async fn import_quota(&self, quota: &Quota) -> anyhow::Result<bool>;
+        async fn import_address_quota(&self, quota: &AddressQuota) -> anyhow::Result<bool>;
+        async fn import_failure(&self, failure: &MintFailure) -> anyhow::Result<bool>;
+        async fn import_config(&self, config: &SystemConfig) -> anyhow::Result<bool>;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::MemoryStore;
+    use crate::models::Channel;
+    use crate::queue::new_request;
+
+    #[test]
+    fn cursor_round_trips() {
+        let request = new_request(&User::new(Channel::Web, "alice"), "0x1".parse().unwrap(), 1);
+        let cursor = MintCursor::from_request(&request);
+        assert_eq!(cursor.to_string().parse::<MintCursor>().unwrap(), cursor);
+        assert!("not-a-cursor".parse::<MintCursor>().is_err());
+    }
+
+    #[tokio::test]
+    async fn pages_through_history() {
+        let store = MemoryStore::new();
+        let user = User::new(Channel::Web, "alice");
+        let account_id = user.account_id;
+        for _ in 0..5 {
+            let request = new_request(&user, "0x1".parse().unwrap(), 1);
+            store.enqueue(&request).await.unwrap();
+        }
+        let other = new_request(&User::new(Channel::Web, "bob"), "0x1".parse().unwrap(), 1);
+        store.enqueue(&other).await.unwrap();
+
+        let mut seen = Vec::new();
+        let mut before = None;
+        loop {
+            let page = store.list_requests_for_account(account_id, before, 2).await.unwrap();
+            let Some(last) = page.last() else { break };
+            before = Some(MintCursor::from_request(last));
+            seen.extend(page.into_iter().map(|request| (request.requested_at, request.id)));
+        }
+
+        assert_eq!(seen.len(), 5);
+        assert!(seen.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    #[tokio::test]
+    async fn linked_identities_share_account() {
+        let store = MemoryStore::new();
+        let web = User::new(Channel::Web, "alice@example.com");
+        let bot = User::new(Channel::Telegram, "alice");
+        store.upsert_user(&web).await.unwrap();
+        store.upsert_user(&bot).await.unwrap();
+
+        let now = Utc::now();
+        let code = LinkCode {
+            code: "ABCD1234".to_string(),
+            account_id: web.account_id,
+            expires_at: now + chrono::Duration::minutes(10),
+        };
+        store.create_link_code(&code).await.unwrap();
+        let link = store.consume_link_code("ABCD1234", now).await.unwrap().unwrap();
+        assert!(store.consume_link_code("ABCD1234", now).await.unwrap().is_none());
+        store.link_user(bot.id, link.account_id).await.unwrap();
+        store.set_role(web.account_id, Role::Privileged).await.unwrap();
+
+        let linked = store.find_user("telegram", "alice").await.unwrap().unwrap();
+        assert_eq!(linked.account_id, web.account_id);
+        assert_eq!(linked.role, Role::Privileged);
+    }
 }
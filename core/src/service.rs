@@ -1,18 +1,39 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use anyhow::Result;
-use chrono::Utc;
-use tracing::{info, warn};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
-    config::{AuthConfig, LimitConfig},
-    models::{Channel, MintOutcome, MintStatus, Role, User},
-    queue::{new_request, AptosClient},
-    rate_limit::RateLimiter,
-    repository::{MintRepository, QuotaRepository, ReportingRepository, UserRepository, ConfigRepository},
+    config::{AuthConfig, LimitConfig, MintMode, QueueConfig},
+    db::Store,
+    logging, metrics,
+    models::{
+        AccountAddress, Channel, LinkCode, LoginNonce, MintOutcome, MintRequest, MintStatus,
+        RefreshToken, Role, User,
+    },
+    queue::{new_request, release_quota, AptosClient, LeaseJournal, MintQueue, TransferState},
+    rate_limit::{LimitRefresh, LimitSource, RateLimiter},
+    repository::{
+        ConfigRepository, MintCursor, MintRepository, QuotaRepository, ReportingRepository,
+        UserRepository,
+    },
 };
 
+const LINK_CODE_BYTES: usize = 16;
+const LINK_CODE_TTL: std::time::Duration = std::time::Duration::from_secs(600);
+/// 同一身份在 `LINK_LOCKOUT` 内输错关联码的次数上限，达到后锁定一个 `LINK_LOCKOUT`
+const LINK_MAX_FAILURES: u32 = 5;
+const LINK_LOCKOUT: chrono::Duration = chrono::Duration::minutes(15);
+const REFRESH_TOKEN_BYTES: usize = 32;
+
 #[derive(Debug, Clone)]
 pub struct Identity<'a> {
     pub channel: Channel,
@@ -20,86 +41,104 @@ pub struct Identity<'a> {
     pub domain: Option<&'a str>,
 }
 
-pub struct FaucetService<S, C>
+pub struct FaucetService<C>
 where
-    S: UserRepository
-        + MintRepository
-        + QuotaRepository
-        + ReportingRepository
-        + Send
-        + Sync
-        + 'static,
-    C: AptosClient,
+    C: AptosClient + 'static,
 {
-    store: Arc<S>,
+    store: Arc<dyn Store>,
     client: Arc<C>,
-    limits: LimitConfig,
+    limits: Arc<LimitSource<Arc<dyn Store>>>,
     privileged_domains: HashSet<String>,
-    rate_limiter: RateLimiter<Arc<S>>,
+    /// OIDC 提供方名 -> 该提供方的 privileged 域名
+    provider_domains: HashMap<String, HashSet<String>>,
+    rate_limiter: RateLimiter<Arc<dyn Store>>,
+    mode: MintMode,
+    visibility_timeout: std::time::Duration,
+    queue: MintQueue<dyn Store, C>,
+    /// 身份 id -> (输错次数, 计数窗口结束时间)，只在本进程内统计
+    link_failures: DashMap<Uuid, (u32, DateTime<Utc>)>,
 }
 
-impl<S, C> FaucetService<S, C>
+impl<C> FaucetService<C>
 where
-    S: UserRepository
-        + MintRepository
-        + QuotaRepository
-        + ReportingRepository
-        + ConfigRepository
-        + Send
-        + Sync
-        + 'static,
-    C: AptosClient,
+    C: AptosClient + 'static,
 {
-    pub fn new(store: Arc<S>, client: Arc<C>, limits: LimitConfig, auth: &AuthConfig) -> Self {
-        let privileged_domains = auth
-            .privileged_domains
+    pub fn new(
+        store: Arc<dyn Store>,
+        client: Arc<C>,
+        limits: LimitConfig,
+        auth: &AuthConfig,
+        queue: &QueueConfig,
+    ) -> Result<Self> {
+        let privileged_domains = lowercase(&auth.privileged_domains);
+        let provider_domains = auth
+            .oidc
             .iter()
-            .map(|d| d.to_ascii_lowercase())
-            .collect::<HashSet<_>>();
+            .map(|provider| (provider.name.clone(), lowercase(&provider.privileged_domains)))
+            .collect();
 
+        let limits = Arc::new(LimitSource::new(store.clone(), limits));
         let rate_limiter = RateLimiter::new(store.clone(), limits.clone());
+        let mint_queue = MintQueue::new(store.clone(), client.clone(), queue.clone())?;
 
-        Self {
+        Ok(Self {
             store,
             client,
             limits,
             privileged_domains,
+            provider_domains,
             rate_limiter,
-        }
+            mode: queue.mode,
+            visibility_timeout: queue.visibility_timeout,
+            queue: mint_queue,
+            link_failures: DashMap::new(),
+        })
+    }
+
+    pub fn mode(&self) -> MintMode {
+        self.mode
     }
 
+    /// 异步模式下由调用方 `spawn` 的后台 worker
+    pub fn queue(&self) -> MintQueue<dyn Store, C> {
+        self.queue.clone()
+    }
+
+    /// 配置文件中的默认限额
     pub fn limits(&self) -> &LimitConfig {
-        &self.limits
+        self.limits.defaults()
     }
 
-    pub fn max_amount_for_role(&self, role: &Role) -> u64 {
-        self.rate_limiter.max_amount(role)
+    pub async fn max_amount_for_role(&self, role: &Role) -> Result<u64> {
+        self.rate_limiter.max_amount(role).await
     }
 
+    /// 叠加数据库覆盖项后的生效限额，与 `RateLimiter` 使用同一份缓存
     pub async fn get_dynamic_limits(&self) -> Result<LimitConfig> {
-        // 首先尝试从数据库获取配置
-        if let Ok(Some(db_config)) = self.store.get_limit_config().await {
-            // 如果数据库有配置，使用数据库配置，否则使用默认配置
-            Ok(LimitConfig {
-                default_amount: db_config.default_amount.unwrap_or(self.limits.default_amount),
-                default_daily_cap: db_config.default_daily_cap.unwrap_or(self.limits.default_daily_cap),
-                privileged_amount: db_config.privileged_amount.unwrap_or(self.limits.privileged_amount),
-                privileged_daily_cap: db_config.privileged_daily_cap.or(self.limits.privileged_daily_cap),
-            })
-        } else {
-            // 如果数据库没有配置，使用默认配置
-            Ok(self.limits.clone())
+        self.limits.current().await
+    }
+
+    /// 身份所属提供方的 privileged 域名：OIDC 身份（handle 为 `<name>:<sub>`）使用该提供方的配置，
+    /// Google 身份使用 `auth.privileged_domains`，其他带前缀的身份没有
+    fn privileged_domains_for(&self, identity: &Identity<'_>) -> Option<&HashSet<String>> {
+        if identity.channel != Channel::Web {
+            return None;
+        }
+        match identity.handle.split_once(':') {
+            Some((provider, _)) => self.provider_domains.get(provider),
+            None => Some(&self.privileged_domains),
         }
     }
 
-    fn determine_role(&self, existing: Option<&Role>, domain: Option<&str>) -> Role {
+    fn determine_role(&self, existing: Option<&Role>, identity: &Identity<'_>) -> Role {
         if matches!(existing, Some(Role::Admin)) {
             return Role::Admin;
         }
 
-        if let Some(domain) = domain {
-            let clean = domain.to_ascii_lowercase();
-            if self.privileged_domains.contains(&clean) {
+        if let (Some(domain), Some(privileged)) =
+            (identity.domain, self.privileged_domains_for(identity))
+        {
+            if privileged.contains(&domain.to_ascii_lowercase()) {
                 return Role::Privileged;
             }
         }
@@ -114,8 +153,12 @@ where
             .await?
         {
             let mut changed = false;
-            let determined_role = self.determine_role(Some(&user.role), identity.domain);
+            let determined_role = self.determine_role(Some(&user.role), &identity);
             if determined_role != user.role {
+                // 账户角色只通过 set_role 修改，upsert_user 不会覆盖
+                self.store
+                    .set_role(user.account_id, determined_role.clone())
+                    .await?;
                 user.role = determined_role;
                 changed = true;
             }
@@ -133,20 +176,35 @@ where
             self.store.upsert_user(&user).await?;
             Ok(user)
         } else {
-            let mut user = User {
-                id: Uuid::new_v4(),
-                channel: identity.channel.clone(),
-                handle: identity.handle.to_string(),
-                role: Role::User,
-                domain: identity.domain.map(|s| s.to_string()),
-                last_seen_at: Utc::now(),
-            };
-            user.role = self.determine_role(None, identity.domain);
+            let mut user = User::new(identity.channel.clone(), identity.handle);
+            user.domain = identity.domain.map(|s| s.to_string());
+            user.role = self.determine_role(None, &identity);
             self.store.upsert_user(&user).await?;
             Ok(user)
         }
     }
 
+    /// 把以 `legacy_handle` 保存的身份改为 `handle`，`handle` 已有身份时不做处理。
+    ///
+    /// Telegram 身份曾以可修改、可被他人重新注册的用户名为 handle，现改用不可变的数字 id，
+    /// 旧记录在用户下次发消息时迁移。
+    pub async fn migrate_handle(
+        &self,
+        channel: Channel,
+        legacy_handle: &str,
+        handle: &str,
+    ) -> Result<()> {
+        let channel = channel.as_str();
+        if legacy_handle == handle || self.store.find_user(channel, handle).await?.is_some() {
+            return Ok(());
+        }
+        if let Some(user) = self.store.find_user(channel, legacy_handle).await? {
+            self.store.rename_user(user.id, handle).await?;
+            info!(channel, from = legacy_handle, to = handle, "user_handle_migrated");
+        }
+        Ok(())
+    }
+
     pub async fn set_role(
         &self,
         actor: &User,
@@ -162,36 +220,95 @@ where
             .store
             .find_user(target_channel.as_str(), target_handle)
             .await?
-            .unwrap_or(User {
-                id: Uuid::new_v4(),
-                channel: target_channel.clone(),
-                handle: target_handle.to_string(),
-                role: Role::User,
-                domain: None,
-                last_seen_at: Utc::now(),
-            });
-        user.role = role;
+            .unwrap_or_else(|| User::new(target_channel.clone(), target_handle));
+        // 角色属于账户，已关联的其他身份同时生效
+        user.role = role.clone();
         user.last_seen_at = Utc::now();
         self.store.upsert_user(&user).await?;
+        self.store.set_role(user.account_id, role).await?;
         Ok(user)
     }
 
-    pub async fn mint(&self, user: &User, amount: u64) -> Result<MintOutcome> {
+    #[instrument(
+        name = "faucet.mint",
+        skip_all,
+        fields(
+            channel = user.channel.as_str(),
+            user = %user.handle,
+            %recipient,
+            amount,
+            http.request_id = logging::current_request_id(),
+        )
+    )]
+    pub async fn mint(
+        &self,
+        user: &User,
+        recipient: AccountAddress,
+        amount: u64,
+    ) -> Result<MintOutcome> {
         if amount == 0 {
             anyhow::bail!("amount must be greater than zero");
         }
 
-        self.rate_limiter.check_and_record(user, amount).await?;
+        let day = self.rate_limiter.check_and_record(user, amount).await?;
+        if let Err(err) = self
+            .rate_limiter
+            .check_and_record_address(&recipient, day, amount)
+            .await
+        {
+            // 地址额度不足时退还刚记下的用户额度
+            self.store.release_mint(user.account_id, day, amount).await?;
+            return Err(err);
+        }
+
+        let mut request = new_request(user, recipient, amount);
+        request.quota_day = Some(day);
+        request.traceparent = logging::current_traceparent();
+        request.http_request_id = logging::current_request_id();
+        if self.mode == MintMode::Async {
+            let request = self
+                .release_on_error(&request.clone(), self.queue.enqueue(request))
+                .await?;
+            metrics::mint(&user.channel, &user.role, "queued");
+            info!(user = %user.handle, request_id = %request.id, %recipient, "mint_enqueued");
+            return Ok(MintOutcome {
+                request,
+                tx_hash: None,
+            });
+        }
 
-        let mut request = new_request(user.id, user.channel.clone(), amount);
-        self.store.enqueue(&request).await?;
-        self.store
-            .update_status(request.id, MintStatus::Processing)
-            .await?;
+        // 同步模式同样以第一次尝试持有租约入库，结果按 attempt 条件写入；
+        // 进程在提交途中退出时，共用存储的异步 worker 可在租约过期后接手确认
+        request.attempt = 1;
+        request.available_at =
+            Some(Utc::now() + chrono::Duration::from_std(self.visibility_timeout)?);
+        self.release_on_error(&request, async {
+            self.store.enqueue(&request).await?;
+            self.store
+                .update_status(request.id, MintStatus::Processing)
+                .await
+        })
+        .await?;
         request.status = MintStatus::Processing;
-        request.attempt = request.attempt.saturating_add(1);
 
-        match self.client.submit_transfer(&request).await {
+        let journal = LeaseJournal::new(self.store.as_ref(), &request);
+        let submitted = metrics::time_transfer(self.client.submit_transfer(&request, &journal)).await;
+        // 提交失败时已记录的交易仍可能上链：确认已上链按成功处理，结果未知时保留额度
+        let (result, refund) = match (submitted, journal.unsettled()) {
+            (Ok(hash), _) => (Ok(hash), false),
+            (Err(err), None) => (Err(err), true),
+            (Err(err), Some(transfer)) => match self.client.confirm_transfer(&transfer).await {
+                Ok(TransferState::Committed) => (Ok(transfer.hash), false),
+                Ok(TransferState::Aborted(_) | TransferState::Expired) => (Err(err), true),
+                Err(confirm_err) => {
+                    warn!(request_id = %request.id, hash = %transfer.hash, error = %confirm_err, "mint_transfer_unconfirmed");
+                    request.tx_hash = Some(transfer.hash);
+                    (Err(err), false)
+                }
+            },
+        };
+
+        match result {
             Ok(hash) => {
                 request.status = MintStatus::Completed;
                 request.tx_hash = Some(hash.clone());
@@ -201,53 +318,91 @@ where
                     request: request.clone(),
                     tx_hash: Some(hash.clone()),
                 };
-                self.store.record_outcome(&outcome).await?;
-                info!(user = %user.handle, ?hash, "mint_success");
+                if !self.store.record_outcome(&outcome).await? {
+                    warn!(request_id = %request.id, "mint_lease_lost");
+                }
+                metrics::mint(&user.channel, &user.role, "completed");
+                info!(user = %user.handle, %recipient, ?hash, "mint_success");
                 Ok(outcome)
             }
             Err(err) => {
                 let error_message = err.to_string();
-                warn!(user = %user.handle, error = %error_message, "mint_failed");
+                warn!(user = %user.handle, error = %error_message, refund, "mint_failed");
+                metrics::mint(&user.channel, &user.role, "failed");
                 request.status = MintStatus::Failed;
                 request.error = Some(error_message.clone());
                 request.processed_at = Some(Utc::now());
 
                 let outcome = MintOutcome {
                     request: request.clone(),
-                    tx_hash: None,
+                    tx_hash: request.tx_hash.clone(),
                 };
 
-                self.store.record_outcome(&outcome).await?;
-                self.store
-                    .log_failure(request.id, Utc::now(), &error_message)
-                    .await?;
+                // 租约已被 worker 接管时结果与额度由它处理
+                if self.store.record_outcome(&outcome).await? {
+                    self.store
+                        .log_failure(request.id, Utc::now(), &error_message)
+                        .await?;
+                    if refund {
+                        release_quota(self.store.as_ref(), &request).await?;
+                    }
+                }
 
                 Err(err)
             }
         }
     }
 
+    /// 请求未能入队时退还已扣的额度，返回原来的错误
+    async fn release_on_error<T>(
+        &self,
+        request: &MintRequest,
+        fut: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let err = match fut.await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        if let Err(release_err) = release_quota(self.store.as_ref(), request).await {
+            error!(request_id = %request.id, error = %release_err, "quota_release_failed");
+        }
+        Err(err)
+    }
+
+    /// 管理员清除用户的冷却与滑动窗口限制，不影响当日累计额度
+    pub async fn reset_limits(
+        &self,
+        actor: &User,
+        target_channel: Channel,
+        target_handle: &str,
+    ) -> Result<User> {
+        if !matches!(actor.role, Role::Admin) {
+            anyhow::bail!("only admins may reset limits");
+        }
+
+        let user = self
+            .store
+            .find_user(target_channel.as_str(), target_handle)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("user not found"))?;
+        self.rate_limiter.refresh(user.account_id, Utc::now()).await?;
+        info!(actor = %actor.handle, user = %user.handle, "rate_limits_reset");
+        Ok(user)
+    }
+
     pub async fn default_amount(&self, role: &Role) -> Result<u64> {
-        let limits = self.get_dynamic_limits().await?;
-        Ok(match role {
-            Role::Admin | Role::Privileged => limits.privileged_amount,
-            Role::User => limits.default_amount,
-        })
+        Ok(self.get_dynamic_limits().await?.max_amount(role))
     }
 
     pub async fn max_daily_cap(&self, role: &Role) -> Result<Option<u64>> {
-        let limits = self.get_dynamic_limits().await?;
-        Ok(match role {
-            Role::Admin | Role::Privileged => limits.privileged_daily_cap,
-            Role::User => Some(limits.default_daily_cap),
-        })
+        Ok(self.get_dynamic_limits().await?.daily_cap(role))
     }
 
     pub async fn quota_snapshot(&self, user: &User) -> Result<QuotaSnapshot> {
         let today = Utc::now().date_naive();
         let minted = self
             .store
-            .fetch_quota(user.id, today)
+            .fetch_quota(user.account_id, today)
             .await?
             .map(|quota| quota.minted_total)
             .unwrap_or(0);
@@ -258,19 +413,204 @@ where
         })
     }
 
+    pub async fn find_request(&self, request_id: Uuid) -> Result<Option<MintRequest>> {
+        self.store.find_request(request_id).await
+    }
+
+    pub async fn list_requests(
+        &self,
+        user: &User,
+        before: Option<MintCursor>,
+        limit: usize,
+    ) -> Result<MintPage> {
+        // 多取一条判断是否还有下一页
+        let mut requests = self
+            .store
+            .list_requests_for_account(user.account_id, before, limit + 1)
+            .await?;
+        let next_cursor = if requests.len() > limit {
+            requests.truncate(limit);
+            requests.last().map(MintCursor::from_request)
+        } else {
+            None
+        };
+        Ok(MintPage {
+            requests,
+            next_cursor,
+        })
+    }
+
     pub async fn find_user(&self, channel: Channel, handle: &str) -> Result<Option<User>> {
         self.store.find_user(channel.as_str(), handle).await
     }
 
+    /// 为当前身份所属账户签发一次性关联码，在其他渠道中使用后即并入同一账户
+    pub async fn issue_link_code(&self, user: &User) -> Result<LinkCode> {
+        let mut bytes = [0u8; LINK_CODE_BYTES];
+        OsRng.fill_bytes(&mut bytes);
+        let code = hex::encode_upper(bytes);
+        let link = LinkCode {
+            code,
+            account_id: user.account_id,
+            expires_at: Utc::now() + chrono::Duration::from_std(LINK_CODE_TTL)?,
+        };
+        self.store.create_link_code(&link).await?;
+        info!(user = %user.handle, account_id = %user.account_id, "link_code_issued");
+        Ok(link)
+    }
+
+    /// 使用关联码把当前身份并入签发方的账户，之后共享角色与每日额度
+    pub async fn link_identity(&self, user: &User, code: &str) -> Result<User> {
+        let now = Utc::now();
+        let locked = self
+            .link_failures
+            .get(&user.id)
+            .is_some_and(|entry| entry.0 >= LINK_MAX_FAILURES && entry.1 > now);
+        if locked {
+            anyhow::bail!("too many invalid link codes, try again later");
+        }
+
+        let code = code.trim().to_ascii_uppercase();
+        let Some(link) = self.store.consume_link_code(&code, now).await? else {
+            self.record_link_failure(user, now);
+            anyhow::bail!("link code is invalid or expired");
+        };
+        self.link_failures.remove(&user.id);
+        if link.account_id == user.account_id {
+            return Ok(user.clone());
+        }
+
+        self.store.link_user(user.id, link.account_id).await?;
+        info!(
+            user = %user.handle,
+            channel = %user.channel.as_str(),
+            account_id = %link.account_id,
+            "identity_linked"
+        );
+        self.store
+            .find_user(user.channel.as_str(), &user.handle)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("user not found"))
+    }
+
+    fn record_link_failure(&self, user: &User, now: DateTime<Utc>) {
+        // 顺带清理已过期的计数，记录数不超过近期输错过的身份数
+        self.link_failures.retain(|_, entry| entry.1 > now);
+        let mut entry = self
+            .link_failures
+            .entry(user.id)
+            .or_insert((0, now + LINK_LOCKOUT));
+        entry.0 += 1;
+        if entry.0 >= LINK_MAX_FAILURES {
+            entry.1 = now + LINK_LOCKOUT;
+            warn!(user = %user.handle, channel = %user.channel.as_str(), "link_identity_locked");
+        }
+    }
+
+    /// 为刚登录的用户开始一个新会话
+    pub async fn start_session(&self, user: &User, ttl: chrono::Duration) -> Result<Session> {
+        self.issue_refresh_token(user, Uuid::new_v4(), ttl).await
+    }
+
+    /// 用刷新令牌换取同一会话的新刷新令牌，旧令牌随即失效。
+    /// 令牌无效、已过期或用户已不存在时返回 `None`；返回的用户带有当前角色。
+    pub async fn refresh_session(
+        &self,
+        refresh_token: &str,
+        ttl: chrono::Duration,
+    ) -> Result<Option<(User, Session)>> {
+        let hash = hash_token(refresh_token);
+        let Some(token) = self.store.consume_refresh_token(&hash, Utc::now()).await? else {
+            return Ok(None);
+        };
+        let Some(user) = self
+            .store
+            .find_user(token.channel.as_str(), &token.handle)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let session = self.issue_refresh_token(&user, token.session_id, ttl).await?;
+        Ok(Some((user, session)))
+    }
+
+    /// 结束会话：删除其刷新令牌，并在访问令牌过期前拒绝 `jti`
+    pub async fn end_session(
+        &self,
+        session_id: Uuid,
+        jti: &str,
+        access_expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        self.store.revoke_session(session_id).await?;
+        self.store.revoke_token(jti, access_expires_at).await?;
+        info!(%session_id, "session_ended");
+        Ok(())
+    }
+
+    pub async fn is_token_revoked(&self, jti: &str) -> Result<bool> {
+        self.store.is_token_revoked(jti, Utc::now()).await
+    }
+
+    /// 钱包登录验签通过后记下挑战，同一个挑战只能登录一次；已用过时返回 `false`
+    pub async fn use_login_nonce(&self, nonce: &LoginNonce) -> Result<bool> {
+        self.store.use_login_nonce(nonce, Utc::now()).await
+    }
+
+    async fn issue_refresh_token(
+        &self,
+        user: &User,
+        session_id: Uuid,
+        ttl: chrono::Duration,
+    ) -> Result<Session> {
+        let refresh_token = hex::encode(rand::random::<[u8; REFRESH_TOKEN_BYTES]>());
+        let token = RefreshToken {
+            token_hash: hash_token(&refresh_token),
+            session_id,
+            channel: user.channel.clone(),
+            handle: user.handle.clone(),
+            expires_at: Utc::now() + ttl,
+        };
+        self.store.create_refresh_token(&token).await?;
+        Ok(Session {
+            id: session_id,
+            refresh_token,
+            expires_at: token.expires_at,
+        })
+    }
+
     pub async fn get_all_configs(&self) -> Result<Vec<crate::models::SystemConfig>> {
         self.store.get_all_configs().await
     }
 
     pub async fn update_limit_config(&self, config: &crate::models::LimitConfigUpdate) -> Result<()> {
-        self.store.update_limit_config(config).await
+        self.limits.update(config).await?;
+        Ok(())
     }
 }
 
+fn lowercase(domains: &[String]) -> HashSet<String> {
+    domains.iter().map(|d| d.to_ascii_lowercase()).collect()
+}
+
+/// 存储中只保存刷新令牌的摘要，泄露的数据库无法直接用来续期
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// 网页会话。`refresh_token` 为明文，只在签发时交给客户端
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub refresh_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MintPage {
+    pub requests: Vec<MintRequest>,
+    pub next_cursor: Option<MintCursor>,
+}
+
 #[derive(Debug, Clone)]
 pub struct QuotaSnapshot {
     pub minted: u64,
@@ -282,3 +622,365 @@ impl QuotaSnapshot {
         self.cap.map(|cap| cap.saturating_sub(self.minted))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::config::MemoryConfig;
+    use crate::db;
+    use crate::models::PendingTransfer;
+    use crate::queue::{TransferJournal, TransferState};
+
+    const CAP: u64 = 100;
+
+    /// 提交总是失败；`record` 时先记录一笔交易，`confirm` 为查询该交易的结果
+    struct FailingClient {
+        record: bool,
+        confirm: Option<TransferState>,
+    }
+
+    #[async_trait::async_trait]
+    impl AptosClient for FailingClient {
+        async fn submit_transfer(
+            &self,
+            _request: &MintRequest,
+            journal: &dyn TransferJournal,
+        ) -> Result<String> {
+            if self.record {
+                journal
+                    .record(&PendingTransfer {
+                        hash: "0x1".to_string(),
+                        sequence_number: 0,
+                        expires_at: Utc::now(),
+                    })
+                    .await?;
+            }
+            anyhow::bail!("request timed out")
+        }
+
+        async fn confirm_transfer(&self, _transfer: &PendingTransfer) -> Result<TransferState> {
+            self.confirm
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("node unavailable"))
+        }
+
+        fn max_attempt_duration(&self) -> Duration {
+            Duration::from_secs(1)
+        }
+    }
+
+    fn service(client: FailingClient) -> FaucetService<FailingClient> {
+        service_with_auth(
+            client,
+            serde_json::json!({
+                "google_client_id": "",
+                "google_client_secret": "",
+                "privileged_domains": [],
+            }),
+        )
+    }
+
+    fn service_with_auth(
+        client: FailingClient,
+        auth: serde_json::Value,
+    ) -> FaucetService<FailingClient> {
+        let limits = serde_json::from_value(serde_json::json!({
+            "default_amount": CAP,
+            "default_daily_cap": CAP,
+            "privileged_amount": CAP,
+            "privileged_daily_cap": null,
+            "address_daily_cap": CAP,
+        }))
+        .unwrap();
+        let auth = serde_json::from_value(auth).unwrap();
+        let queue = QueueConfig {
+            mode: MintMode::Inline,
+            visibility_timeout: Duration::from_secs(30),
+            retry_backoff: Duration::ZERO,
+            max_retries: 0,
+            poll_interval: Duration::from_millis(10),
+        };
+        FaucetService::new(
+            db::open_memory(&MemoryConfig::default()).unwrap(),
+            Arc::new(client),
+            limits,
+            &auth,
+            &queue,
+        )
+        .unwrap()
+    }
+
+    async fn user(service: &FaucetService<FailingClient>) -> User {
+        service
+            .touch_user(Identity {
+                channel: Channel::Web,
+                handle: "alice",
+                domain: None,
+            })
+            .await
+            .unwrap()
+    }
+
+    fn recipient() -> AccountAddress {
+        "0x1".parse().unwrap()
+    }
+
+    async fn minted(service: &FaucetService<FailingClient>, user: &User, day: NaiveDate) -> u64 {
+        service
+            .store
+            .fetch_quota(user.account_id, day)
+            .await
+            .unwrap()
+            .map_or(0, |quota| quota.minted_total)
+    }
+
+    /// 地址当天的额度是否已全部退还
+    async fn address_released(service: &FaucetService<FailingClient>, day: NaiveDate) -> bool {
+        service
+            .store
+            .record_address_mint(&recipient(), day, CAP, Some(CAP))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn touch_user_keeps_assigned_role() {
+        let service = service(FailingClient {
+            record: false,
+            confirm: None,
+        });
+        let alice = user(&service).await;
+        let mut admin = User::new(Channel::Web, "root");
+        admin.role = Role::Admin;
+
+        service
+            .set_role(&admin, Channel::Web, &alice.handle, Role::Privileged)
+            .await
+            .unwrap();
+        // 登录前读到的旧用户记录不会把角色改回去
+        service.store.upsert_user(&alice).await.unwrap();
+        let touched = user(&service).await;
+
+        assert_eq!(touched.role, Role::Privileged);
+        let found = service.find_user(Channel::Web, &alice.handle).await.unwrap().unwrap();
+        assert_eq!(found.role, Role::Privileged);
+    }
+
+    #[tokio::test]
+    async fn legacy_handle_is_migrated_once() {
+        let service = service(FailingClient {
+            record: false,
+            confirm: None,
+        });
+        let legacy = User::new(Channel::Telegram, "alice");
+        service.store.upsert_user(&legacy).await.unwrap();
+
+        service
+            .migrate_handle(Channel::Telegram, "alice", "42")
+            .await
+            .unwrap();
+        let migrated = service.find_user(Channel::Telegram, "42").await.unwrap().unwrap();
+        assert_eq!(migrated.account_id, legacy.account_id);
+        assert!(service.find_user(Channel::Telegram, "alice").await.unwrap().is_none());
+
+        // 用户名被他人重新注册后不会再接管已迁移的身份
+        let squatter = User::new(Channel::Telegram, "alice");
+        service.store.upsert_user(&squatter).await.unwrap();
+        service
+            .migrate_handle(Channel::Telegram, "alice", "42")
+            .await
+            .unwrap();
+        let kept = service.find_user(Channel::Telegram, "42").await.unwrap().unwrap();
+        assert_eq!(kept.account_id, legacy.account_id);
+    }
+
+    #[tokio::test]
+    async fn privileged_domains_are_scoped_to_provider() {
+        let service = service_with_auth(
+            FailingClient {
+                record: false,
+                confirm: None,
+            },
+            serde_json::json!({
+                "google_client_id": "",
+                "google_client_secret": "",
+                "privileged_domains": ["google.example"],
+                "oidc": [
+                    {
+                        "name": "corp",
+                        "issuer": "https://corp.example",
+                        "client_id": "faucet",
+                        "domain_claim": "org",
+                        "privileged_domains": ["Corp.Example"],
+                    },
+                    {
+                        "name": "public",
+                        "issuer": "https://public.example",
+                        "client_id": "faucet",
+                        "domain_claim": "org",
+                    },
+                ],
+            }),
+        );
+        let role = |handle: &'static str, domain: &'static str| {
+            let service = &service;
+            async move {
+                service
+                    .touch_user(Identity {
+                        channel: Channel::Web,
+                        handle,
+                        domain: Some(domain),
+                    })
+                    .await
+                    .unwrap()
+                    .role
+            }
+        };
+
+        assert_eq!(role("corp:1", "corp.example").await, Role::Privileged);
+        // 其他提供方声明同一个域名不会提升角色
+        assert_eq!(role("public:1", "corp.example").await, Role::User);
+        // 全局列表只适用于 Google 账号
+        assert_eq!(role("public:2", "google.example").await, Role::User);
+        assert_eq!(role("corp:2", "google.example").await, Role::User);
+        assert_eq!(role("alice@google.example", "google.example").await, Role::Privileged);
+    }
+
+    #[tokio::test]
+    async fn locks_out_repeated_link_failures() {
+        let service = service(FailingClient {
+            record: false,
+            confirm: None,
+        });
+        let web = user(&service).await;
+        let bot = service
+            .touch_user(Identity {
+                channel: Channel::Telegram,
+                handle: "alice_bot",
+                domain: None,
+            })
+            .await
+            .unwrap();
+        let link = service.issue_link_code(&web).await.unwrap();
+        assert_eq!(link.code.len(), LINK_CODE_BYTES * 2);
+
+        for _ in 0..LINK_MAX_FAILURES {
+            let err = service.link_identity(&bot, "0000").await.unwrap_err();
+            assert_eq!(err.to_string(), "link code is invalid or expired");
+        }
+        // 锁定期间正确的码也不接受，且不会被消耗
+        let err = service.link_identity(&bot, &link.code).await.unwrap_err();
+        assert_eq!(err.to_string(), "too many invalid link codes, try again later");
+
+        // 锁定只针对输错的身份
+        let other = service
+            .touch_user(Identity {
+                channel: Channel::Discord,
+                handle: "alice_dc",
+                domain: None,
+            })
+            .await
+            .unwrap();
+        let linked = service.link_identity(&other, &link.code).await.unwrap();
+        assert_eq!(linked.account_id, web.account_id);
+    }
+
+    #[tokio::test]
+    async fn failed_inline_mint_releases_quota() {
+        let service = service(FailingClient {
+            record: false,
+            confirm: None,
+        });
+        let user = user(&service).await;
+
+        service.mint(&user, recipient(), 10).await.unwrap_err();
+
+        let page = service.list_requests(&user, None, 10).await.unwrap();
+        let day = page.requests[0].quota_day.unwrap();
+        assert_eq!(page.requests[0].status, MintStatus::Failed);
+        assert_eq!(minted(&service, &user, day).await, 0);
+        assert!(address_released(&service, day).await);
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_inline_mint_keeps_quota() {
+        let service = service(FailingClient {
+            record: true,
+            confirm: None,
+        });
+        let user = user(&service).await;
+
+        service.mint(&user, recipient(), 10).await.unwrap_err();
+
+        let page = service.list_requests(&user, None, 10).await.unwrap();
+        let request = &page.requests[0];
+        let day = request.quota_day.unwrap();
+        assert_eq!(request.status, MintStatus::Failed);
+        assert_eq!(request.tx_hash.as_deref(), Some("0x1"));
+        assert_eq!(minted(&service, &user, day).await, 10);
+        assert!(!address_released(&service, day).await);
+    }
+
+    #[tokio::test]
+    async fn timed_out_inline_mint_that_landed_completes() {
+        let service = service(FailingClient {
+            record: true,
+            confirm: Some(TransferState::Committed),
+        });
+        let user = user(&service).await;
+
+        let outcome = service.mint(&user, recipient(), 10).await.unwrap();
+
+        assert_eq!(outcome.tx_hash.as_deref(), Some("0x1"));
+        assert_eq!(outcome.request.status, MintStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn mint_records_http_request_id() {
+        let service = service(FailingClient {
+            record: true,
+            confirm: Some(TransferState::Committed),
+        });
+        let user = user(&service).await;
+
+        let outcome = logging::with_request_id(
+            "req-1".to_string(),
+            service.mint(&user, recipient(), 10),
+        )
+        .await
+        .unwrap();
+
+        let stored = service.find_request(outcome.request.id).await.unwrap().unwrap();
+        assert_eq!(stored.http_request_id.as_deref(), Some("req-1"));
+    }
+
+    #[tokio::test]
+    async fn enqueue_error_releases_quota() {
+        let service = service(FailingClient {
+            record: false,
+            confirm: None,
+        });
+        let user = user(&service).await;
+        let day = service.rate_limiter.check_and_record(&user, 10).await.unwrap();
+        service
+            .rate_limiter
+            .check_and_record_address(&recipient(), day, 10)
+            .await
+            .unwrap();
+        let mut request = new_request(&user, recipient(), 10);
+        request.quota_day = Some(day);
+
+        let err = service
+            .release_on_error(&request, async { Err::<(), _>(anyhow::anyhow!("database down")) })
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "database down");
+        assert_eq!(minted(&service, &user, day).await, 0);
+        assert!(address_released(&service, day).await);
+    }
+}
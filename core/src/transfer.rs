@@ -0,0 +1,213 @@
+//! 存储后端之间的数据迁移。
+//!
+//! 导出为 NDJSON：每行一条 `{"table": ..., "row": ...}`，按用户、请求、额度、地址额度、
+//! 失败记录、系统配置的顺序写出，导入时按行原样写入目标存储，保证外键引用的行先于引用方。
+
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{bail, Context, Result};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::models::{AddressQuota, MintFailure, MintRequest, Quota, SystemConfig, User};
+use crate::repository::TransferRepository;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "table", content = "row", rename_all = "snake_case")]
+pub enum Record {
+    Users(User),
+    MintRequests(MintRequest),
+    Quotas(Quota),
+    AddressQuotas(AddressQuota),
+    MintFailures(MintFailure),
+    SystemConfigs(SystemConfig),
+}
+
+impl Record {
+    pub fn table(&self) -> &'static str {
+        match self {
+            Record::Users(_) => "users",
+            Record::MintRequests(_) => "mint_requests",
+            Record::Quotas(_) => "quotas",
+            Record::AddressQuotas(_) => "address_quotas",
+            Record::MintFailures(_) => "mint_failures",
+            Record::SystemConfigs(_) => "system_configs",
+        }
+    }
+
+    /// 表内唯一的键，与各后端的主键或唯一索引一致
+    fn key(&self) -> String {
+        match self {
+            Record::Users(user) => user.id.to_string(),
+            Record::MintRequests(request) => request.id.to_string(),
+            Record::Quotas(quota) => format!("{}/{}", quota.user_id, quota.day),
+            Record::AddressQuotas(quota) => format!("{}/{}", quota.address, quota.day),
+            Record::MintFailures(failure) => failure.id.to_string(),
+            Record::SystemConfigs(config) => config.key.clone(),
+        }
+    }
+}
+
+/// 每张表处理的行数
+pub type TableCounts = BTreeMap<&'static str, u64>;
+
+/// 记录已出现过的键，同一张表中的重复键视为错误
+#[derive(Default)]
+struct UniqueKeys {
+    seen: HashSet<(&'static str, String)>,
+    counts: TableCounts,
+}
+
+impl UniqueKeys {
+    fn check(&mut self, record: &Record) -> Result<()> {
+        if !self.seen.insert((record.table(), record.key())) {
+            bail!("{} 中存在重复的键 {}", record.table(), record.key());
+        }
+        *self.counts.entry(record.table()).or_default() += 1;
+        Ok(())
+    }
+}
+
+/// 把 `store` 的全部数据写入 `writer`
+pub async fn export<S, W>(store: &S, mut writer: W) -> Result<TableCounts>
+where
+    S: TransferRepository + ?Sized,
+    W: AsyncWrite + Unpin,
+{
+    let mut keys = UniqueKeys::default();
+    write_table(&mut writer, &mut keys, store.export_users(), Record::Users).await?;
+    write_table(&mut writer, &mut keys, store.export_requests(), Record::MintRequests).await?;
+    write_table(&mut writer, &mut keys, store.export_quotas(), Record::Quotas).await?;
+    write_table(
+        &mut writer,
+        &mut keys,
+        store.export_address_quotas(),
+        Record::AddressQuotas,
+    )
+    .await?;
+    write_table(&mut writer, &mut keys, store.export_failures(), Record::MintFailures).await?;
+    write_table(&mut writer, &mut keys, store.export_configs(), Record::SystemConfigs).await?;
+    writer.flush().await?;
+    Ok(keys.counts)
+}
+
+async fn write_table<T, W>(
+    writer: &mut W,
+    keys: &mut UniqueKeys,
+    rows: impl Stream<Item = Result<T>>,
+    wrap: fn(T) -> Record,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    futures::pin_mut!(rows);
+    while let Some(row) = rows.next().await {
+        let record = wrap(row?);
+        keys.check(&record)?;
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        writer.write_all(&line).await?;
+    }
+    Ok(())
+}
+
+/// 逐行写入 `reader` 中的记录。
+///
+/// 文件内出现重复的键，或目标存储中已经存在同一条记录时中止，已写入的行不会回滚。
+pub async fn import<S, R>(store: &S, reader: R) -> Result<TableCounts>
+where
+    S: TransferRepository + ?Sized,
+    R: AsyncBufRead + Unpin,
+{
+    let mut keys = UniqueKeys::default();
+    let mut lines = reader.lines();
+    let mut line_no = 0;
+    while let Some(line) = lines.next_line().await? {
+        line_no += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record =
+            serde_json::from_str(&line).with_context(|| format!("第 {line_no} 行无法解析"))?;
+        keys.check(&record)
+            .with_context(|| format!("第 {line_no} 行"))?;
+        let inserted = match &record {
+            Record::Users(user) => store.import_user(user).await,
+            Record::MintRequests(request) => store.import_request(request).await,
+            Record::Quotas(quota) => store.import_quota(quota).await,
+            Record::AddressQuotas(quota) => store.import_address_quota(quota).await,
+            Record::MintFailures(failure) => store.import_failure(failure).await,
+            Record::SystemConfigs(config) => store.import_config(config).await,
+        }
+        .with_context(|| format!("第 {line_no} 行写入失败"))?;
+        if !inserted {
+            bail!(
+                "第 {line_no} 行：目标存储的 {} 中已存在 {}",
+                record.table(),
+                record.key()
+            );
+        }
+    }
+    Ok(keys.counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::memory::MemoryStore;
+    use crate::models::{Channel, MintStatus};
+    use crate::queue::new_request;
+    use crate::repository::{
+        ConfigRepository, MintRepository, QuotaRepository, ReportingRepository, UserRepository,
+    };
+
+    #[tokio::test]
+    async fn round_trips_between_stores() {
+        let source = MemoryStore::new();
+        let user = User::new(Channel::Web, "alice");
+        source.upsert_user(&user).await.unwrap();
+        let request = new_request(&user, "0x1".parse().unwrap(), 10);
+        source.enqueue(&request).await.unwrap();
+        source
+            .update_status(request.id, MintStatus::Failed)
+            .await
+            .unwrap();
+        source
+            .log_failure(request.id, request.requested_at, "node unavailable")
+            .await
+            .unwrap();
+        let day = request.requested_at.date_naive();
+        source.record_mint(user.account_id, day, 10, None).await.unwrap();
+        let address = request.recipient.unwrap();
+        source
+            .record_address_mint(&address, day, 10, None)
+            .await
+            .unwrap();
+        source.set_config("limits.default_amount", "5", None).await.unwrap();
+
+        let mut ndjson = Vec::new();
+        let exported = export(&source, &mut ndjson).await.unwrap();
+        assert_eq!(exported.values().sum::<u64>(), 6);
+        assert!(exported.values().all(|count| *count == 1));
+
+        let target = MemoryStore::new();
+        let imported = import(&target, ndjson.as_slice()).await.unwrap();
+        assert_eq!(imported, exported);
+        let stored = target.find_request(request.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, MintStatus::Failed);
+        assert!(target.find_user("web", "alice").await.unwrap().is_some());
+
+        // 再次导入会撞上已有记录
+        let err = import(&target, ndjson.as_slice()).await.unwrap_err();
+        assert!(err.to_string().contains("已存在"), "{err}");
+
+        // 文件内的重复行
+        let line = ndjson.split(|b| *b == b'\n').next().unwrap().to_vec();
+        let duplicated = [line.clone(), line].join(&b'\n');
+        let err = import(&MemoryStore::new(), duplicated.as_slice())
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("重复"), "{err:#}");
+    }
+}
@@ -2,21 +2,22 @@ use std::{str::FromStr, sync::Arc};
 
 use anyhow::{Context as AnyhowContext, Result};
 use faucet_core::{
-    config::AppConfig,
+    config::{AppConfig, MintMode},
+    db::{self, PeriodicFlush},
     logging,
-    models::{Channel, Role},
-    queue::LoggingAptosClient,
-    DatabaseStore, FaucetService, Identity,
+    metrics,
+    models::{parse_mint_args, Channel, Role},
+    ChainClient, FaucetService, Identity, Service,
 };
 use serenity::{
     async_trait,
     model::{channel::Message, gateway::Ready},
     prelude::*,
 };
-use tracing::{error, info, warn};
+use tracing::{error, info, instrument, warn, Span};
 
 struct BotState {
-    faucet: Arc<FaucetService<DatabaseStore, LoggingAptosClient>>,
+    faucet: Arc<FaucetService<ChainClient>>,
 }
 
 struct Handler {
@@ -48,17 +49,27 @@ impl EventHandler for Handler {
 }
 
 impl Handler {
+    #[instrument(
+        name = "discord.command",
+        skip_all,
+        fields(channel_id = %msg.channel_id, command = tracing::field::Empty)
+    )]
     async fn handle_message(&self, ctx: &Context, msg: &Message) -> Result<()> {
         let content = msg.content.trim();
+        if let Some(command) = content.split_whitespace().next().filter(|c| c.starts_with('!')) {
+            Span::current().record("command", command);
+        }
         if content.starts_with("!mint") {
             self.handle_mint(ctx, msg, content).await
         } else if content.starts_with("!setrole") {
             self.handle_set_role(ctx, msg, content).await
+        } else if content.starts_with("!link") {
+            self.handle_link(ctx, msg, content).await
         } else if content.starts_with("!help") {
             msg.channel_id
                 .say(
                     &ctx.http,
-                    "命令列表:\n!mint [amount] - 按默认或指定数量发放\n!setrole <@user> <user|privileged|admin> - 管理员设定角色",
+                    "命令列表:\n!mint <address> [amount] - 向钱包地址按默认或指定数量发放\n!setrole <@user> <user|privileged|admin> - 管理员设定角色\n!link <code> - 使用网页端生成的关联码关联账户",
                 )
                 .await?;
             Ok(())
@@ -79,20 +90,30 @@ impl Handler {
             })
             .await?;
 
-        let mut parts = content.split_whitespace();
-        parts.next();
-        let amount = parts.next().map(|value| value.parse::<u64>()).transpose()?;
-        let amount = amount.unwrap_or_else(|| self.state.faucet.default_amount(&profile.role));
+        let (recipient, amount) = parse_mint_args(content)?;
+        let Some(recipient) = recipient else {
+            msg.channel_id
+                .say(&ctx.http, "用法: !mint <钱包地址> [数量]")
+                .await?;
+            return Ok(());
+        };
+        let amount = match amount {
+            Some(amount) => amount,
+            None => self.state.faucet.default_amount(&profile.role).await?,
+        };
 
-        match self.state.faucet.mint(&profile, amount).await {
+        match self.state.faucet.mint(&profile, recipient, amount).await {
             Ok(outcome) => {
                 let snapshot = self.state.faucet.quota_snapshot(&profile).await?;
-                let hash = outcome.tx_hash.unwrap_or_else(|| "<pending>".to_string());
+                let hash = outcome
+                    .tx_hash
+                    .unwrap_or_else(|| format!("<pending, 请求 {}>", outcome.request.id));
                 msg.channel_id
                     .say(
                         &ctx.http,
                         format!(
-                            "✅ 已分发 {} 枚代币\nTx: {}\n今日已用: {}\n今日剩余: {}",
+                            "✅ 已向 {} 分发 {} 枚代币\nTx: {}\n今日已用: {}\n今日剩余: {}",
+                            recipient,
                             outcome.request.amount,
                             hash,
                             snapshot.minted,
@@ -114,6 +135,44 @@ impl Handler {
         Ok(())
     }
 
+    async fn handle_link(&self, ctx: &Context, msg: &Message, content: &str) -> Result<()> {
+        let Some(code) = content.split_whitespace().nth(1) else {
+            msg.channel_id
+                .say(&ctx.http, "用法: !link <关联码>，关联码可在网页端生成")
+                .await?;
+            return Ok(());
+        };
+
+        let handle = msg.author.id.to_string();
+        let profile = self
+            .state
+            .faucet
+            .touch_user(Identity {
+                channel: Channel::Discord,
+                handle: &handle,
+                domain: None,
+            })
+            .await?;
+
+        match self.state.faucet.link_identity(&profile, code).await {
+            Ok(linked) => {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        format!("🔗 已关联网页账户，额度与角色将共享\n角色: {:?}", linked.role),
+                    )
+                    .await?;
+            }
+            Err(err) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("❌ 关联失败: {}", err))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn handle_set_role(&self, ctx: &Context, msg: &Message, content: &str) -> Result<()> {
         let actor_handle = msg.author.id.to_string();
         let actor = self
@@ -163,23 +222,37 @@ impl Handler {
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = AppConfig::load()?;
-    logging::init_telemetry(&config.telemetry);
+    let _telemetry = logging::init_telemetry("faucet-dc-bot", &config.telemetry)?;
+    // 机器人没有 HTTP 服务，只在配置了监听地址时暴露指标
+    if config.metrics.listen_addr.is_some() {
+        metrics::install(config.metrics.listen_addr)?;
+    }
 
     let token = std::env::var("DISCORD_TOKEN")?;
 
     let skip_db = should_skip_db();
     let store = if skip_db {
-        warn!("数据库连接已跳过，Discord 机器人使用内存存储，数据不会持久化");
-        Arc::new(DatabaseStore::memory())
+        match &config.memory.snapshot_path {
+            Some(path) => info!(path = %path.display(), "数据库连接已跳过，使用内存存储并写入快照"),
+            None => warn!("数据库连接已跳过，Discord 机器人使用内存存储，数据不会持久化"),
+        }
+        db::open_memory(&config.memory)?
     } else {
-        Arc::new(DatabaseStore::connect(&config.database).await?)
+        db::connect(&config.database).await?
     };
+    if skip_db && config.memory.snapshot_path.is_some() {
+        PeriodicFlush::new(store.clone(), config.memory.flush_interval).spawn();
+    }
     let faucet = Arc::new(FaucetService::new(
         store.clone(),
-        Arc::new(LoggingAptosClient),
+        Arc::new(ChainClient::from_config(config.aptos.as_ref())?),
         config.limits.clone(),
         &config.auth,
-    ));
+        &config.queue,
+    )?);
+    if faucet.mode() == MintMode::Async {
+        faucet.queue().spawn();
+    }
 
     let handler = Handler {
         state: Arc::new(BotState { faucet }),
@@ -190,7 +263,16 @@ async fn main() -> Result<()> {
         .event_handler(handler)
         .await?;
 
+    // Ctrl+C 时先断开网关，再写入内存快照
+    let shard_manager = client.shard_manager.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            shard_manager.shutdown_all().await;
+        }
+    });
+
     client.start().await?;
+    store.flush().await?;
 
     Ok(())
 }
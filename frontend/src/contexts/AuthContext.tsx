@@ -1,6 +1,6 @@
 import React, { createContext, useContext, useEffect, useState, ReactNode } from 'react';
 import { User } from '../types';
-import { authApi } from '../lib/api';
+import { authApi, clearSession } from '../lib/api';
 import { googleAuth, GoogleUser } from '../lib/googleAuth';
 
 interface AuthContextType {
@@ -40,7 +40,7 @@ export const AuthProvider: React.FC<AuthProviderProps> = ({ children }) => {
       setUser(userData);
     } catch (error) {
       // 如果token过期或无效，清除本地存储
-      localStorage.removeItem('auth_token');
+      clearSession();
       setUser(null);
       setGoogleUser(null);
     }
@@ -66,7 +66,7 @@ export const AuthProvider: React.FC<AuthProviderProps> = ({ children }) => {
       // 忽略登出错误
     } finally {
       // 清除本地存储的token
-      localStorage.removeItem('auth_token');
+      clearSession();
       setUser(null);
       setGoogleUser(null);
     }
@@ -84,7 +84,7 @@ export const AuthProvider: React.FC<AuthProviderProps> = ({ children }) => {
         }
       } catch (error) {
         // Clear invalid token
-        localStorage.removeItem('auth_token');
+        clearSession();
       } finally {
         setIsLoading(false);
       }
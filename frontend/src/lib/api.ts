@@ -1,24 +1,69 @@
-import { User, SessionResponse, MintRequest, MintResponse, RoleUpdateRequest } from '../types';
+import { User, SessionResponse, LoginNonce, WalletLoginRequest, MintRequest, MintResponse, RoleUpdateRequest } from '../types';
 
 const API_BASE_URL = (import.meta as any).env?.VITE_API_BASE_URL || '/api';
 
-// Helper function to make API requests
-async function apiRequest<T>(endpoint: string, options: RequestInit = {}): Promise<T> {
-  const token = localStorage.getItem('auth_token');
-  
-  const config: RequestInit = {
+const ACCESS_TOKEN_KEY = 'auth_token';
+const REFRESH_TOKEN_KEY = 'refresh_token';
+
+export function storeSession(session: SessionResponse): void {
+  localStorage.setItem(ACCESS_TOKEN_KEY, session.token);
+  localStorage.setItem(REFRESH_TOKEN_KEY, session.refresh_token);
+}
+
+export function clearSession(): void {
+  localStorage.removeItem(ACCESS_TOKEN_KEY);
+  localStorage.removeItem(REFRESH_TOKEN_KEY);
+}
+
+// 并发请求同时遇到 401 时只刷新一次
+let pendingRefresh: Promise<boolean> | null = null;
+
+async function refreshAccessToken(): Promise<boolean> {
+  const refreshToken = localStorage.getItem(REFRESH_TOKEN_KEY);
+  if (!refreshToken) return false;
+
+  pendingRefresh ??= (async () => {
+    try {
+      const response = await fetch(`${API_BASE_URL}/api/session/refresh`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ refresh_token: refreshToken }),
+      });
+      if (!response.ok) return false;
+      storeSession(await response.json());
+      return true;
+    } catch (error) {
+      return false;
+    } finally {
+      pendingRefresh = null;
+    }
+  })();
+  return pendingRefresh;
+}
+
+function send(endpoint: string, options: RequestInit): Promise<Response> {
+  const token = localStorage.getItem(ACCESS_TOKEN_KEY);
+  return fetch(`${API_BASE_URL}${endpoint}`, {
     ...options,
     headers: {
       'Content-Type': 'application/json',
       ...(token && { Authorization: `Bearer ${token}` }),
       ...options.headers,
     },
-  };
+  });
+}
+
+// Helper function to make API requests
+async function apiRequest<T>(endpoint: string, options: RequestInit = {}): Promise<T> {
+  let response = await send(endpoint, options);
+
+  // 访问令牌有效期很短，过期后用刷新令牌换一对新令牌再重试一次
+  if (response.status === 401 && (await refreshAccessToken())) {
+    response = await send(endpoint, options);
+  }
 
-  const response = await fetch(`${API_BASE_URL}${endpoint}`, config);
-  
   if (response.status === 401) {
-    localStorage.removeItem('auth_token');
+    clearSession();
     window.location.href = '/';
     throw new Error('Unauthorized');
   }
@@ -38,9 +83,30 @@ export const authApi = {
     });
   },
 
+  // Sign-In With Aptos：先取挑战，钱包签名后提交
+  async getLoginNonce(): Promise<LoginNonce> {
+    return apiRequest<LoginNonce>('/api/session/nonce');
+  },
+
+  async createWalletSession(request: WalletLoginRequest): Promise<SessionResponse> {
+    return apiRequest<SessionResponse>('/api/session/wallet', {
+      method: 'POST',
+      body: JSON.stringify(request),
+    });
+  },
+
   async getCurrentUser(): Promise<User> {
     return apiRequest<User>('/api/me');
   },
+
+  // 登出时作废服务端会话，失败也不影响本地清理
+  async endSession(): Promise<void> {
+    try {
+      await send('/api/session', { method: 'DELETE' });
+    } finally {
+      clearSession();
+    }
+  },
 };
 
 export const faucetApi = {
@@ -1,4 +1,4 @@
-import { authApi } from './api';
+import { authApi, storeSession } from './api';
 
 // Google OAuth configuration
 const GOOGLE_CLIENT_ID = (import.meta as any).env?.VITE_GOOGLE_CLIENT_ID || '';
@@ -61,8 +61,8 @@ class GoogleAuthService {
       // 创建后端会话
       const session = await authApi.createSession(response.credential);
       
-      // 存储 token
-      localStorage.setItem('auth_token', session.token);
+      // 存储访问令牌与刷新令牌
+      storeSession(session);
 
       // 触发登录成功事件
       window.dispatchEvent(new CustomEvent('googleSignIn', { 
@@ -154,7 +154,7 @@ class GoogleAuthService {
     if (window.google && this.isInitialized) {
       window.google.accounts.id.disableAutoSelect();
     }
-    localStorage.removeItem('auth_token');
+    await authApi.endSession();
   }
 
   async getCurrentUser(): Promise<GoogleUser | null> {
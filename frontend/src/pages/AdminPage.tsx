@@ -67,7 +67,8 @@ const AdminPage: React.FC = () => {
   const channelOptions = [
     { value: 'web', label: 'Web' },
     { value: 'telegram', label: 'Telegram' },
-    { value: 'discord', label: 'Discord' }
+    { value: 'discord', label: 'Discord' },
+    { value: 'wallet', label: 'Aptos 钱包' }
   ];
 
   return (
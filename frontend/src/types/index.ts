@@ -9,9 +9,26 @@ export interface User {
 
 export interface SessionResponse {
   token: string;
+  expires_in: number;
+  refresh_token: string;
+  refresh_expires_at: string;
   user: User;
 }
 
+export interface LoginNonce {
+  nonce: string;
+  message: string;
+  expires_at: string;
+}
+
+export interface WalletLoginRequest {
+  nonce: string;
+  public_key: string;
+  signature: string;
+  address?: string;
+  full_message?: string;
+}
+
 export interface MintRequest {
   amount?: number;
   wallet_address?: string;
@@ -27,7 +44,7 @@ export interface MintResponse {
 
 export interface RoleUpdateRequest {
   handle: string;
-  channel: 'web' | 'telegram' | 'discord';
+  channel: 'web' | 'telegram' | 'discord' | 'wallet';
   role: 'user' | 'privileged' | 'admin';
 }
 
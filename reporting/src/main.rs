@@ -1,26 +1,62 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::{Duration, Utc};
 use faucet_core::{
     config::AppConfig,
+    db,
     logging,
     repository::{DailyReportRow, ReportingRepository},
-    DatabaseStore,
+    transfer::{self, TableCounts},
 };
+use tokio::io::BufReader;
 use tracing::{info, warn};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = AppConfig::load()?;
-    logging::init_telemetry(&config.telemetry);
+    let _telemetry = logging::init_telemetry("faucet-reporting", &config.telemetry)?;
 
     let skip_db = should_skip_db();
     let store = if skip_db {
-        warn!("数据库连接已跳过，报表任务使用内存存储，数据不会持久化");
-        DatabaseStore::memory()
+        match &config.memory.snapshot_path {
+            Some(path) => info!(path = %path.display(), "数据库连接已跳过，使用内存快照"),
+            None => warn!("数据库连接已跳过，报表任务使用内存存储，数据不会持久化"),
+        }
+        db::open_memory(&config.memory)?
     } else {
-        DatabaseStore::connect(&config.database).await?
+        db::connect(&config.database).await?
     };
 
+    // `reporting export <文件>` 与 `reporting import <文件>`：以 NDJSON 在存储后端之间迁移数据，
+    // 源和目标分别由各自运行时的数据库配置决定
+    let args: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    match args.first().map(String::as_str) {
+        Some("export") => {
+            let path = args.get(1).context("用法：reporting export <文件>")?;
+            let file = tokio::fs::File::create(path)
+                .await
+                .with_context(|| format!("无法创建 {path}"))?;
+            let counts = transfer::export(&store, tokio::io::BufWriter::new(file)).await?;
+            print_counts("已导出", &counts);
+            return Ok(());
+        }
+        Some("import") => {
+            let path = args.get(1).context("用法：reporting import <文件>")?;
+            let file = tokio::fs::File::open(path)
+                .await
+                .with_context(|| format!("无法打开 {path}"))?;
+            let counts = transfer::import(&store, BufReader::new(file)).await?;
+            // 导入到内存存储时写入快照
+            store.flush().await?;
+            print_counts("已导入", &counts);
+            return Ok(());
+        }
+        Some(other) => anyhow::bail!("未知的子命令 {other}，可用的子命令：export、import"),
+        None => {}
+    }
+
     let today = Utc::now().date_naive();
     let yesterday = today - Duration::days(1);
 
@@ -47,6 +83,13 @@ fn should_skip_db() -> bool {
     false
 }
 
+fn print_counts(action: &str, counts: &TableCounts) {
+    for (table, count) in counts {
+        println!("{action} {table}: {count}");
+    }
+    println!("{action} 共 {} 行", counts.values().sum::<u64>());
+}
+
 fn render_report(title: &str, rows: &[DailyReportRow]) {
     info!(title, entries = rows.len(), "汇总统计");
     for row in rows {
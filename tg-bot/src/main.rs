@@ -2,41 +2,56 @@ use std::{str::FromStr, sync::Arc};
 
 use anyhow::{Context, Result};
 use faucet_core::{
-    config::AppConfig,
+    config::{AppConfig, MintMode},
+    db::{self, PeriodicFlush},
     logging,
-    models::{Channel, Role, User},
-    queue::LoggingAptosClient,
-    DatabaseStore, FaucetService, Identity,
+    metrics,
+    models::{parse_mint_args, AccountAddress, Channel, Role, User},
+    ChainClient, FaucetService, Identity, Service,
 };
 use teloxide::{
     dispatching::UpdateFilterExt, dptree, error_handlers::ErrorHandler, prelude::*,
     update_listeners::Polling,
 };
-use tracing::{error, info, warn};
+use tracing::{error, info, instrument, warn, Span};
 
 #[derive(Clone)]
 struct BotState {
-    faucet: Arc<FaucetService<DatabaseStore, LoggingAptosClient>>,
+    faucet: Arc<FaucetService<ChainClient>>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let config = AppConfig::load()?;
-    logging::init_telemetry(&config.telemetry);
+    let _telemetry = logging::init_telemetry("faucet-tg-bot", &config.telemetry)?;
+    // 机器人没有 HTTP 服务，只在配置了监听地址时暴露指标
+    if config.metrics.listen_addr.is_some() {
+        metrics::install(config.metrics.listen_addr)?;
+    }
 
     let skip_db = should_skip_db();
     let store = if skip_db {
-        warn!("数据库连接已跳过，Telegram 机器人使用内存存储，数据不会持久化");
-        Arc::new(DatabaseStore::memory())
+        match &config.memory.snapshot_path {
+            Some(path) => info!(path = %path.display(), "数据库连接已跳过，使用内存存储并写入快照"),
+            None => warn!("数据库连接已跳过，Telegram 机器人使用内存存储，数据不会持久化"),
+        }
+        db::open_memory(&config.memory)?
     } else {
-        Arc::new(DatabaseStore::connect(&config.database).await?)
+        db::connect(&config.database).await?
     };
+    if skip_db && config.memory.snapshot_path.is_some() {
+        PeriodicFlush::new(store.clone(), config.memory.flush_interval).spawn();
+    }
     let faucet = Arc::new(FaucetService::new(
         store.clone(),
-        Arc::new(LoggingAptosClient),
+        Arc::new(ChainClient::from_config(config.aptos.as_ref())?),
         config.limits.clone(),
         &config.auth,
-    ));
+        &config.queue,
+    )?);
+    if faucet.mode() == MintMode::Async {
+        faucet.queue().spawn();
+    }
 
     let bot = Bot::from_env();
     let state = Arc::new(BotState { faucet });
@@ -55,6 +70,7 @@ async fn main() -> Result<()> {
         Arc::new(LoggingErrorHandler),
     )
     .await;
+    store.flush().await?;
 
     Ok(())
 }
@@ -72,17 +88,29 @@ fn should_skip_db() -> bool {
     false
 }
 
+#[instrument(
+    name = "telegram.command",
+    skip_all,
+    fields(chat_id = %msg.chat.id, command = tracing::field::Empty)
+)]
 async fn handle_message(bot: Bot, msg: Message, state: Arc<BotState>) -> Result<()> {
     let text = msg.text().unwrap_or("").trim();
     if text.is_empty() {
         return Ok(());
     }
+    if let Some(command) = text.split_whitespace().next() {
+        Span::current().record("command", command);
+    }
 
     let user = msg.from.as_ref().context("消息缺少发送者信息")?;
-    let handle = user
-        .username
-        .clone()
-        .unwrap_or_else(|| user.id.0.to_string());
+    // 用户名可以修改并被他人重新注册，身份只认数字 id
+    let handle = user.id.0.to_string();
+    if let Some(username) = &user.username {
+        state
+            .faucet
+            .migrate_handle(Channel::Telegram, username, &handle)
+            .await?;
+    }
 
     let profile = state
         .faucet
@@ -94,15 +122,20 @@ async fn handle_message(bot: Bot, msg: Message, state: Arc<BotState>) -> Result<
         .await?;
 
     if text.starts_with("/start") || text.starts_with("/help") {
-        send_welcome(&bot, &msg, &state, &profile, &handle).await?;
+        let name = user.username.as_deref().unwrap_or(&user.first_name);
+        send_welcome(&bot, &msg, &state, &profile, name).await?;
     } else if text.starts_with("/mint") {
-        let amount = text
-            .split_whitespace()
-            .nth(1)
-            .map(|value| value.parse::<u64>())
-            .transpose()?;
-        let amount = amount.unwrap_or_else(|| state.faucet.default_amount(&profile.role));
-        handle_mint(&bot, &msg, &state, &profile, amount).await?;
+        let (recipient, amount) = parse_mint_args(text)?;
+        let Some(recipient) = recipient else {
+            bot.send_message(msg.chat.id, "用法: /mint <钱包地址> [数量]")
+                .await?;
+            return Ok(());
+        };
+        let amount = match amount {
+            Some(amount) => amount,
+            None => state.faucet.default_amount(&profile.role).await?,
+        };
+        handle_mint(&bot, &msg, &state, &profile, recipient, amount).await?;
     } else if text.starts_with("/setrole") {
         let mut parts = text.split_whitespace();
         parts.next();
@@ -110,6 +143,13 @@ async fn handle_message(bot: Bot, msg: Message, state: Arc<BotState>) -> Result<
         let role_str = parts.next().context("缺少角色参数")?;
         let role = Role::from_str(role_str)?;
         set_role(&bot, &msg, &state, &profile, target.to_string(), role).await?;
+    } else if text.starts_with("/link") {
+        let Some(code) = text.split_whitespace().nth(1) else {
+            bot.send_message(msg.chat.id, "用法: /link <关联码>，关联码可在网页端生成")
+                .await?;
+            return Ok(());
+        };
+        link_account(&bot, &msg, &state, &profile, code).await?;
     }
 
     Ok(())
@@ -120,7 +160,7 @@ async fn send_welcome(
     msg: &Message,
     state: &Arc<BotState>,
     profile: &User,
-    handle: &str,
+    name: &str,
 ) -> Result<()> {
     let snapshot = state.faucet.quota_snapshot(profile).await?;
     let cap_text = snapshot
@@ -132,10 +172,11 @@ async fn send_welcome(
         .map(|left| left.to_string())
         .unwrap_or_else(|| "无限制".to_string());
     let message = format!(
-        "欢迎回来, {}!\n角色: {:?}\n单次额度: {}\n日上限: {}\n今日已用: {}\n今日剩余: {}",
-        handle,
+        "欢迎回来, {}!\n用户 ID: {}\n角色: {:?}\n单次额度: {}\n日上限: {}\n今日已用: {}\n今日剩余: {}\n\n领取: /mint <钱包地址> [数量]\n关联网页账户: /link <关联码>",
+        name,
+        profile.handle,
         profile.role,
-        state.faucet.max_amount_for_role(&profile.role),
+        state.faucet.max_amount_for_role(&profile.role).await?,
         cap_text,
         snapshot.minted,
         remaining_text,
@@ -149,20 +190,30 @@ async fn handle_mint(
     msg: &Message,
     state: &Arc<BotState>,
     profile: &User,
+    recipient: AccountAddress,
     amount: u64,
 ) -> Result<()> {
-    match state.faucet.mint(profile, amount).await {
+    match state.faucet.mint(profile, recipient, amount).await {
         Ok(outcome) => {
             let snapshot = state.faucet.quota_snapshot(profile).await?;
-            let hash = outcome.tx_hash.as_deref().unwrap_or("<pending>");
             let remaining_text = snapshot
                 .remaining()
                 .map(|left| left.to_string())
                 .unwrap_or_else(|| "无限制".to_string());
-            let message = format!(
-                "✅ 铸币成功!\n数量: {}\n交易: {}\n今日已用: {}\n今日剩余: {}",
-                outcome.request.amount, hash, snapshot.minted, remaining_text,
-            );
+            let message = match outcome.tx_hash.as_deref() {
+                Some(hash) => format!(
+                    "✅ 铸币成功!\n地址: {}\n数量: {}\n交易: {}\n今日已用: {}\n今日剩余: {}",
+                    recipient, outcome.request.amount, hash, snapshot.minted, remaining_text,
+                ),
+                None => format!(
+                    "⏳ 已受理，稍后到账\n请求: {}\n地址: {}\n数量: {}\n今日已用: {}\n今日剩余: {}",
+                    outcome.request.id,
+                    recipient,
+                    outcome.request.amount,
+                    snapshot.minted,
+                    remaining_text,
+                ),
+            };
             bot.send_message(msg.chat.id, message).await?;
         }
         Err(err) => {
@@ -173,6 +224,29 @@ async fn handle_mint(
     Ok(())
 }
 
+async fn link_account(
+    bot: &Bot,
+    msg: &Message,
+    state: &Arc<BotState>,
+    profile: &User,
+    code: &str,
+) -> Result<()> {
+    match state.faucet.link_identity(profile, code).await {
+        Ok(linked) => {
+            bot.send_message(
+                msg.chat.id,
+                format!("🔗 已关联网页账户，额度与角色将共享\n角色: {:?}", linked.role),
+            )
+            .await?;
+        }
+        Err(err) => {
+            bot.send_message(msg.chat.id, format!("关联失败: {}", err))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
 async fn set_role(
     bot: &Bot,
     msg: &Message,
@@ -187,10 +261,15 @@ async fn set_role(
         return Ok(());
     }
 
-    let target_handle = handle.trim_start_matches('@').to_string();
+    // 身份以数字 id 保存，用户名无法对应到身份；对方发送 /start 可以看到自己的 id
+    if handle.parse::<u64>().is_err() {
+        bot.send_message(msg.chat.id, "请使用对方的数字用户 ID（发送 /start 可查看）")
+            .await?;
+        return Ok(());
+    }
     match state
         .faucet
-        .set_role(actor, Channel::Telegram, &target_handle, role.clone())
+        .set_role(actor, Channel::Telegram, &handle, role.clone())
         .await
     {
         Ok(updated) => {
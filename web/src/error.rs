@@ -6,6 +6,7 @@ use tracing::error;
 pub enum ApiError {
     Unauthorized,
     Forbidden,
+    NotFound,
     BadRequest(String),
     Internal(anyhow::Error),
 }
@@ -32,6 +33,13 @@ impl IntoResponse for ApiError {
                 }),
             )
                 .into_response(),
+            ApiError::NotFound => (
+                StatusCode::NOT_FOUND,
+                Json(ErrorBody {
+                    error: "not found".to_string(),
+                }),
+            )
+                .into_response(),
             ApiError::BadRequest(message) => {
                 (StatusCode::BAD_REQUEST, Json(ErrorBody { error: message })).into_response()
             }
@@ -0,0 +1,104 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+
+use faucet_core::{
+    models::{Role, User},
+    Identity,
+};
+
+use crate::{error::ApiError, jwt::Claims, parse_channel, AppState};
+
+/// 持有有效 JWT 的已登录用户；缺少或无效的 token 返回 401
+pub struct AuthUser(pub User);
+
+/// 角色为 `admin` 的已登录用户；已登录但不是管理员时返回 403
+pub struct AdminUser(pub User);
+
+/// 当前访问令牌的 claims，用于结束会话等只关心令牌本身的操作
+pub struct AuthSession(pub Claims);
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let token = extract_bearer(parts)?;
+        resolve_user(state, token).await.map(AuthUser)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let token = extract_bearer(parts)?;
+        verify_claims(state, token).await.map(AuthSession)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let AuthUser(user) = AuthUser::from_request_parts(parts, state).await?;
+        if !matches!(user.role, Role::Admin) {
+            return Err(ApiError::Forbidden);
+        }
+        Ok(AdminUser(user))
+    }
+}
+
+/// 校验签名与有效期，并拒绝已登出会话的令牌
+async fn verify_claims(state: &AppState, token: &str) -> Result<Claims, ApiError> {
+    let claims = state.jwt_service.verify_token(token)
+        .map_err(|_| ApiError::Unauthorized)?;
+    if state.faucet.is_token_revoked(&claims.jti).await? {
+        return Err(ApiError::Unauthorized);
+    }
+    Ok(claims)
+}
+
+async fn resolve_user(state: &AppState, token: &str) -> Result<User, ApiError> {
+    // 验证JWT token，已吊销的 jti 视为未登录
+    let claims = verify_claims(state, token).await?;
+
+    // 从JWT claims中获取用户信息
+    let channel = parse_channel(&claims.channel)
+        .map_err(|_| ApiError::Unauthorized)?;
+
+    let identity = Identity {
+        channel,
+        handle: &claims.handle,
+        domain: claims.domain.as_deref(),
+    };
+
+    // 角色以存储中的为准，签发后被降级的用户不会沿用 token 里的旧角色
+    let user = state.faucet.touch_user(identity).await?;
+
+    Ok(user)
+}
+
+fn extract_bearer(parts: &Parts) -> Result<&str, ApiError> {
+    let value = parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or(ApiError::Unauthorized)?;
+
+    let token = value
+        .strip_prefix("Bearer ")
+        .or_else(|| value.strip_prefix("bearer "))
+        .ok_or(ApiError::Unauthorized)?
+        .trim();
+
+    if token.is_empty() {
+        Err(ApiError::Unauthorized)
+    } else {
+        Ok(token)
+    }
+}
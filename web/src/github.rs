@@ -0,0 +1,193 @@
+use anyhow::{Context, Result};
+use axum::async_trait;
+use faucet_core::config::GithubConfig;
+use reqwest::{
+    header::{ACCEPT, USER_AGENT},
+    Client,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::http_client,
+    identity::{IdentityProvider, LoginCredential, VerifiedIdentity},
+};
+
+/// GitHub OAuth App 登录：授权码换取 access token 后读取当前用户
+pub struct GithubProvider {
+    client: Client,
+    config: GithubConfig,
+}
+
+impl GithubProvider {
+    pub fn new(config: GithubConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+            config,
+        })
+    }
+
+    async fn exchange_code(&self, code: &str, redirect_uri: Option<&str>) -> Result<String> {
+        let mut form = vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("client_secret", self.config.client_secret.as_str()),
+            ("code", code),
+        ];
+        if let Some(redirect_uri) = redirect_uri {
+            form.push(("redirect_uri", redirect_uri));
+        }
+
+        let response = self
+            .client
+            .post(format!(
+                "{}/login/oauth/access_token",
+                self.config.oauth_url.trim_end_matches('/')
+            ))
+            .header(ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await
+            .context("failed to call github token endpoint")?;
+        if !response.status().is_success() {
+            anyhow::bail!("github code exchange failed: status {}", response.status());
+        }
+
+        // 授权码无效时 GitHub 仍返回 200，错误放在 `error` 字段中
+        let payload: TokenResponse = response
+            .json()
+            .await
+            .context("failed to decode github token response")?;
+        match payload.access_token {
+            Some(token) => Ok(token),
+            None => anyhow::bail!(
+                "github code exchange failed: {}",
+                payload.error.as_deref().unwrap_or("missing access_token")
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for GithubProvider {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    async fn authenticate(&self, credential: &LoginCredential) -> Result<VerifiedIdentity> {
+        let code = credential
+            .code
+            .as_deref()
+            .context("github login requires an authorization code")?;
+        let access_token = self
+            .exchange_code(code, credential.redirect_uri.as_deref())
+            .await?;
+
+        let response = self
+            .client
+            .get(format!("{}/user", self.config.api_url.trim_end_matches('/')))
+            .bearer_auth(access_token)
+            .header(ACCEPT, "application/vnd.github+json")
+            .header(USER_AGENT, "faucet-web")
+            .send()
+            .await
+            .context("failed to call github user endpoint")?;
+        if !response.status().is_success() {
+            anyhow::bail!("github user lookup failed: status {}", response.status());
+        }
+        let user: GithubUser = response
+            .json()
+            .await
+            .context("failed to decode github user")?;
+
+        // 用户名可以改名后被他人注册，handle 使用不变的数字 id；
+        // GitHub 不担保组织域名，这类账号需要管理员手动授予角色
+        tracing::debug!(login = %user.login, id = user.id, "github_user_verified");
+        Ok(VerifiedIdentity {
+            handle: format!("github:{}", user.id),
+            domain: None,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubUser {
+    id: u64,
+    login: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// 本地的 GitHub 替身：只认 `good-code`，只接受对应的 access token
+    async fn github_server() -> String {
+        let app = Router::new()
+            .route(
+                "/login/oauth/access_token",
+                post(|Form(form): Form<HashMap<String, String>>| async move {
+                    if form.get("code").map(String::as_str) == Some("good-code")
+                        && form.get("client_secret").map(String::as_str) == Some("secret")
+                    {
+                        Json(json!({ "access_token": "gho_test", "token_type": "bearer" }))
+                    } else {
+                        Json(json!({ "error": "bad_verification_code" }))
+                    }
+                }),
+            )
+            .route(
+                "/user",
+                get(|headers: HeaderMap| async move {
+                    match headers.get("authorization").and_then(|v| v.to_str().ok()) {
+                        Some("Bearer gho_test") => Ok(Json(json!({ "id": 583231, "login": "octocat" }))),
+                        _ => Err(StatusCode::UNAUTHORIZED),
+                    }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn exchanges_code_for_qualified_handle() {
+        let base = github_server().await;
+        let provider = GithubProvider::new(GithubConfig {
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            oauth_url: base.clone(),
+            api_url: base,
+        })
+        .unwrap();
+
+        let login = |code: &str| LoginCredential {
+            code: Some(code.to_string()),
+            ..LoginCredential::default()
+        };
+        let identity = provider.authenticate(&login("good-code")).await.unwrap();
+        assert_eq!(identity.handle, "github:583231");
+        assert_eq!(identity.domain, None);
+
+        assert!(provider.authenticate(&login("stale-code")).await.is_err());
+        assert!(provider
+            .authenticate(&LoginCredential {
+                id_token: Some("ey...".to_string()),
+                ..LoginCredential::default()
+            })
+            .await
+            .is_err());
+    }
+}
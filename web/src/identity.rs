@@ -0,0 +1,103 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use axum::async_trait;
+use serde::Deserialize;
+
+use faucet_core::{config::AuthConfig, models::Channel, Identity};
+
+use crate::{auth::GoogleVerifier, github::GithubProvider, oidc::OidcProvider};
+
+/// 登录请求中携带的凭据，各提供方只读取自己需要的字段
+#[derive(Debug, Default, Deserialize)]
+pub struct LoginCredential {
+    /// 前端直接拿到的 ID token（Google、OIDC）
+    #[serde(default)]
+    pub id_token: Option<String>,
+    /// 授权码流程回调得到的 code（GitHub、OIDC）
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+}
+
+/// 提供方验证通过的身份
+#[derive(Debug, Clone)]
+pub struct VerifiedIdentity {
+    /// 带提供方前缀的 handle，例如 `github:583231`
+    pub handle: String,
+    /// 提供方担保的组织域名，与该提供方适用的 `privileged_domains` 比较决定是否获得 privileged 角色
+    pub domain: Option<String>,
+}
+
+impl VerifiedIdentity {
+    pub fn identity(&self) -> Identity<'_> {
+        Identity {
+            channel: Channel::Web,
+            handle: &self.handle,
+            domain: self.domain.as_deref(),
+        }
+    }
+}
+
+/// Web 登录的身份提供方
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// 登录请求中的 `provider` 字段
+    fn name(&self) -> &str;
+
+    async fn authenticate(&self, credential: &LoginCredential) -> Result<VerifiedIdentity>;
+}
+
+/// 内置登录方式的名字：Google、GitHub 与钱包登录（`/api/session/wallet`）
+const BUILTIN_PROVIDERS: [&str; 3] = ["google", "github", "wallet"];
+
+/// 按名字查找已配置的提供方；Google 始终启用，GitHub 与 OIDC 按配置启用
+#[derive(Clone)]
+pub struct IdentityProviders {
+    providers: HashMap<String, Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    pub fn from_config(auth: &AuthConfig) -> Result<Self> {
+        let mut providers = Self {
+            providers: HashMap::new(),
+        };
+        providers.insert(Arc::new(GoogleVerifier::new(
+            &auth.google_client_id,
+            &auth.google,
+        )?))?;
+        if let Some(github) = &auth.github {
+            providers.insert(Arc::new(GithubProvider::new(github.clone())?))?;
+        }
+        for oidc in &auth.oidc {
+            providers.register(Arc::new(OidcProvider::new(oidc.clone())?))?;
+        }
+        Ok(providers)
+    }
+
+    /// 注册配置的 OIDC 提供方。内置提供方的名字即使未启用也不能使用，
+    /// 否则其用户的 handle 会与之后启用的内置提供方混在一起
+    fn register(&mut self, provider: Arc<dyn IdentityProvider>) -> Result<()> {
+        let name = provider.name();
+        if BUILTIN_PROVIDERS.contains(&name) {
+            anyhow::bail!("identity provider name {name} is reserved for the built-in provider");
+        }
+        self.insert(provider)
+    }
+
+    fn insert(&mut self, provider: Arc<dyn IdentityProvider>) -> Result<()> {
+        let name = provider.name().to_string();
+        if name.is_empty() || name.contains(':') {
+            anyhow::bail!("invalid identity provider name {name:?}");
+        }
+        if self.providers.insert(name.clone(), provider).is_some() {
+            anyhow::bail!("duplicate identity provider {name}");
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn IdentityProvider> {
+        self.providers.get(name).map(|provider| provider.as_ref())
+    }
+}
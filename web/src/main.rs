@@ -1,60 +1,71 @@
 mod auth;
 mod error;
+mod extract;
+mod github;
+mod identity;
 mod jwt;
-mod session;
+mod oidc;
+mod wallet;
 
 use std::sync::Arc;
 
 use anyhow::Result;
-use auth::GoogleVerifier;
+use chrono::{DateTime, Utc};
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
+    body::Body,
+    extract::{MatchedPath, Path, Query, State},
+    http::{Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use tower_http::cors::CorsLayer;
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
 use error::ApiError;
+use extract::{AdminUser, AuthSession, AuthUser};
 use faucet_core::{
-    config::AppConfig,
+    config::{AppConfig, MintMode},
+    db::{self, PeriodicFlush},
     logging,
-    models::{Channel, MintStatus, Role, User},
-    queue::LoggingAptosClient,
-    DatabaseStore, FaucetService, Identity,
+    metrics::{self, PrometheusHandle},
+    models::{AccountAddress, Channel, MintRequest, MintStatus, Role, User},
+    repository::MintCursor,
+    service::Session,
+    ChainClient, FaucetService, Identity, Service,
 };
+use identity::{IdentityProviders, LoginCredential};
 use jwt::JwtService;
 
-// 辅助函数来解析Role
-fn parse_role(s: &str) -> Result<Role> {
-    match s {
-        "user" => Ok(Role::User),
-        "privileged" => Ok(Role::Privileged),
-        "admin" => Ok(Role::Admin),
-        _ => anyhow::bail!("invalid role: {}", s),
-    }
-}
-
 fn parse_channel(s: &str) -> Result<Channel> {
     match s {
         "web" => Ok(Channel::Web),
         "telegram" => Ok(Channel::Telegram),
         "discord" => Ok(Channel::Discord),
+        "wallet" => Ok(Channel::Wallet),
         _ => anyhow::bail!("invalid channel: {}", s),
     }
 }
 use serde::{Deserialize, Serialize};
-use session::SessionManager;
 use tokio::signal;
-use tracing::{info, warn};
+use tracing::{info, info_span, warn, Span};
+use uuid::Uuid;
+
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+const MAX_HISTORY_LIMIT: usize = 100;
+const LOGIN_NONCE_TTL: chrono::Duration = chrono::Duration::minutes(5);
 
 #[derive(Clone)]
 struct AppState {
-    faucet: Arc<FaucetService<DatabaseStore, LoggingAptosClient>>,
-    sessions: SessionManager,
-    verifier: GoogleVerifier,
+    faucet: Arc<FaucetService<ChainClient>>,
+    identity: IdentityProviders,
     jwt_service: JwtService,
     config: AppConfig,
+    metrics: PrometheusHandle,
 }
 
 #[tokio::main]
@@ -63,39 +74,57 @@ async fn main() -> Result<()> {
     dotenv::dotenv().ok();
     
     let config = AppConfig::load()?;
-    logging::init_telemetry(&config.telemetry);
+    let _telemetry = logging::init_telemetry("faucet-web", &config.telemetry)?;
+
+    // `web migrate [--dry-run]`：只执行数据库迁移后退出
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        let dry_run = args.iter().any(|arg| arg == "--dry-run");
+        return run_migrations(&config, dry_run).await;
+    }
+
+    let metrics = metrics::install(config.metrics.listen_addr)?;
+
+    // 密钥不安全时在连接数据库之前就拒绝启动
+    let jwt_service = JwtService::from_config(&config.auth.jwt)?;
 
     let skip_db = should_skip_db();
     let store = if skip_db {
-        warn!("数据库连接已跳过，使用内存存储，所有数据将在进程结束后丢失");
-        Arc::new(DatabaseStore::memory())
+        match &config.memory.snapshot_path {
+            Some(path) => info!(path = %path.display(), "数据库连接已跳过，使用内存存储并写入快照"),
+            None => warn!("数据库连接已跳过，使用内存存储，所有数据将在进程结束后丢失"),
+        }
+        db::open_memory(&config.memory)?
     } else {
-        Arc::new(DatabaseStore::connect(&config.database).await?)
+        db::connect(&config.database).await?
     };
-    let aptos_client = Arc::new(LoggingAptosClient);
+    if skip_db && config.memory.snapshot_path.is_some() {
+        PeriodicFlush::new(store.clone(), config.memory.flush_interval).spawn();
+    }
+    let aptos_client = Arc::new(ChainClient::from_config(config.aptos.as_ref())?);
     let faucet = Arc::new(FaucetService::new(
         store.clone(),
         aptos_client,
         config.limits.clone(),
         &config.auth,
-    ));
+        &config.queue,
+    )?);
+    if faucet.mode() == MintMode::Async {
+        faucet.queue().spawn();
+    }
 
-    let verifier = GoogleVerifier::new(&config.auth.google_client_id)?;
+    let identity = IdentityProviders::from_config(&config.auth)?;
     
-    // 初始化JWT服务，使用环境变量或默认密钥
-    let jwt_secret = std::env::var("FAUCET__JWT_SECRET")
-        .unwrap_or_else(|_| "your-secret-key-change-this-in-production".to_string());
-    let jwt_service = JwtService::new(&jwt_secret)?;
 
     // 保存配置值，因为config会被移动到AppState中
     let http_addr = config.server.http_addr.clone();
 
     let state = AppState {
         faucet,
-        sessions: SessionManager::default(),
-        verifier,
+        identity,
         jwt_service,
         config,
+        metrics,
     };
 
     info!(addr = %http_addr, "Web 服务启动");
@@ -106,10 +135,30 @@ async fn main() -> Result<()> {
     axum::serve(listener, router)
         .with_graceful_shutdown(shutdown_signal())
         .await?;
+    store.flush().await?;
 
     Ok(())
 }
 
+async fn run_migrations(config: &AppConfig, dry_run: bool) -> Result<()> {
+    let steps = db::migrate(&config.database, dry_run).await?;
+    if steps.is_empty() {
+        println!("数据库已是最新版本");
+        return Ok(());
+    }
+
+    for step in &steps {
+        let action = if dry_run { "待执行" } else { "已执行" };
+        println!("{action} {:04} {}", step.version, step.name);
+        if dry_run {
+            for statement in &step.statements {
+                println!("    {statement}");
+            }
+        }
+    }
+    Ok(())
+}
+
 fn should_skip_db() -> bool {
     if std::env::args().any(|arg| arg == "--no-db") {
         return true;
@@ -132,7 +181,12 @@ fn build_router(state: AppState) -> Router {
             "http://127.0.0.1:3000".parse().unwrap(),
             "http://127.0.0.1:3001".parse().unwrap(),
         ])
-        .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::OPTIONS])
+        .allow_methods([
+            axum::http::Method::GET,
+            axum::http::Method::POST,
+            axum::http::Method::DELETE,
+            axum::http::Method::OPTIONS,
+        ])
         .allow_headers([
             axum::http::header::CONTENT_TYPE,
             axum::http::header::AUTHORIZATION,
@@ -141,31 +195,115 @@ fn build_router(state: AppState) -> Router {
 
     Router::new()
         .route("/health", get(health))
-        .route("/api/session", post(create_session))
+        .route("/metrics", get(render_metrics))
+        .route("/api/session", post(create_session).delete(end_session))
+        .route("/api/session/refresh", post(refresh_session))
+        .route("/api/session/nonce", get(create_login_nonce))
+        .route("/api/session/wallet", post(create_wallet_session))
         .route("/api/me", get(current_user))
         .route("/api/mint", post(mint_tokens))
+        .route("/api/mint/:id", get(get_mint))
+        .route("/api/me/mints", get(list_my_mints))
+        .route("/api/me/link-code", post(create_link_code))
         .route("/api/admin/role", post(update_role))
         .route("/api/admin/config", get(get_configs))
         .route("/api/admin/config/limits", post(update_limit_config))
+        .route("/api/admin/limits/reset", post(reset_limits))
         .layer(cors)
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(TraceLayer::new_for_http().make_span_with(request_span))
+                .layer(middleware::from_fn(scope_request_id))
+                .layer(PropagateRequestIdLayer::x_request_id()),
+        )
         .with_state(state)
 }
 
+/// 让处理函数发起的铸币请求记下 `x-request-id`
+async fn scope_request_id(request: Request<Body>, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    match request_id {
+        Some(request_id) => logging::with_request_id(request_id, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+/// 每个 HTTP 请求一个 span，带上 `x-request-id`，并接到调用方传来的 `traceparent` 之下
+fn request_span(request: &Request<Body>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| request.uri().path());
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = info_span!(
+        "http.request",
+        otel.name = format!("{} {route}", request.method()),
+        http.method = %request.method(),
+        http.route = route,
+        request_id,
+    );
+    let traceparent = request
+        .headers()
+        .get("traceparent")
+        .and_then(|value| value.to_str().ok());
+    logging::set_remote_parent(&span, traceparent);
+    span
+}
+
 async fn health() -> impl IntoResponse {
     StatusCode::OK
 }
 
+async fn render_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    state.metrics.render()
+}
+
 #[derive(Debug, Deserialize)]
 struct SessionRequest {
-    id_token: String,
+    /// 缺省为 `google`，兼容只提交 `id_token` 的旧前端
+    #[serde(default = "default_provider")]
+    provider: String,
+    #[serde(flatten)]
+    credential: LoginCredential,
+}
+
+fn default_provider() -> String {
+    "google".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
 }
 
+/// `token` 为短期访问令牌，过期后用 `refresh_token` 换取新的一对令牌
 #[derive(Debug, Serialize)]
 struct SessionResponse {
     token: String,
+    expires_in: i64,
+    refresh_token: String,
+    refresh_expires_at: DateTime<Utc>,
     user: UserView,
 }
 
+/// 钱包登录的挑战，`message` 原样交给钱包签名
+#[derive(Debug, Serialize)]
+struct NonceResponse {
+    nonce: String,
+    message: String,
+    expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize)]
 struct UserView {
     handle: String,
@@ -184,13 +322,70 @@ struct MintRequestPayload {
 
 #[derive(Debug, Serialize)]
 struct MintResponse {
+    request_id: Uuid,
     status: MintStatus,
     amount: u64,
+    wallet_address: Option<AccountAddress>,
     tx_hash: Option<String>,
     minted_today: u64,
     remaining_today: Option<u64>,
 }
 
+#[derive(Debug, Serialize)]
+struct MintView {
+    id: Uuid,
+    channel: Channel,
+    status: MintStatus,
+    amount: u64,
+    wallet_address: Option<AccountAddress>,
+    tx_hash: Option<String>,
+    error: Option<String>,
+    attempt: u16,
+    requested_at: DateTime<Utc>,
+    processed_at: Option<DateTime<Utc>>,
+}
+
+impl From<MintRequest> for MintView {
+    fn from(request: MintRequest) -> Self {
+        Self {
+            id: request.id,
+            channel: request.channel,
+            status: request.status,
+            amount: request.amount,
+            wallet_address: request.recipient,
+            tx_hash: request.tx_hash,
+            error: request.error,
+            attempt: request.attempt,
+            requested_at: request.requested_at,
+            processed_at: request.processed_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MintHistoryQuery {
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct MintHistoryResponse {
+    items: Vec<MintView>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkCodeResponse {
+    code: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LimitResetRequest {
+    handle: String,
+    channel: Channel,
+}
+
 #[derive(Debug, Deserialize)]
 struct RoleUpdateRequest {
     handle: String,
@@ -202,94 +397,228 @@ async fn create_session(
     State(state): State<AppState>,
     Json(payload): Json<SessionRequest>,
 ) -> Result<Json<SessionResponse>, ApiError> {
-    let profile = state
-        .verifier
-        .verify(&payload.id_token)
+    let provider = state
+        .identity
+        .get(&payload.provider)
+        .ok_or_else(|| ApiError::BadRequest(format!("unknown provider {}", payload.provider)))?;
+    let verified = provider
+        .authenticate(&payload.credential)
         .await
         .map_err(|err| {
-            tracing::warn!(error = %err, "google_token_invalid");
+            tracing::warn!(provider = %payload.provider, error = %err, "login_credential_invalid");
             ApiError::Unauthorized
         })?;
 
+    let user = state.faucet.touch_user(verified.identity()).await?;
+
+    let session = state
+        .faucet
+        .start_session(&user, chrono::Duration::hours(state.config.auth.jwt_expiry_hours))
+        .await?;
+    Ok(Json(session_response(&state, &user, session).await?))
+}
+
+async fn create_login_nonce(
+    State(state): State<AppState>,
+) -> Result<Json<NonceResponse>, ApiError> {
+    // 挑战自带签名与过期时间，签发时不写存储
+    let nonce = state.jwt_service.issue_login_nonce(LOGIN_NONCE_TTL)?;
+    Ok(Json(NonceResponse {
+        message: wallet::challenge_message(&state.config.server.public_base_url, &nonce),
+        nonce: nonce.nonce,
+        expires_at: nonce.expires_at,
+    }))
+}
+
+/// Sign-In With Aptos：验证对挑战的 Ed25519 签名，以账户地址作为 `wallet` 渠道的身份登录
+async fn create_wallet_session(
+    State(state): State<AppState>,
+    Json(payload): Json<wallet::WalletLogin>,
+) -> Result<Json<SessionResponse>, ApiError> {
+    let nonce = state
+        .jwt_service
+        .verify_login_nonce(&payload.nonce)
+        .map_err(|_| ApiError::Unauthorized)?;
+    let address = wallet::verify(&payload, &state.config.server.public_base_url, &nonce)
+        .map_err(|err| {
+            tracing::warn!(error = %err, "wallet_signature_invalid");
+            ApiError::Unauthorized
+        })?;
+    // 验签通过后才记下挑战，存储只保存成功登录用过的 nonce
+    if !state.faucet.use_login_nonce(&nonce).await? {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let handle = address.to_string();
     let user = state
         .faucet
         .touch_user(Identity {
-            channel: Channel::Web,
-            handle: &profile.email,
-            domain: profile.domain.as_deref(),
+            channel: Channel::Wallet,
+            handle: &handle,
+            domain: None,
         })
         .await?;
 
-    // 生成JWT token，使用配置的过期时间
-    let token = state.jwt_service.generate_token(
-        user.id,
-        &user.handle,
-        &user.channel,
-        user.domain.as_deref(),
-        &user.role,
-        state.config.auth.jwt_expiry_hours,
-    )?;
-    
-    let view = build_user_view(&state, &user).await?;
-    Ok(Json(SessionResponse { token, user: view }))
+    let session = state
+        .faucet
+        .start_session(&user, chrono::Duration::hours(state.config.auth.jwt_expiry_hours))
+        .await?;
+    Ok(Json(session_response(&state, &user, session).await?))
+}
+
+/// 刷新令牌只能使用一次，每次都会换发新的刷新令牌；角色按存储中的当前值签入新令牌
+async fn refresh_session(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<SessionResponse>, ApiError> {
+    let (user, session) = state
+        .faucet
+        .refresh_session(
+            &payload.refresh_token,
+            chrono::Duration::hours(state.config.auth.jwt_expiry_hours),
+        )
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+    Ok(Json(session_response(&state, &user, session).await?))
+}
+
+/// 登出：作废该会话的刷新令牌，当前访问令牌在过期前也不再被接受
+async fn end_session(
+    State(state): State<AppState>,
+    AuthSession(claims): AuthSession,
+) -> Result<StatusCode, ApiError> {
+    let session_id = claims
+        .sid
+        .parse::<Uuid>()
+        .map_err(|_| ApiError::Unauthorized)?;
+    let expires_at = DateTime::from_timestamp(claims.exp, 0).ok_or(ApiError::Unauthorized)?;
+    state
+        .faucet
+        .end_session(session_id, &claims.jti, expires_at)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn session_response(
+    state: &AppState,
+    user: &User,
+    session: Session,
+) -> Result<SessionResponse, ApiError> {
+    let ttl = chrono::Duration::from_std(state.config.auth.access_token_ttl)
+        .map_err(anyhow::Error::from)?;
+    let token = state.jwt_service.generate_token(user, session.id, ttl)?;
+    Ok(SessionResponse {
+        token,
+        expires_in: ttl.num_seconds(),
+        refresh_token: session.refresh_token,
+        refresh_expires_at: session.expires_at,
+        user: build_user_view(state, user).await?,
+    })
 }
 
 async fn current_user(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    AuthUser(user): AuthUser,
 ) -> Result<Json<UserView>, ApiError> {
-    let token = extract_bearer(&headers)?;
-    let user = resolve_user(&state, token).await?;
     Ok(Json(build_user_view(&state, &user).await?))
 }
 
 async fn mint_tokens(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    AuthUser(user): AuthUser,
     Json(payload): Json<MintRequestPayload>,
 ) -> Result<Json<MintResponse>, ApiError> {
-    let token = extract_bearer(&headers)?;
-    let user = resolve_user(&state, token).await?;
     let amount = if let Some(amount) = payload.amount {
         amount
     } else {
         state.faucet.default_amount(&user.role).await.unwrap_or(100000000)
     };
 
-    // 验证钱包地址（如果提供）
-    if let Some(ref wallet_address) = payload.wallet_address {
-        if wallet_address.trim().is_empty() {
-            return Err(ApiError::BadRequest("钱包地址不能为空".to_string()));
-        }
-        // 这里可以添加更多的钱包地址验证逻辑
-        if !wallet_address.starts_with("0x") {
-            return Err(ApiError::BadRequest("钱包地址格式不正确，应以0x开头".to_string()));
-        }
-    }
+    let wallet_address = payload
+        .wallet_address
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| ApiError::BadRequest("钱包地址不能为空".to_string()))?;
+    let recipient = wallet_address.parse::<AccountAddress>().map_err(|_| {
+        ApiError::BadRequest("钱包地址格式不正确，应为 0x 开头的十六进制地址".to_string())
+    })?;
 
-    let outcome = state.faucet.mint(&user, amount).await?;
+    let outcome = state.faucet.mint(&user, recipient, amount).await?;
     let snapshot = state.faucet.quota_snapshot(&user).await?;
 
     Ok(Json(MintResponse {
+        request_id: outcome.request.id,
         status: outcome.request.status,
         amount: outcome.request.amount,
+        wallet_address: outcome.request.recipient,
         tx_hash: outcome.tx_hash,
         minted_today: snapshot.minted,
         remaining_today: snapshot.remaining(),
     }))
 }
 
+async fn get_mint(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Json<MintView>, ApiError> {
+    let request = state
+        .faucet
+        .find_request(id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    // 普通用户只能查看本账户的请求，管理员可查看全部以便排查
+    if request.account_id != user.account_id && !matches!(user.role, Role::Admin) {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(request.into()))
+}
+
+async fn list_my_mints(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+    Query(query): Query<MintHistoryQuery>,
+) -> Result<Json<MintHistoryResponse>, ApiError> {
+    let cursor = query
+        .cursor
+        .as_deref()
+        .filter(|value| !value.is_empty())
+        .map(str::parse::<MintCursor>)
+        .transpose()
+        .map_err(|_| ApiError::BadRequest("cursor 无效".to_string()))?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_HISTORY_LIMIT)
+        .clamp(1, MAX_HISTORY_LIMIT);
+
+    let page = state.faucet.list_requests(&user, cursor, limit).await?;
+    Ok(Json(MintHistoryResponse {
+        items: page.requests.into_iter().map(MintView::from).collect(),
+        next_cursor: page.next_cursor.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// 签发一次性关联码，在 Telegram 中发送 `/link <code>` 或在 Discord 中发送
+/// `!link <code>` 即可把机器人身份并入当前账户
+async fn create_link_code(
+    State(state): State<AppState>,
+    AuthUser(user): AuthUser,
+) -> Result<Json<LinkCodeResponse>, ApiError> {
+    let link = state.faucet.issue_link_code(&user).await?;
+    Ok(Json(LinkCodeResponse {
+        code: link.code,
+        expires_at: link.expires_at,
+    }))
+}
+
 async fn update_role(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    AdminUser(actor): AdminUser,
     Json(payload): Json<RoleUpdateRequest>,
 ) -> Result<Json<UserView>, ApiError> {
-    let token = extract_bearer(&headers)?;
-    let actor = resolve_user(&state, token).await?;
-    if !matches!(actor.role, Role::Admin) {
-        return Err(ApiError::Forbidden);
-    }
-
     let updated = state
         .faucet
         .set_role(
@@ -303,57 +632,31 @@ async fn update_role(
     Ok(Json(build_user_view(&state, &updated).await?))
 }
 
+async fn reset_limits(
+    State(state): State<AppState>,
+    AdminUser(actor): AdminUser,
+    Json(payload): Json<LimitResetRequest>,
+) -> Result<Json<UserView>, ApiError> {
+    let user = state
+        .faucet
+        .reset_limits(&actor, payload.channel.clone(), &payload.handle)
+        .await?;
+
+    Ok(Json(build_user_view(&state, &user).await?))
+}
+
 async fn build_user_view(state: &AppState, user: &User) -> Result<UserView, ApiError> {
     let snapshot = state.faucet.quota_snapshot(user).await?;
     Ok(UserView {
         handle: user.handle.clone(),
         role: user.role.clone(),
-        max_amount: state.faucet.max_amount_for_role(&user.role),
+        max_amount: state.faucet.max_amount_for_role(&user.role).await?,
         max_daily_cap: state.faucet.max_daily_cap(&user.role).await?,
         minted_today: snapshot.minted,
         remaining_today: snapshot.remaining(),
     })
 }
 
-async fn resolve_user(state: &AppState, token: &str) -> Result<User, ApiError> {
-    // 验证JWT token
-    let claims = state.jwt_service.verify_token(token)
-        .map_err(|_| ApiError::Unauthorized)?;
-
-    // 从JWT claims中获取用户信息
-    let channel = parse_channel(&claims.channel)
-        .map_err(|_| ApiError::Unauthorized)?;
-
-    let identity = Identity {
-        channel,
-        handle: &claims.handle,
-        domain: claims.domain.as_deref(),
-    };
-
-    let user = state.faucet.touch_user(identity).await?;
-
-    Ok(user)
-}
-
-fn extract_bearer(headers: &HeaderMap) -> Result<&str, ApiError> {
-    let value = headers
-        .get(axum::http::header::AUTHORIZATION)
-        .and_then(|v| v.to_str().ok())
-        .ok_or(ApiError::Unauthorized)?;
-
-    let token = value
-        .strip_prefix("Bearer ")
-        .or_else(|| value.strip_prefix("bearer "))
-        .ok_or(ApiError::Unauthorized)?
-        .trim();
-
-    if token.is_empty() {
-        Err(ApiError::Unauthorized)
-    } else {
-        Ok(token)
-    }
-}
-
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -384,23 +687,17 @@ async fn shutdown_signal() {
 
 async fn get_configs(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: AdminUser,
 ) -> Result<Json<Vec<faucet_core::models::SystemConfig>>, ApiError> {
-    let _user = resolve_user(&state, extract_bearer(&headers)?).await?;
-    // TODO: 检查用户是否为管理员
-    
     let configs = state.faucet.get_all_configs().await?;
     Ok(Json(configs))
 }
 
 async fn update_limit_config(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    _admin: AdminUser,
     Json(payload): Json<faucet_core::models::LimitConfigUpdate>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
-    let _user = resolve_user(&state, extract_bearer(&headers)?).await?;
-    // TODO: 检查用户是否为管理员
-    
     state.faucet.update_limit_config(&payload).await?;
     
     Ok(Json(serde_json::json!({
@@ -408,3 +705,423 @@ async fn update_limit_config(
         "success": true
     })))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use axum::http::{header, Method};
+    use faucet_core::config::MemoryConfig;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const ADMIN_ROUTES: [(Method, &str); 4] = [
+        (Method::POST, "/api/admin/role"),
+        (Method::GET, "/api/admin/config"),
+        (Method::POST, "/api/admin/config/limits"),
+        (Method::POST, "/api/admin/limits/reset"),
+    ];
+
+    fn test_config() -> AppConfig {
+        serde_json::from_value(serde_json::json!({
+            "server": { "http_addr": "127.0.0.1:0", "public_base_url": "http://localhost" },
+            "limits": {
+                "default_amount": 100,
+                "default_daily_cap": 500,
+                "privileged_amount": 1000,
+                "privileged_daily_cap": 5000,
+            },
+            "auth": {
+                "google_client_id": "test-client",
+                "google_client_secret": "",
+                "privileged_domains": [],
+            },
+            "queue": {
+                "visibility_timeout": "30s",
+                "retry_backoff": "10s",
+                "max_retries": 3,
+            },
+            "database": { "kind": "postgres", "url": "" },
+            "telemetry": { "json": false, "otlp_endpoint": null },
+        }))
+        .unwrap()
+    }
+
+    fn test_state() -> AppState {
+        // 全局 recorder 只能安装一次
+        static METRICS: OnceLock<PrometheusHandle> = OnceLock::new();
+        let config = test_config();
+        let faucet = Arc::new(FaucetService::new(
+            db::open_memory(&MemoryConfig::default()).unwrap(),
+            Arc::new(ChainClient::from_config(None).unwrap()),
+            config.limits.clone(),
+            &config.auth,
+            &config.queue,
+        ).unwrap());
+        AppState {
+            faucet,
+            identity: IdentityProviders::from_config(&config.auth).unwrap(),
+            jwt_service: JwtService::new("test-secret").unwrap(),
+            config,
+            metrics: METRICS
+                .get_or_init(|| metrics::install(None).unwrap())
+                .clone(),
+        }
+    }
+
+    async fn login(state: &AppState, handle: &str, role: Option<Role>) -> SessionResponse {
+        let mut user = state
+            .faucet
+            .touch_user(Identity {
+                channel: Channel::Web,
+                handle,
+                domain: None,
+            })
+            .await
+            .unwrap();
+        if let Some(role) = role {
+            let mut root = User::new(Channel::Web, "root@example.com");
+            root.role = Role::Admin;
+            user = state
+                .faucet
+                .set_role(&root, Channel::Web, handle, role)
+                .await
+                .unwrap();
+        }
+        let session = state
+            .faucet
+            .start_session(&user, chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        session_response(state, &user, session).await.unwrap()
+    }
+
+    async fn send(
+        router: &Router,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or_default())
+    }
+
+    async fn call(router: &Router, method: Method, uri: &str, token: Option<&str>) -> StatusCode {
+        send(router, method, uri, token, serde_json::json!({})).await.0
+    }
+
+    #[tokio::test]
+    async fn admin_routes_reject_non_admins() {
+        let state = test_state();
+        let user = login(&state, "user@example.com", None).await.token;
+        let privileged = login(&state, "vip@example.com", Some(Role::Privileged)).await.token;
+        let router = build_router(state);
+
+        for (method, uri) in ADMIN_ROUTES {
+            assert_eq!(
+                call(&router, method.clone(), uri, None).await,
+                StatusCode::UNAUTHORIZED,
+                "{uri}"
+            );
+            assert_eq!(
+                call(&router, method.clone(), uri, Some("not-a-jwt")).await,
+                StatusCode::UNAUTHORIZED,
+                "{uri}"
+            );
+            assert_eq!(
+                call(&router, method.clone(), uri, Some(&user)).await,
+                StatusCode::FORBIDDEN,
+                "{uri}"
+            );
+            assert_eq!(
+                call(&router, method, uri, Some(&privileged)).await,
+                StatusCode::FORBIDDEN,
+                "{uri}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn admin_token_passes_extractor() {
+        let state = test_state();
+        let admin = login(&state, "admin@example.com", Some(Role::Admin)).await.token;
+        let user = login(&state, "user@example.com", None).await.token;
+        let router = build_router(state);
+
+        assert_eq!(
+            call(&router, Method::GET, "/api/admin/config", Some(&admin)).await,
+            StatusCode::OK
+        );
+        assert_eq!(call(&router, Method::GET, "/api/me", Some(&user)).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_tokens_and_logout_revokes_session() {
+        let state = test_state();
+        let session = login(&state, "user@example.com", None).await;
+        let router = build_router(state);
+        let refresh = |token: &str| serde_json::json!({ "refresh_token": token });
+
+        let (status, body) = send(
+            &router,
+            Method::POST,
+            "/api/session/refresh",
+            None,
+            refresh(&session.refresh_token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let access = body["token"].as_str().unwrap().to_string();
+        let rotated = body["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(rotated, session.refresh_token);
+
+        // 刷新令牌只能使用一次
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/refresh",
+            None,
+            refresh(&session.refresh_token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        assert_eq!(call(&router, Method::GET, "/api/me", Some(&access)).await, StatusCode::OK);
+        assert_eq!(
+            call(&router, Method::DELETE, "/api/session", Some(&access)).await,
+            StatusCode::NO_CONTENT
+        );
+        // 登出后当前访问令牌与该会话的刷新令牌都失效
+        assert_eq!(
+            call(&router, Method::GET, "/api/me", Some(&access)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        let (status, _) =
+            send(&router, Method::POST, "/api/session/refresh", None, refresh(&rotated)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn demoted_admin_loses_access_before_token_expires() {
+        let state = test_state();
+        let admin = login(&state, "admin@example.com", Some(Role::Admin)).await;
+        login(&state, "admin@example.com", Some(Role::User)).await;
+        let router = build_router(state);
+
+        assert_eq!(
+            call(&router, Method::GET, "/api/admin/config", Some(&admin.token)).await,
+            StatusCode::FORBIDDEN
+        );
+        let (status, body) = send(
+            &router,
+            Method::POST,
+            "/api/session/refresh",
+            None,
+            serde_json::json!({ "refresh_token": admin.refresh_token }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user"]["role"], "user");
+    }
+
+    #[tokio::test]
+    async fn history_includes_linked_identities() {
+        let state = test_state();
+        let session = login(&state, "user@example.com", None).await;
+        let web = state
+            .faucet
+            .find_user(Channel::Web, "user@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        let bot = state
+            .faucet
+            .touch_user(Identity {
+                channel: Channel::Telegram,
+                handle: "4242",
+                domain: None,
+            })
+            .await
+            .unwrap();
+        let link = state.faucet.issue_link_code(&web).await.unwrap();
+        let bot = state.faucet.link_identity(&bot, &link.code).await.unwrap();
+
+        let recipient: AccountAddress = "0x1".parse().unwrap();
+        let from_web = state.faucet.mint(&web, recipient, 10).await.unwrap();
+        let from_bot = state.faucet.mint(&bot, recipient, 10).await.unwrap();
+        let router = build_router(state);
+
+        let (status, body) =
+            send(&router, Method::GET, "/api/me/mints", Some(&session.token), serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<_> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![from_bot.request.id.to_string(), from_web.request.id.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn session_requires_a_configured_provider() {
+        let router = build_router(test_state());
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session",
+            None,
+            serde_json::json!({ "provider": "github", "code": "abc" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // 缺少 Google 需要的 id_token
+        let (status, _) = send(&router, Method::POST, "/api/session", None, serde_json::json!({})).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn wallet_signature_logs_in_once_per_nonce() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let router = build_router(test_state());
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        let challenge = || async {
+            let (status, body) = send(&router, Method::GET, "/api/session/nonce", None, serde_json::json!({})).await;
+            assert_eq!(status, StatusCode::OK);
+            (body["nonce"].as_str().unwrap().to_string(), body["message"].as_str().unwrap().to_string())
+        };
+        let sign = |text: &str| hex::encode(key.sign(text.as_bytes()).to_bytes());
+
+        let (nonce, message) = challenge().await;
+        let login = serde_json::json!({
+            "nonce": nonce,
+            "public_key": public_key,
+            "signature": sign(&message),
+        });
+        let (status, body) = send(&router, Method::POST, "/api/session/wallet", None, login.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let address = faucet_core::aptos::verify_signed_message(
+            key.verifying_key().as_bytes(),
+            message.as_bytes(),
+            &key.sign(message.as_bytes()).to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(body["user"]["handle"], address.to_string());
+        assert_eq!(
+            call(&router, Method::GET, "/api/me", body["token"].as_str()).await,
+            StatusCode::OK
+        );
+
+        // 同一挑战不能重放
+        let (status, _) = send(&router, Method::POST, "/api/session/wallet", None, login).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // 钱包标准的 signMessage 包装
+        let (nonce, message) = challenge().await;
+        let full_message = format!("APTOS\naddress: {address}\nmessage: {message}\nnonce: {nonce}");
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/wallet",
+            None,
+            serde_json::json!({
+                "nonce": nonce,
+                "public_key": public_key,
+                "signature": sign(&full_message),
+                "full_message": full_message,
+                "address": address.to_string(),
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // 声明的地址与公钥不符，或签名的不是本次挑战
+        let (nonce, message) = challenge().await;
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/wallet",
+            None,
+            serde_json::json!({
+                "nonce": nonce,
+                "public_key": public_key,
+                "signature": sign(&message),
+                "address": "0x1",
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (nonce, fresh) = challenge().await;
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/wallet",
+            None,
+            serde_json::json!({ "nonce": nonce, "public_key": public_key, "signature": sign(&message) }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // 验签失败不会用掉挑战
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/wallet",
+            None,
+            serde_json::json!({ "nonce": nonce, "public_key": public_key, "signature": sign(&fresh) }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        // 不是本服务签发的 nonce
+        let forged = message.replace(&nonce, "forged");
+        let (status, _) = send(
+            &router,
+            Method::POST,
+            "/api/session/wallet",
+            None,
+            serde_json::json!({ "nonce": "forged", "public_key": public_key, "signature": sign(&forged) }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn oidc_providers_cannot_take_builtin_names() {
+        for name in ["google", "github", "wallet"] {
+            let mut config = test_config();
+            config.auth.oidc.push(faucet_core::config::OidcProviderConfig {
+                name: name.to_string(),
+                issuer: "https://idp.example.com".to_string(),
+                client_id: "client".to_string(),
+                client_secret: None,
+                domain_claim: None,
+                privileged_domains: Vec::new(),
+            });
+            assert!(IdentityProviders::from_config(&config.auth).is_err(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use chrono::SecondsFormat;
+use serde::Deserialize;
+
+use faucet_core::{
+    aptos::verify_signed_message,
+    models::{AccountAddress, LoginNonce},
+};
+
+/// `POST /api/session/wallet` 的请求体
+///
+/// 登录地址由公钥推导（`sha3(public_key || 0x00)`），不查询链上的 `authentication_key`：
+/// 轮换过认证密钥的账户，其地址与当前公钥不再对应，无法用钱包登录。
+#[derive(Debug, Deserialize)]
+pub struct WalletLogin {
+    pub nonce: String,
+    /// 单签 Ed25519 公钥，十六进制，可带 `0x`
+    pub public_key: String,
+    pub signature: String,
+    /// 可选：声明的账户地址，必须与公钥推导出的地址一致
+    #[serde(default)]
+    pub address: Option<String>,
+    /// 钱包 `signMessage` 实际签名的完整文本（以 `APTOS` 开头），直接对挑战签名时省略
+    #[serde(default)]
+    pub full_message: Option<String>,
+}
+
+/// 挑战文本。只由 nonce 与过期时间决定，验证时按存储中的记录重新生成，不信任客户端回传的内容
+pub fn challenge_message(public_base_url: &str, nonce: &LoginNonce) -> String {
+    let base_url = public_base_url.trim_end_matches('/');
+    let domain = base_url.split_once("://").map_or(base_url, |(_, rest)| rest);
+    format!(
+        "{domain} wants you to sign in with your Aptos account.\n\n\
+         URI: {base_url}\n\
+         Nonce: {}\n\
+         Expiration Time: {}",
+        nonce.nonce,
+        nonce.expires_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+    )
+}
+
+/// 校验签名覆盖了这次的挑战，返回签名公钥对应的账户地址
+pub fn verify(login: &WalletLogin, public_base_url: &str, nonce: &LoginNonce) -> Result<AccountAddress> {
+    let expected = challenge_message(public_base_url, nonce);
+    let signed = match &login.full_message {
+        // 钱包标准在消息外加上 `APTOS` 头与可选的 address/application/chainId，
+        // 末尾固定为 `message: ...\nnonce: ...`
+        Some(full) => {
+            let suffix = format!("message: {expected}\nnonce: {}", nonce.nonce);
+            if !full.starts_with("APTOS\n") || !full.ends_with(&suffix) {
+                anyhow::bail!("signed message does not contain the challenge");
+            }
+            full.as_str()
+        }
+        None => expected.as_str(),
+    };
+
+    let public_key: [u8; 32] = decode_hex(&login.public_key)
+        .context("invalid public key")?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("public key must be 32 bytes"))?;
+    let signature: [u8; 64] = decode_hex(&login.signature)
+        .context("invalid signature")?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("signature must be 64 bytes"))?;
+    let address = verify_signed_message(&public_key, signed.as_bytes(), &signature)?;
+
+    if let Some(claimed) = &login.address {
+        let claimed: AccountAddress = claimed.parse()?;
+        if claimed != address {
+            anyhow::bail!("address {claimed} does not match the public key");
+        }
+    }
+    Ok(address)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    let value = value.trim();
+    Ok(hex::decode(value.strip_prefix("0x").unwrap_or(value))?)
+}